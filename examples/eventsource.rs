@@ -67,6 +67,9 @@ async fn event_source() {
                     calendar_alert.calendar_event_id, calendar_alert.alert_id
                 );
             }
+            PushNotification::Reconnected => {
+                println!("-> Reconnected.");
+            }
         }
     }
 }
@@ -36,7 +36,7 @@ async fn websocket() {
     // Connect to the WebSocket endpoint
     let mut ws_stream = client.connect_ws().await.unwrap();
 
-    // Read WS messages on a separate thread
+    // Read push notifications on a separate thread
     let (stream_tx, mut stream_rx) = mpsc::channel::<WebSocketMessage>(100);
     tokio::spawn(async move {
         while let Some(change) = ws_stream.next().await {
@@ -52,23 +52,17 @@ async fn websocket() {
         .name("WebSocket Test")
         .create_id()
         .unwrap();
-    let request_id = request.send_ws().await.unwrap();
-
-    // Read response from WS stream
-    let mailbox_id = if let Some(WebSocketMessage::Response(mut response)) = stream_rx.recv().await
-    {
-        assert_eq!(request_id, response.request_id().unwrap());
-        response
-            .pop_method_response()
-            .unwrap()
-            .unwrap_set_mailbox()
-            .unwrap()
-            .created(&create_id)
-            .unwrap()
-            .take_id()
-    } else {
-        unreachable!()
-    };
+    let mailbox_id = request
+        .send_ws()
+        .await
+        .unwrap()
+        .pop_method_response()
+        .unwrap()
+        .unwrap_set_mailbox()
+        .unwrap()
+        .created(&create_id)
+        .unwrap()
+        .take_id();
 
     // Enable push notifications over WS
     client
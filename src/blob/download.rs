@@ -9,63 +9,295 @@
  * except according to those terms.
  */
 
-use reqwest::header::CONTENT_TYPE;
+use reqwest::{
+    header::{HeaderValue, CONTENT_TYPE, RANGE},
+    StatusCode,
+};
 
-use crate::{client::Client, core::session::URLPart};
+use crate::{
+    client::Client,
+    core::session::URLPart,
+    transport::{HttpRequest, HttpResponse, StreamBody},
+};
 
-#[cfg(feature = "blocking")]
-use reqwest::blocking::Client as HttpClient;
-#[cfg(feature = "async")]
-use reqwest::Client as HttpClient;
+pub struct DownloadRequest<'x> {
+    client: &'x Client,
+    account_id: String,
+    blob_id: String,
+    name: Option<String>,
+    content_type: Option<String>,
+    offset: u64,
+}
 
 impl Client {
     #[maybe_async::maybe_async]
     pub async fn download(&self, blob_id: &str) -> crate::Result<Vec<u8>> {
-        let account_id = self.default_account_id();
+        self.download_request(blob_id)
+            .send()
+            .await
+            .map(|response| response.into_body())
+    }
+
+    /// Downloads a blob as a stream of chunks, without buffering it in memory.
+    ///
+    /// A non-zero `offset` requests the blob contents from that byte onwards using
+    /// an HTTP Range request, which allows resuming an interrupted download.
+    #[cfg(feature = "async")]
+    pub async fn download_stream(
+        &self,
+        blob_id: &str,
+        offset: u64,
+    ) -> crate::Result<impl futures_util::Stream<Item = crate::Result<bytes::Bytes>> + Send + Unpin>
+    {
+        self.download_request(blob_id)
+            .offset(offset)
+            .send_stream()
+            .await
+            .map(|response| response.body)
+    }
+
+    /// Downloads a blob as a reader, without buffering it in memory.
+    ///
+    /// A non-zero `offset` requests the blob contents from that byte onwards using
+    /// an HTTP Range request, which allows resuming an interrupted download.
+    #[cfg(feature = "blocking")]
+    pub fn download_stream(
+        &self,
+        blob_id: &str,
+        offset: u64,
+    ) -> crate::Result<impl std::io::Read + Send> {
+        self.download_request(blob_id)
+            .offset(offset)
+            .send_stream()
+            .map(|response| response.body)
+    }
+
+    /// Builds a download of a blob from the default account, see [DownloadRequest].
+    pub fn download_request(&self, blob_id: impl Into<String>) -> DownloadRequest<'_> {
+        DownloadRequest {
+            account_id: self.default_account_id(),
+            blob_id: blob_id.into(),
+            name: None,
+            content_type: None,
+            offset: 0,
+            client: self,
+        }
+    }
+}
+
+impl<'x> DownloadRequest<'x> {
+    pub fn account_id(mut self, account_id: impl Into<String>) -> Self {
+        self.account_id = account_id.into();
+        self
+    }
+
+    /// Sets the filename the server should use when sending the blob.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the media type the server should use when sending the blob.
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Requests the blob contents starting at `offset` using an HTTP Range request.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Downloads the blob, returning the response headers along with its contents.
+    #[maybe_async::maybe_async]
+    pub async fn send(self) -> crate::Result<HttpResponse> {
+        let response = Client::handle_error(
+            self.client
+                .send_http(
+                    self.http_request().with_timeout(self.client.timeout()),
+                    true,
+                    false,
+                )
+                .await?,
+        )?;
+        self.check_range(response.status())?;
+        Ok(response)
+    }
+
+    /// Downloads the blob, returning the response headers along with a stream of its contents.
+    #[cfg(feature = "async")]
+    pub async fn send_stream(self) -> crate::Result<HttpResponse<crate::transport::ByteStream>> {
+        self.stream().await
+    }
+
+    /// Downloads the blob, returning the response headers along with a reader of its contents.
+    #[cfg(feature = "blocking")]
+    pub fn send_stream(self) -> crate::Result<HttpResponse<crate::transport::ByteReader>> {
+        self.stream()
+    }
+
+    #[maybe_async::maybe_async]
+    async fn stream(self) -> crate::Result<HttpResponse<StreamBody>> {
+        let response = self.client.send_http_stream(self.http_request()).await?;
+        if response.status().is_success() {
+            self.check_range(response.status())?;
+            Ok(response)
+        } else {
+            Err(Client::response_error(&response.collect().await?))
+        }
+    }
+
+    fn check_range(&self, status: StatusCode) -> crate::Result<()> {
+        if self.offset > 0 && status != StatusCode::PARTIAL_CONTENT {
+            Err(crate::Error::Internal(
+                "Server does not support resuming downloads.".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn http_request(&self) -> HttpRequest {
         let mut download_url = String::with_capacity(
-            self.session().download_url().len() + account_id.len() + blob_id.len(),
+            self.client.session().download_url().len() + self.account_id.len() + self.blob_id.len(),
         );
 
-        for part in self.download_url() {
+        for part in self.client.download_url().iter() {
             match part {
                 URLPart::Value(value) => {
                     download_url.push_str(value);
                 }
                 URLPart::Parameter(param) => match param {
                     super::URLParameter::AccountId => {
-                        download_url.push_str(account_id);
+                        url_encode(&mut download_url, &self.account_id);
                     }
                     super::URLParameter::BlobId => {
-                        download_url.push_str(blob_id);
+                        url_encode(&mut download_url, &self.blob_id);
                     }
                     super::URLParameter::Name => {
-                        download_url.push_str("none");
+                        url_encode(&mut download_url, self.name.as_deref().unwrap_or("none"));
                     }
                     super::URLParameter::Type => {
-                        download_url.push_str("application/octet-stream");
+                        url_encode(
+                            &mut download_url,
+                            self.content_type
+                                .as_deref()
+                                .unwrap_or("application/octet-stream"),
+                        );
                     }
                 },
             }
         }
 
-        let mut headers = self.headers().clone();
+        let mut headers = self.client.headers().clone();
         headers.remove(CONTENT_TYPE);
+        if self.offset > 0 {
+            headers.insert(
+                RANGE,
+                HeaderValue::from_str(&format!("bytes={}-", self.offset)).unwrap(),
+            );
+        }
 
-        Client::handle_error(
-            HttpClient::builder()
-                .timeout(self.timeout())
-                .danger_accept_invalid_certs(self.accept_invalid_certs)
-                .redirect(self.redirect_policy())
-                .default_headers(headers)
-                .build()?
-                .get(download_url)
-                .send()
-                .await?,
-        )
-        .await?
-        .bytes()
-        .await
-        .map(|bytes| bytes.to_vec())
-        .map_err(|err| err.into())
+        HttpRequest::get(download_url).with_headers(headers)
+    }
+}
+
+/// Percent-encodes all characters outside the URI Template unreserved set.
+fn url_encode(url: &mut String, value: &str) {
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::CONTENT_DISPOSITION;
+
+    use crate::{client::Client, testing::MockServer};
+
+    #[cfg(feature = "async")]
+    async fn download_all(client: &Client, blob_id: &str, offset: u64) -> Vec<u8> {
+        use futures_util::StreamExt;
+
+        let mut stream = client.download_stream(blob_id, offset).await.unwrap();
+        let mut contents = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            contents.extend_from_slice(&chunk.unwrap());
+        }
+        contents
+    }
+
+    #[cfg(feature = "blocking")]
+    fn download_all(client: &Client, blob_id: &str, offset: u64) -> Vec<u8> {
+        use std::io::Read;
+
+        let mut contents = Vec::new();
+        client
+            .download_stream(blob_id, offset)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn download_stream() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let blob_id = client
+            .upload(None, b"hello world".to_vec(), None)
+            .await
+            .unwrap()
+            .take_blob_id();
+
+        let contents = download_all(&client, &blob_id, 0).await;
+        assert_eq!(contents, b"hello world");
+
+        // Resumes from an offset
+        let contents = download_all(&client, &blob_id, 6).await;
+        assert_eq!(contents, b"world");
+
+        let result = client.download_stream(&blob_id, 11).await;
+        assert!(result.is_err());
+        let result = client.download_stream("unknown", 0).await;
+        assert!(result.is_err());
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn download_request() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let blob_id = client
+            .upload(None, b"%PDF-1.7".to_vec(), Some("application/octet-stream"))
+            .await
+            .unwrap()
+            .take_blob_id();
+
+        let response = client
+            .download_request(&blob_id)
+            .account_id(client.default_account_id())
+            .name("Q3 report.pdf")
+            .content_type("application/pdf")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.content_type(), Some("application/pdf"));
+        assert_eq!(
+            response.headers().get(CONTENT_DISPOSITION).unwrap(),
+            "attachment; filename=\"Q3 report.pdf\""
+        );
+        assert_eq!(response.body(), b"%PDF-1.7");
+
+        let response = client
+            .download_request(&blob_id)
+            .account_id("unknown")
+            .send()
+            .await;
+        assert!(response.is_err());
     }
 }
@@ -0,0 +1,95 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use base64::{engine::general_purpose, Engine};
+use serde::Serialize;
+
+use crate::core::get::GetObject;
+
+use super::Blob;
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct GetArguments {
+    #[serde(rename = "offset")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+
+    #[serde(rename = "length")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+}
+
+impl Blob {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn take_id(&mut self) -> String {
+        std::mem::take(&mut self.id)
+    }
+
+    pub fn data_as_text(&self) -> Option<&str> {
+        self.data_as_text.as_deref()
+    }
+
+    pub fn data_as_base64(&self) -> Option<&str> {
+        self.data_as_base64.as_deref()
+    }
+
+    /// Returns the blob contents, decoding them if they were sent as base64.
+    pub fn data(&self) -> Option<Vec<u8>> {
+        if let Some(text) = &self.data_as_text {
+            Some(text.as_bytes().to_vec())
+        } else {
+            self.data_as_base64
+                .as_ref()
+                .and_then(|data| general_purpose::STANDARD.decode(data).ok())
+        }
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+
+    pub fn digest_sha(&self) -> Option<&str> {
+        self.digest_sha.as_deref()
+    }
+
+    pub fn digest_sha_256(&self) -> Option<&str> {
+        self.digest_sha_256.as_deref()
+    }
+
+    /// Returns `true` if the contents requested as text are not valid UTF-8.
+    pub fn is_encoding_problem(&self) -> bool {
+        self.is_encoding_problem
+    }
+
+    /// Returns `true` if the requested range extends past the end of the blob.
+    pub fn is_truncated(&self) -> bool {
+        self.is_truncated
+    }
+}
+
+impl GetArguments {
+    pub fn offset(&mut self, offset: usize) -> &mut Self {
+        self.offset = offset.into();
+        self
+    }
+
+    pub fn length(&mut self, length: usize) -> &mut Self {
+        self.length = length.into();
+        self
+    }
+}
+
+impl GetObject for Blob {
+    type GetArguments = GetArguments;
+}
@@ -11,11 +11,20 @@
 
 use crate::{
     client::Client,
-    core::request::{Arguments, Request},
-    Method,
+    core::{
+        get::GetRequest,
+        request::{Arguments, Request},
+        response::BlobGetResponse,
+    },
+    DataType, Method, URI,
 };
 
-use super::copy::{CopyBlobRequest, CopyBlobResponse};
+use super::{
+    copy::{CopyBlobRequest, CopyBlobResponse},
+    lookup::{BlobLookup, LookupBlobRequest, LookupBlobResponse},
+    upload_blob::{UploadBlobRequest, UploadBlobResponse},
+    Blob, Property,
+};
 
 impl Client {
     #[maybe_async::maybe_async]
@@ -32,6 +41,58 @@ impl Client {
             .await?
             .copied(&blob_id)
     }
+
+    /// Uploads a blob inline within an API request, returning its id.
+    #[maybe_async::maybe_async]
+    pub async fn blob_upload(
+        &self,
+        data: impl AsRef<[u8]>,
+        content_type: Option<&str>,
+    ) -> crate::Result<String> {
+        let mut request = self.build();
+        let upload = request.upload_blob().create().bytes(data);
+        if let Some(content_type) = content_type {
+            upload.content_type(content_type);
+        }
+        let create_id = upload.create_id();
+        request
+            .send_single::<UploadBlobResponse>()
+            .await?
+            .created(&create_id)
+            .map(|mut blob| blob.take_id())
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn blob_get(
+        &self,
+        blob_id: &str,
+        properties: Option<impl IntoIterator<Item = Property>>,
+    ) -> crate::Result<Option<Blob>> {
+        let mut request = self.build();
+        let get_request = request.get_blob().ids([blob_id]);
+        if let Some(properties) = properties {
+            get_request.properties(properties);
+        }
+        request
+            .send_single::<BlobGetResponse>()
+            .await
+            .map(|mut r| r.take_list().pop())
+    }
+
+    /// Returns the ids of the objects of the given types that reference a blob.
+    #[maybe_async::maybe_async]
+    pub async fn blob_lookup(
+        &self,
+        blob_id: &str,
+        type_names: impl IntoIterator<Item = DataType>,
+    ) -> crate::Result<Option<BlobLookup>> {
+        let mut request = self.build();
+        request.lookup_blob().type_names(type_names).ids([blob_id]);
+        request
+            .send_single::<LookupBlobResponse>()
+            .await
+            .map(|mut r| r.take_list().pop())
+    }
 }
 
 impl Request<'_> {
@@ -48,4 +109,142 @@ impl Request<'_> {
     pub async fn send_copy_blob(self) -> crate::Result<CopyBlobResponse> {
         self.send_single().await
     }
+
+    pub fn upload_blob(&mut self) -> &mut UploadBlobRequest {
+        self.add_capability(URI::Blob);
+        self.add_method_call(
+            Method::UploadBlob,
+            Arguments::blob_upload(self.params(Method::UploadBlob)),
+        )
+        .blob_upload_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_upload_blob(self) -> crate::Result<UploadBlobResponse> {
+        self.send_single().await
+    }
+
+    pub fn get_blob(&mut self) -> &mut GetRequest<Blob> {
+        self.add_capability(URI::Blob);
+        self.add_method_call(
+            Method::GetBlob,
+            Arguments::blob_get(self.params(Method::GetBlob)),
+        )
+        .blob_get_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_get_blob(self) -> crate::Result<BlobGetResponse> {
+        self.send_single().await
+    }
+
+    pub fn lookup_blob(&mut self) -> &mut LookupBlobRequest {
+        self.add_capability(URI::Blob);
+        self.add_method_call(
+            Method::LookupBlob,
+            Arguments::blob_lookup(self.params(Method::LookupBlob)),
+        )
+        .blob_lookup_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_lookup_blob(self) -> crate::Result<LookupBlobResponse> {
+        self.send_single().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        blob::{upload_blob::UploadBlobResponse, Property},
+        core::response::BlobGetResponse,
+        mailbox::Role,
+        testing::MockServer,
+        DataType,
+    };
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn blob_management() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+
+        let blob_id = client
+            .blob_upload(b"hello world", Some("text/plain"))
+            .await
+            .unwrap();
+        let blob = client
+            .blob_get(&blob_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blob.data_as_text(), Some("hello world"));
+        assert_eq!(blob.size(), Some(11));
+
+        // Blobs can be built from existing ones
+        let mut request = client.build();
+        let upload = request
+            .upload_blob()
+            .create()
+            .blob(&blob_id, Some(6), Some(5))
+            .bytes([0xff])
+            .content_type("application/octet-stream");
+        let create_id = upload.create_id();
+        let new_blob_id = request
+            .send_single::<UploadBlobResponse>()
+            .await
+            .unwrap()
+            .created(&create_id)
+            .unwrap()
+            .take_id();
+
+        let mut request = client.build();
+        request
+            .get_blob()
+            .ids([&new_blob_id])
+            .properties([Property::DataAsText, Property::DataAsBase64])
+            .arguments()
+            .offset(1)
+            .length(10);
+        let blob = request
+            .send_single::<BlobGetResponse>()
+            .await
+            .unwrap()
+            .take_list()
+            .pop()
+            .unwrap();
+        assert_eq!(blob.data_as_text(), None);
+        assert_eq!(blob.data(), Some(b"orld\xff".to_vec()));
+        assert!(blob.is_truncated());
+
+        // Blobs referenced by emails
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let email = client
+            .email_import(
+                b"Subject: Blob\r\n\r\nHello\r\n".to_vec(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap();
+        let lookup = client
+            .blob_lookup(
+                email.blob_id().unwrap(),
+                [DataType::Email, DataType::Mailbox],
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lookup.matched_ids(&DataType::Email), [email.id().unwrap()]);
+        assert_eq!(lookup.matched_ids(&DataType::Mailbox), [inbox_id]);
+        let lookup = client
+            .blob_lookup("unknown", [DataType::Email])
+            .await
+            .unwrap();
+        assert!(lookup.is_none());
+    }
 }
@@ -0,0 +1,105 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{core::RequestParams, DataType};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LookupBlobRequest {
+    #[serde(rename = "accountId")]
+    account_id: String,
+    #[serde(rename = "typeNames")]
+    type_names: Vec<DataType>,
+    #[serde(rename = "ids")]
+    ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LookupBlobResponse {
+    #[serde(rename = "accountId")]
+    account_id: String,
+    #[serde(rename = "list")]
+    list: Vec<BlobLookup>,
+    #[serde(rename = "notFound")]
+    #[serde(default)]
+    not_found: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlobLookup {
+    #[serde(rename = "id")]
+    id: String,
+    #[serde(rename = "matchedIds")]
+    matched_ids: AHashMap<DataType, Vec<String>>,
+}
+
+impl LookupBlobRequest {
+    pub fn new(params: RequestParams) -> Self {
+        LookupBlobRequest {
+            account_id: params.account_id,
+            type_names: vec![],
+            ids: vec![],
+        }
+    }
+
+    pub fn account_id(&mut self, account_id: impl Into<String>) -> &mut Self {
+        self.account_id = account_id.into();
+        self
+    }
+
+    pub fn type_names(&mut self, type_names: impl IntoIterator<Item = DataType>) -> &mut Self {
+        self.type_names = type_names.into_iter().collect();
+        self
+    }
+
+    pub fn ids<U, V>(&mut self, ids: U) -> &mut Self
+    where
+        U: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.ids = ids.into_iter().map(|v| v.into()).collect();
+        self
+    }
+}
+
+impl LookupBlobResponse {
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn list(&self) -> &[BlobLookup] {
+        &self.list
+    }
+
+    pub fn take_list(&mut self) -> Vec<BlobLookup> {
+        std::mem::take(&mut self.list)
+    }
+
+    pub fn not_found(&self) -> &[String] {
+        &self.not_found
+    }
+}
+
+impl BlobLookup {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the ids of the objects of the given type that reference the blob.
+    pub fn matched_ids(&self, data_type: &DataType) -> &[String] {
+        self.matched_ids
+            .get(data_type)
+            .map(|ids| ids.as_slice())
+            .unwrap_or_default()
+    }
+}
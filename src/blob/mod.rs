@@ -9,12 +9,126 @@
  * except according to those terms.
  */
 
-use crate::core::session::URLParser;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{session::URLParser, Object};
 
 pub mod copy;
 pub mod download;
+pub mod get;
 pub mod helpers;
+pub mod lookup;
 pub mod upload;
+pub mod upload_blob;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blob {
+    #[serde(rename = "id")]
+    id: String,
+
+    #[serde(rename = "data:asText")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_as_text: Option<String>,
+
+    #[serde(rename = "data:asBase64")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_as_base64: Option<String>,
+
+    #[serde(rename = "size")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<usize>,
+
+    #[serde(rename = "digest:sha")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest_sha: Option<String>,
+
+    #[serde(rename = "digest:sha-256")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest_sha_256: Option<String>,
+
+    #[serde(rename = "isEncodingProblem")]
+    #[serde(default)]
+    is_encoding_problem: bool,
+
+    #[serde(rename = "isTruncated")]
+    #[serde(default)]
+    is_truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
+pub enum Property {
+    #[serde(rename = "id")]
+    Id,
+    #[serde(rename = "data")]
+    Data,
+    #[serde(rename = "data:asText")]
+    DataAsText,
+    #[serde(rename = "data:asBase64")]
+    DataAsBase64,
+    #[serde(rename = "size")]
+    Size,
+    #[serde(rename = "digest:sha")]
+    DigestSha,
+    #[serde(rename = "digest:sha-256")]
+    DigestSha256,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobCapabilities {
+    #[serde(rename = "maxSizeBlobSet")]
+    max_size_blob_set: Option<usize>,
+
+    #[serde(rename = "maxDataSources")]
+    max_data_sources: usize,
+
+    #[serde(rename = "supportedTypeNames")]
+    supported_type_names: Vec<String>,
+
+    #[serde(rename = "supportedDigestAlgorithms")]
+    supported_digest_algorithms: Vec<String>,
+}
+
+impl Display for Property {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Property::Id => write!(f, "id"),
+            Property::Data => write!(f, "data"),
+            Property::DataAsText => write!(f, "data:asText"),
+            Property::DataAsBase64 => write!(f, "data:asBase64"),
+            Property::Size => write!(f, "size"),
+            Property::DigestSha => write!(f, "digest:sha"),
+            Property::DigestSha256 => write!(f, "digest:sha-256"),
+        }
+    }
+}
+
+impl Object for Blob {
+    type Property = Property;
+
+    fn requires_account_id() -> bool {
+        true
+    }
+}
+
+impl BlobCapabilities {
+    pub fn max_size_blob_set(&self) -> Option<usize> {
+        self.max_size_blob_set
+    }
+
+    pub fn max_data_sources(&self) -> usize {
+        self.max_data_sources
+    }
+
+    pub fn supported_type_names(&self) -> &[String] {
+        &self.supported_type_names
+    }
+
+    pub fn supported_digest_algorithms(&self) -> &[String] {
+        &self.supported_digest_algorithms
+    }
+}
 
 pub enum URLParameter {
     AccountId,
@@ -9,15 +9,27 @@
  * except according to those terms.
  */
 
-use reqwest::header::CONTENT_TYPE;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use reqwest::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
 use serde::Deserialize;
 
-use crate::{client::Client, core::session::URLPart};
+use crate::{
+    client::Client,
+    core::session::URLPart,
+    transport::{HttpRequest, StreamBody},
+};
 
-#[cfg(feature = "blocking")]
-use reqwest::blocking::Client as HttpClient;
 #[cfg(feature = "async")]
-use reqwest::Client as HttpClient;
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+type ProgressCallback = Box<dyn FnMut(u64, Option<u64>) + Send>;
 
 #[derive(Debug, Deserialize)]
 pub struct UploadResponse {
@@ -34,6 +46,24 @@ pub struct UploadResponse {
     size: usize,
 }
 
+/// Upload of a blob streamed from a source instead of being held in memory.
+///
+/// JMAP offers no way to resume a partial upload, a failed upload has to be restarted.
+pub struct UploadRequest<'x> {
+    client: &'x Client,
+    account_id: String,
+    content_type: Option<String>,
+    progress: Option<ProgressCallback>,
+}
+
+struct UploadProgress {
+    sent: u64,
+    size: Option<u64>,
+    max_size: u64,
+    exceeded: Arc<AtomicBool>,
+    callback: Option<ProgressCallback>,
+}
+
 impl Client {
     #[maybe_async::maybe_async]
     pub async fn upload(
@@ -42,11 +72,61 @@ impl Client {
         blob: Vec<u8>,
         content_type: Option<&str>,
     ) -> crate::Result<UploadResponse> {
-        let account_id = account_id.unwrap_or_else(|| self.default_account_id());
+        let default_account_id;
+        let account_id = match account_id {
+            Some(account_id) => account_id,
+            None => {
+                default_account_id = self.default_account_id();
+                &default_account_id
+            }
+        };
+
+        let max_size = self.max_size_upload();
+        if max_size > 0 && blob.len() as u64 > max_size {
+            return Err(size_error(blob.len() as u64, max_size));
+        }
+
+        let limiter = self.upload_limiter();
+        let _permit = limiter.acquire().await;
+        serde_json::from_slice::<UploadResponse>(
+            Client::handle_error(
+                self.send_http(
+                    HttpRequest::post(self.blob_upload_url(account_id))
+                        .with_headers(self.headers().clone())
+                        .with_header(CONTENT_TYPE, upload_content_type(content_type)?)
+                        .with_body(blob)
+                        .with_timeout(self.timeout()),
+                    false,
+                    false,
+                )
+                .await?,
+            )?
+            .body(),
+        )
+        .map_err(|err| err.into())
+    }
+
+    /// Builds an upload to the default account, see [UploadRequest].
+    pub fn upload_request(&self) -> UploadRequest<'_> {
+        UploadRequest {
+            account_id: self.default_account_id(),
+            content_type: None,
+            progress: None,
+            client: self,
+        }
+    }
+
+    fn max_size_upload(&self) -> u64 {
+        self.session()
+            .core_capabilities()
+            .map_or(0, |capabilities| capabilities.max_size_upload() as u64)
+    }
+
+    fn blob_upload_url(&self, account_id: &str) -> String {
         let mut upload_url =
             String::with_capacity(self.session().upload_url().len() + account_id.len());
 
-        for part in self.upload_url() {
+        for part in self.upload_url().iter() {
             match part {
                 URLPart::Value(value) => {
                     upload_url.push_str(value);
@@ -59,31 +139,218 @@ impl Client {
             }
         }
 
-        serde_json::from_slice::<UploadResponse>(
-            &Client::handle_error(
-                HttpClient::builder()
-                    .timeout(self.timeout())
-                    .danger_accept_invalid_certs(self.accept_invalid_certs)
-                    .redirect(self.redirect_policy())
-                    .default_headers(self.headers().clone())
-                    .build()?
-                    .post(upload_url)
-                    .header(
-                        CONTENT_TYPE,
-                        content_type.unwrap_or("application/octet-stream"),
-                    )
-                    .body(blob)
-                    .send()
-                    .await?,
-            )
-            .await?
-            .bytes()
-            .await?,
-        )
-        .map_err(|err| err.into())
+        upload_url
     }
 }
 
+impl<'x> UploadRequest<'x> {
+    pub fn account_id(mut self, account_id: impl Into<String>) -> Self {
+        self.account_id = account_id.into();
+        self
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Sets a callback invoked with the number of bytes sent so far and the total size, if known.
+    pub fn progress(mut self, progress: impl FnMut(u64, Option<u64>) + Send + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Uploads the chunks of a stream. When `size` is known, uploads larger than the
+    /// server's `maxSizeUpload` are rejected before sending any data.
+    #[cfg(feature = "async")]
+    pub async fn send_stream(
+        mut self,
+        stream: impl futures_util::Stream<Item = crate::Result<bytes::Bytes>> + Send + 'static,
+        size: Option<u64>,
+    ) -> crate::Result<UploadResponse> {
+        use futures_util::StreamExt;
+
+        let mut progress = self.start(size)?;
+        let exceeded = progress.exceeded.clone();
+        let mut stream = Box::pin(stream);
+        let body = Box::pin(async_stream::stream! {
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(chunk) if progress.advance(chunk.len()) => {
+                        yield Ok(chunk);
+                    }
+                    Ok(_) => {
+                        yield Err(progress.size_error());
+                        break;
+                    }
+                    Err(err) => {
+                        yield Err(err);
+                        break;
+                    }
+                }
+            }
+        });
+        self.send(body, size, exceeded).await
+    }
+
+    /// Uploads the contents of a reader, see [UploadRequest::send_stream].
+    #[cfg(feature = "async")]
+    pub async fn send_reader(
+        self,
+        reader: impl tokio::io::AsyncRead + Send + 'static,
+        size: Option<u64>,
+    ) -> crate::Result<UploadResponse> {
+        use tokio::io::AsyncReadExt;
+
+        let mut reader = Box::pin(reader);
+        let stream = async_stream::stream! {
+            let mut buf = vec![0u8; UPLOAD_CHUNK_SIZE];
+            loop {
+                match reader.read(&mut buf).await {
+                    Ok(0) => break,
+                    Ok(len) => yield Ok(bytes::Bytes::copy_from_slice(&buf[..len])),
+                    Err(err) => {
+                        yield Err(read_error(err));
+                        break;
+                    }
+                }
+            }
+        };
+        self.send_stream(stream, size).await
+    }
+
+    /// Uploads the contents of a file.
+    #[cfg(feature = "async")]
+    pub async fn send_file(self, path: impl AsRef<Path>) -> crate::Result<UploadResponse> {
+        let file = tokio::fs::File::open(path).await.map_err(read_error)?;
+        let size = file.metadata().await.map_err(read_error)?.len();
+        self.send_reader(file, Some(size)).await
+    }
+
+    /// Uploads the contents of a reader. When `size` is known, uploads larger than the
+    /// server's `maxSizeUpload` are rejected before sending any data.
+    #[cfg(feature = "blocking")]
+    pub fn send_reader(
+        mut self,
+        reader: impl std::io::Read + Send + 'static,
+        size: Option<u64>,
+    ) -> crate::Result<UploadResponse> {
+        let progress = self.start(size)?;
+        let exceeded = progress.exceeded.clone();
+        self.send(Box::new(UploadReader { reader, progress }), size, exceeded)
+    }
+
+    /// Uploads the contents of a file.
+    #[cfg(feature = "blocking")]
+    pub fn send_file(self, path: impl AsRef<Path>) -> crate::Result<UploadResponse> {
+        let file = std::fs::File::open(path).map_err(read_error)?;
+        let size = file.metadata().map_err(read_error)?.len();
+        self.send_reader(file, Some(size))
+    }
+
+    fn start(&mut self, size: Option<u64>) -> crate::Result<UploadProgress> {
+        let progress = UploadProgress {
+            sent: 0,
+            size,
+            max_size: self.client.max_size_upload(),
+            exceeded: Arc::new(AtomicBool::new(false)),
+            callback: self.progress.take(),
+        };
+        match size {
+            Some(size) if progress.max_size > 0 && size > progress.max_size => {
+                Err(progress.size_error())
+            }
+            _ => Ok(progress),
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn send(
+        self,
+        body: StreamBody,
+        size: Option<u64>,
+        exceeded: Arc<AtomicBool>,
+    ) -> crate::Result<UploadResponse> {
+        let mut request = HttpRequest::post(self.client.blob_upload_url(&self.account_id))
+            .with_headers(self.client.headers().clone())
+            .with_header(
+                CONTENT_TYPE,
+                upload_content_type(self.content_type.as_deref())?,
+            );
+        if let Some(size) = size {
+            request = request.with_header(CONTENT_LENGTH, HeaderValue::from(size));
+        }
+
+        let limiter = self.client.upload_limiter();
+        let _permit = limiter.acquire().await;
+        let result = self.client.send_http_upload(request, body).await;
+        if exceeded.load(Ordering::Relaxed) {
+            return Err(crate::Error::Internal(format!(
+                "Upload exceeds the server limit of {} bytes.",
+                self.client.max_size_upload()
+            )));
+        }
+        serde_json::from_slice::<UploadResponse>(Client::handle_error(result?)?.body())
+            .map_err(|err| err.into())
+    }
+}
+
+impl UploadProgress {
+    /// Accounts for a chunk about to be sent, returning `false` when it exceeds the upload limit.
+    fn advance(&mut self, len: usize) -> bool {
+        self.sent += len as u64;
+        if self.max_size > 0 && self.sent > self.max_size {
+            self.exceeded.store(true, Ordering::Relaxed);
+            false
+        } else {
+            if let Some(callback) = &mut self.callback {
+                callback(self.sent, self.size);
+            }
+            true
+        }
+    }
+
+    fn size_error(&self) -> crate::Error {
+        size_error(self.size.unwrap_or(self.sent), self.max_size)
+    }
+}
+
+fn size_error(size: u64, max_size: u64) -> crate::Error {
+    crate::Error::Internal(format!(
+        "Upload of {} bytes exceeds the server limit of {} bytes.",
+        size, max_size
+    ))
+}
+
+#[cfg(feature = "blocking")]
+struct UploadReader<R> {
+    reader: R,
+    progress: UploadProgress,
+}
+
+#[cfg(feature = "blocking")]
+impl<R: std::io::Read> std::io::Read for UploadReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        if self.progress.advance(len) {
+            Ok(len)
+        } else {
+            Err(std::io::Error::other(
+                self.progress.size_error().to_string(),
+            ))
+        }
+    }
+}
+
+fn upload_content_type(content_type: Option<&str>) -> crate::Result<HeaderValue> {
+    HeaderValue::from_str(content_type.unwrap_or("application/octet-stream"))
+        .map_err(|_| crate::Error::Internal("Invalid content type.".to_string()))
+}
+
+fn read_error(err: std::io::Error) -> crate::Error {
+    crate::Error::Internal(format!("Failed to read upload: {}", err))
+}
+
 impl UploadResponse {
     pub fn account_id(&self) -> &str {
         &self.account_id
@@ -105,3 +372,84 @@ impl UploadResponse {
         std::mem::take(&mut self.blob_id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::testing::MockServer;
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn upload_stream() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let progress = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let blob_id = client
+            .upload_request()
+            .content_type("text/plain")
+            .progress({
+                let progress = progress.clone();
+                move |sent, size| progress.lock().push((sent, size))
+            })
+            .send_stream(
+                futures_util::stream::iter(
+                    ["hello", " ", "world"]
+                        .map(|chunk| Ok(bytes::Bytes::from_static(chunk.as_bytes()))),
+                ),
+                None,
+            )
+            .await
+            .unwrap()
+            .take_blob_id();
+        assert_eq!(client.download(&blob_id).await.unwrap(), b"hello world");
+        assert_eq!(*progress.lock(), [(5, None), (6, None), (11, None)]);
+
+        let blob_id = client
+            .upload_request()
+            .send_reader(std::io::Cursor::new(b"from a reader".to_vec()), Some(13))
+            .await
+            .unwrap()
+            .take_blob_id();
+        assert_eq!(client.download(&blob_id).await.unwrap(), b"from a reader");
+
+        // Rejected before sending when the size is known to exceed the limit
+        let err = client
+            .upload_request()
+            .send_stream(futures_util::stream::empty(), Some(50_000_001))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds the server limit"));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn upload_file() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let path = std::env::temp_dir().join(format!("jmap-client-upload-{}", std::process::id()));
+        std::fs::write(&path, b"file contents").unwrap();
+        let progress = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let result = client
+            .upload_request()
+            .progress({
+                let progress = progress.clone();
+                move |sent, size| progress.lock().push((sent, size))
+            })
+            .send_file(&path)
+            .await;
+        std::fs::remove_file(&path).unwrap();
+        let blob_id = result.unwrap().take_blob_id();
+        let contents = client.download(&blob_id).await.unwrap();
+        assert_eq!(contents, b"file contents");
+        assert_eq!(progress.lock().last(), Some(&(13, Some(13))));
+
+        // Uploads from memory are also checked against the limit
+        let result = client.upload(None, vec![0; 50_000_001], None).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("exceeds the server limit"));
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{set::SetError, RequestParams},
+    Error,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadBlobRequest {
+    #[serde(rename = "accountId")]
+    account_id: String,
+
+    create: AHashMap<String, UploadObject>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadObject {
+    #[serde(skip)]
+    create_id: usize,
+
+    data: Vec<DataSourceObject>,
+
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    type_: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum DataSourceObject {
+    Text {
+        #[serde(rename = "data:asText")]
+        data: String,
+    },
+    Base64 {
+        #[serde(rename = "data:asBase64")]
+        data: String,
+    },
+    Blob {
+        #[serde(rename = "blobId")]
+        blob_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        offset: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        length: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadBlobResponse {
+    #[serde(rename = "accountId")]
+    account_id: String,
+
+    #[serde(rename = "created")]
+    created: Option<AHashMap<String, UploadedBlob>>,
+
+    #[serde(rename = "notCreated")]
+    not_created: Option<AHashMap<String, SetError<String>>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadedBlob {
+    #[serde(rename = "id")]
+    id: String,
+
+    #[serde(rename = "type")]
+    type_: Option<String>,
+
+    #[serde(rename = "size")]
+    size: usize,
+}
+
+impl UploadBlobRequest {
+    pub fn new(params: RequestParams) -> Self {
+        UploadBlobRequest {
+            account_id: params.account_id,
+            create: AHashMap::new(),
+        }
+    }
+
+    pub fn account_id(&mut self, account_id: impl Into<String>) -> &mut Self {
+        self.account_id = account_id.into();
+        self
+    }
+
+    pub fn create(&mut self) -> &mut UploadObject {
+        let create_id = self.create.len();
+        let create_id_str = format!("b{}", create_id);
+        self.create
+            .insert(create_id_str.clone(), UploadObject::new(create_id));
+        self.create.get_mut(&create_id_str).unwrap()
+    }
+}
+
+impl UploadObject {
+    fn new(create_id: usize) -> Self {
+        UploadObject {
+            create_id,
+            data: vec![],
+            type_: None,
+        }
+    }
+
+    pub fn text(&mut self, text: impl Into<String>) -> &mut Self {
+        self.data.push(DataSourceObject::Text { data: text.into() });
+        self
+    }
+
+    pub fn bytes(&mut self, bytes: impl AsRef<[u8]>) -> &mut Self {
+        self.data.push(DataSourceObject::Base64 {
+            data: general_purpose::STANDARD.encode(bytes),
+        });
+        self
+    }
+
+    /// Appends the contents of an existing blob, or the `length` bytes starting at `offset`.
+    pub fn blob(
+        &mut self,
+        blob_id: impl Into<String>,
+        offset: Option<usize>,
+        length: Option<usize>,
+    ) -> &mut Self {
+        self.data.push(DataSourceObject::Blob {
+            blob_id: blob_id.into(),
+            offset,
+            length,
+        });
+        self
+    }
+
+    pub fn content_type(&mut self, content_type: impl Into<String>) -> &mut Self {
+        self.type_ = Some(content_type.into());
+        self
+    }
+
+    pub fn create_id(&self) -> String {
+        format!("b{}", self.create_id)
+    }
+}
+
+impl UploadBlobResponse {
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn created(&mut self, id: &str) -> crate::Result<UploadedBlob> {
+        if let Some(result) = self.created.as_mut().and_then(|r| r.remove(id)) {
+            Ok(result)
+        } else if let Some(error) = self.not_created.as_mut().and_then(|r| r.remove(id)) {
+            Err(error.to_string_error().into())
+        } else {
+            Err(Error::Internal(format!("Id {} not found.", id)))
+        }
+    }
+
+    pub fn created_ids(&self) -> Option<impl Iterator<Item = &String>> {
+        self.created.as_ref().map(|map| map.keys())
+    }
+
+    pub fn not_created_ids(&self) -> Option<impl Iterator<Item = &String>> {
+        self.not_created.as_ref().map(|map| map.keys())
+    }
+}
+
+impl UploadedBlob {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn take_id(&mut self) -> String {
+        std::mem::take(&mut self.id)
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.type_.as_deref()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
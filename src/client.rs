@@ -15,19 +15,22 @@ use crate::{
         request::{self, Request},
         response,
         session::{Session, URLPart},
+        split::SplitRequest,
     },
+    credentials::CredentialProvider,
+    discovery::{self, DnsResolver},
+    limiter::Limiter,
+    oauth::{OAuthCredentials, OAuthProvider, TokenCallback},
+    retry::{self, RetryPolicy},
+    transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport},
     Error,
 };
 use ahash::AHashSet;
 use base64::{engine::general_purpose, Engine};
-#[cfg(feature = "blocking")]
-use reqwest::blocking::{Client as HttpClient, Response};
 use reqwest::{
     header::{self},
-    redirect,
+    StatusCode,
 };
-#[cfg(feature = "async")]
-use reqwest::{Client as HttpClient, Response};
 use serde::de::DeserializeOwned;
 use std::{
     net::IpAddr,
@@ -39,43 +42,87 @@ use std::{
 };
 
 const DEFAULT_TIMEOUT_MS: u64 = 10 * 1000;
+#[cfg(feature = "async")]
+const DEFAULT_PING_TIMEOUT: u32 = 3;
 static USER_AGENT: &str = concat!("jmap-client/", env!("CARGO_PKG_VERSION"));
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Credentials {
     Basic(String),
     Bearer(String),
+    OAuth(OAuthCredentials),
 }
 
+pub type SessionCallback = Arc<dyn Fn(&Session, &Session) + Send + Sync>;
+
 pub struct Client {
-    session: parking_lot::Mutex<Arc<Session>>,
+    session: parking_lot::Mutex<SessionState>,
     session_url: String,
-    api_url: String,
     session_updated: AtomicBool,
+    auto_refresh_session: bool,
+    on_session_change: Option<SessionCallback>,
     trusted_hosts: Arc<AHashSet<String>>,
+    trusted_domain: Option<String>,
 
-    upload_url: Vec<URLPart<blob::URLParameter>>,
-    download_url: Vec<URLPart<blob::URLParameter>>,
-    #[cfg(feature = "async")]
-    event_source_url: Vec<URLPart<crate::event_source::URLParameter>>,
+    credentials: Arc<dyn CredentialProvider>,
 
     headers: header::HeaderMap,
-    default_account_id: String,
     timeout: Duration,
     pub(crate) accept_invalid_certs: bool,
+    transport: Arc<dyn Transport>,
+    custom_transport: bool,
+    oauth: Option<Arc<OAuthProvider>>,
+    retry_policy: RetryPolicy,
+    #[cfg(feature = "async")]
+    ping_timeout: u32,
 
-    #[cfg(feature = "websockets")]
-    pub(crate) authorization: String,
     #[cfg(feature = "websockets")]
     pub(crate) ws: tokio::sync::Mutex<Option<crate::client_ws::WsStream>>,
+    #[cfg(feature = "websockets")]
+    pub(crate) ws_state: tokio::sync::watch::Sender<crate::client_ws::WsConnectionState>,
+    #[cfg(feature = "websockets")]
+    pub(crate) ws_keepalive: Option<Duration>,
+    #[cfg(feature = "websockets")]
+    pub(crate) prefer_websocket: bool,
+    #[cfg(feature = "websockets")]
+    pub(crate) ws_latency: Arc<parking_lot::Mutex<Option<Duration>>>,
+}
+
+struct SessionState {
+    session: Arc<Session>,
+    upload_url: Arc<[URLPart<blob::URLParameter>]>,
+    download_url: Arc<[URLPart<blob::URLParameter>]>,
+    #[cfg(feature = "async")]
+    event_source_url: Arc<[URLPart<crate::event_source::URLParameter>]>,
+    default_account_id: String,
+    is_default_account_custom: bool,
+    request_limiter: Arc<Limiter>,
+    upload_limiter: Arc<Limiter>,
 }
 
+#[derive(Clone)]
 pub struct ClientBuilder {
     credentials: Option<Credentials>,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
     trusted_hosts: AHashSet<String>,
+    trusted_domain: Option<String>,
     forwarded_for: Option<String>,
     accept_invalid_certs: bool,
     timeout: Duration,
+    transport: Option<Arc<dyn Transport>>,
+    retry_policy: RetryPolicy,
+    auto_refresh_session: bool,
+    on_session_change: Option<SessionCallback>,
+    on_token_refresh: Option<TokenCallback>,
+    dns_resolver: Option<Arc<dyn DnsResolver>>,
+    #[cfg(feature = "async")]
+    ping_timeout: u32,
+    #[cfg(feature = "websockets")]
+    ws_keepalive: Option<Duration>,
+    #[cfg(feature = "websockets")]
+    prefer_websocket: bool,
+    #[cfg(any(test, feature = "testing"))]
+    fixture: Option<crate::testing::fixture::FixtureMode>,
 }
 
 impl Default for ClientBuilder {
@@ -91,10 +138,26 @@ impl ClientBuilder {
     pub fn new() -> Self {
         Self {
             credentials: None,
+            credential_provider: None,
             trusted_hosts: AHashSet::new(),
+            trusted_domain: None,
             timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
             forwarded_for: None,
             accept_invalid_certs: false,
+            transport: None,
+            retry_policy: RetryPolicy::none(),
+            auto_refresh_session: false,
+            on_session_change: None,
+            on_token_refresh: None,
+            dns_resolver: None,
+            #[cfg(feature = "async")]
+            ping_timeout: DEFAULT_PING_TIMEOUT,
+            #[cfg(feature = "websockets")]
+            ws_keepalive: None,
+            #[cfg(feature = "websockets")]
+            prefer_websocket: false,
+            #[cfg(any(test, feature = "testing"))]
+            fixture: None,
         }
     }
 
@@ -129,6 +192,16 @@ impl ClientBuilder {
     /// let credentials = Credentials::basic("user@domain.com", "password");
     /// Client::new().credentials(credentials);
     /// ```
+    ///
+    /// # OAuth 2.0
+    /// Pass an [OAuthCredentials](../oauth/struct.OAuthCredentials.html) with a refresh token to have the
+    /// access token renewed automatically when the server responds with `401 Unauthorized`.
+    ///
+    /// ```rust
+    /// let credentials = OAuthCredentials::new("access-token", "https://auth.example.org/token", "client-id")
+    ///     .with_refresh_token("refresh-token");
+    /// Client::new().credentials(credentials);
+    /// ```
     pub fn credentials(mut self, credentials: impl Into<Credentials>) -> Self {
         self.credentials = Some(credentials.into());
         self
@@ -144,6 +217,48 @@ impl ClientBuilder {
         self
     }
 
+    /// Set how many ping intervals can pass without receiving a ping or any other event
+    /// before an [EventSource](struct.Client.html#method.event_source) connection is considered dead,
+    /// or without receiving a pong to a [WebSocket keepalive](struct.ClientBuilder.html#method.ws_keepalive) ping.
+    ///
+    /// For EventSource connections it only applies when a ping interval is requested, the interval
+    /// reported by the server in its ping events takes precedence over the requested one.
+    /// A value of `0` disables the timeout.
+    ///
+    /// By default the timeout is 3 ping intervals.
+    #[cfg(feature = "async")]
+    pub fn ping_timeout(mut self, ping_timeout: u32) -> Self {
+        self.ping_timeout = ping_timeout;
+        self
+    }
+
+    /// Send a ping over [WebSocket](struct.Client.html#method.connect_ws) connections every `interval`,
+    /// measuring the [latency](struct.Client.html#method.ws_latency) from the pongs received.
+    ///
+    /// Connections that do not answer within the [ping timeout](struct.ClientBuilder.html#method.ping_timeout)
+    /// are dropped and re-established if the [retry policy](struct.ClientBuilder.html#method.retry_policy) allows.
+    ///
+    /// By default no keepalive pings are sent.
+    #[cfg(feature = "websockets")]
+    pub fn ws_keepalive(mut self, interval: Duration) -> Self {
+        self.ws_keepalive = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Send all API requests over the [WebSocket](struct.Client.html#method.connect_ws) connection
+    /// when the server supports JMAP over WebSocket, connecting on the first request.
+    ///
+    /// Requests are sent over HTTP when the server does not advertise WebSocket support,
+    /// the connection cannot be established or it is being re-established, and when
+    /// a request has to be split to stay within the server limits.
+    ///
+    /// By default requests are sent over HTTP.
+    #[cfg(feature = "websockets")]
+    pub fn prefer_websocket(mut self, prefer_websocket: bool) -> Self {
+        self.prefer_websocket = prefer_websocket;
+        self
+    }
+
     /// Accepts invalid certificates for all the requests to the JMAP API.
     ///
     /// By default certificates are validated.
@@ -179,70 +294,226 @@ impl ClientBuilder {
         self
     }
 
-    /// Connects to the JMAP API Session URL.
+    /// Use a custom HTTP [Transport](../transport/trait.Transport.html) for all the requests to the JMAP API.
+    ///
+    /// By default requests are sent using [ReqwestTransport](../transport/struct.ReqwestTransport.html).
+    /// A custom transport is responsible for its own certificate validation and redirect handling.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Set the policy used to retry requests that failed due to transient errors.
+    ///
+    /// Connection errors, timeouts, `429`, `502`, `503` and `504` responses as well as
+    /// `serverUnavailable` method errors are retried, honoring the `Retry-After` header.
+    /// Only idempotent requests are retried unless [RetryPolicy.retry_set()](../retry/struct.RetryPolicy.html#method.retry_set) is enabled.
+    /// The policy is also used to re-establish lost [WebSocket](struct.Client.html#method.connect_ws) connections.
+    ///
+    /// The policy can be changed after the `Client` has been created by using [Client.set_retry_policy()](struct.Client.html#method.set_retry_policy).
+    ///
+    /// By default requests are not retried.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Refresh the session automatically when a response reports a new `sessionState`.
+    ///
+    /// The session is fetched again before returning the response that reported the change,
+    /// updating the API, upload, download and event source URLs as well as the default
+    /// account (unless it was set with [Client.set_default_account_id()](struct.Client.html#method.set_default_account_id)).
+    ///
+    /// By default the session is not refreshed and [Client.is_session_updated()](struct.Client.html#method.is_session_updated)
+    /// has to be checked by the caller.
+    pub fn auto_refresh_session(mut self, auto_refresh_session: bool) -> Self {
+        self.auto_refresh_session = auto_refresh_session;
+        self
+    }
+
+    /// Register a callback that receives the old and new `Session` whenever a refreshed
+    /// session has a different state.
+    pub fn on_session_change(
+        mut self,
+        callback: impl Fn(&Session, &Session) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_session_change = Some(Arc::new(callback));
+        self
+    }
+
+    /// Use a custom [CredentialProvider](../credentials/trait.CredentialProvider.html) to obtain the
+    /// `Authorization` header for each request, replacing any [Credentials](struct.ClientBuilder.html#method.credentials).
+    ///
+    /// The provider is asked to refresh its credentials when the server responds with `401 Unauthorized`.
+    pub fn credential_provider(mut self, provider: impl CredentialProvider + 'static) -> Self {
+        self.credential_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Register a callback that receives the new OAuth credentials after the access token
+    /// has been refreshed, for example to persist the rotated refresh token.
+    pub fn on_token_refresh(
+        mut self,
+        callback: impl Fn(&OAuthCredentials) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_token_refresh = Some(Arc::new(callback));
+        self
+    }
+
+    /// Use a custom [DnsResolver](../discovery/trait.DnsResolver.html) to look up the JMAP service
+    /// in [ClientBuilder.connect_by_email()](struct.ClientBuilder.html#method.connect_by_email).
+    ///
+    /// By default the system resolver is used when the `dns` feature is enabled, otherwise
+    /// no SRV lookup is performed.
+    pub fn dns_resolver(mut self, resolver: impl DnsResolver + 'static) -> Self {
+        self.dns_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Record every request and response exchanged with the JMAP server to a fixture file,
+    /// which can be served back with [ClientBuilder.replay()](struct.ClientBuilder.html#method.replay).
+    ///
+    /// Requests are sent using the configured [Transport](struct.ClientBuilder.html#method.transport),
+    /// which is not rebuilt when the timeout or trusted hosts are changed after connecting.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn record(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.fixture = Some(crate::testing::fixture::FixtureMode::Record(path.into()));
+        self
+    }
+
+    /// Serve all requests from a fixture file written by [ClientBuilder.record()](struct.ClientBuilder.html#method.record)
+    /// instead of sending them to the JMAP server.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn replay(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.fixture = Some(crate::testing::fixture::FixtureMode::Replay(path.into()));
+        self
+    }
+
+    /// Discovers the JMAP API Session URL of an email address' domain and connects to it.
     ///
-    /// Setting up [Credentials](struct.ClientBuilder.html#method.credentials) must be done before calling this function.
+    /// The `_jmap._tcp` SRV records of the domain are tried first, followed by
+    /// `https://<domain>/.well-known/jmap` (RFC 8620, Section 2.2). The discovered hosts and
+    /// the subdomains of the email domain are trusted for redirects, other hosts the session
+    /// URL redirects to have to be added with [ClientBuilder.follow_redirects()](struct.ClientBuilder.html#method.follow_redirects).
     #[maybe_async::maybe_async]
-    pub async fn connect(self, url: &str) -> crate::Result<Client> {
-        let authorization = match self.credentials.expect("Missing credentials") {
-            Credentials::Basic(s) => format!("Basic {}", s),
-            Credentials::Bearer(s) => format!("Bearer {}", s),
+    pub async fn connect_by_email(mut self, email: &str) -> crate::Result<Client> {
+        let domain = discovery::email_domain(email)?;
+        let records = match self
+            .dns_resolver
+            .clone()
+            .or_else(discovery::default_resolver)
+        {
+            Some(resolver) => resolver
+                .srv_lookup(&format!("_jmap._tcp.{}.", domain))
+                .await
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        let urls = discovery::service_urls(domain, records)?;
+        for url in &urls {
+            let host = url.trim_start_matches("https://");
+            let host = host.rsplit_once(':').map_or(host, |(host, _)| host);
+            self.trusted_hosts.insert(host.to_string());
+        }
+        self.trusted_domain = Some(domain.to_string());
+
+        // Report the error of the preferred service URL if none of them can be reached
+        let mut first_err = None;
+        for url in urls {
+            match self.clone().connect(&url).await {
+                Ok(client) => return Ok(client),
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        Err(first_err.unwrap_or_else(|| {
+            crate::Error::Internal(format!("No JMAP service found for domain '{}'.", domain))
+        }))
+    }
+
+    fn build_transport(
+        &mut self,
+        trusted_hosts: &Arc<AHashSet<String>>,
+    ) -> crate::Result<(Arc<dyn Transport>, bool)> {
+        let custom_transport = self.transport.is_some();
+        let transport = match self.transport.take() {
+            Some(transport) => transport,
+            None => Arc::new(ReqwestTransport::with_trusted_domain(
+                trusted_hosts.clone(),
+                self.trusted_domain.clone(),
+                self.accept_invalid_certs,
+                self.timeout,
+            )?),
         };
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(fixture) = self.fixture.take() {
+            return Ok((fixture.into_transport(transport)?, true));
+        }
+        Ok((transport, custom_transport))
+    }
+
+    /// Connects to the JMAP API Session URL.
+    ///
+    /// Setting up [Credentials](struct.ClientBuilder.html#method.credentials) or a [CredentialProvider](struct.ClientBuilder.html#method.credential_provider)
+    /// must be done before calling this function.
+    #[maybe_async::maybe_async]
+    pub async fn connect(mut self, url: &str) -> crate::Result<Client> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             header::USER_AGENT,
             header::HeaderValue::from_static(USER_AGENT),
         );
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&authorization).unwrap(),
-        );
-        if let Some(forwarded_for) = self.forwarded_for {
+        if let Some(forwarded_for) = &self.forwarded_for {
             headers.insert(
                 header::FORWARDED,
-                header::HeaderValue::from_str(&forwarded_for).unwrap(),
+                header::HeaderValue::from_str(forwarded_for).unwrap(),
             );
         }
 
-        let trusted_hosts = Arc::new(self.trusted_hosts);
+        let trusted_hosts = Arc::new(std::mem::take(&mut self.trusted_hosts));
+        let (transport, custom_transport) = self.build_transport(&trusted_hosts)?;
+
+        let mut oauth = None;
+        let credentials: Arc<dyn CredentialProvider> =
+            match (self.credential_provider, self.credentials) {
+                (Some(provider), _) => provider,
+                (None, Some(Credentials::OAuth(credentials))) => oauth
+                    .insert(Arc::new(OAuthProvider::new(
+                        credentials,
+                        transport.clone(),
+                        self.timeout,
+                        self.on_token_refresh,
+                    )))
+                    .clone(),
+                (None, credentials) => Arc::new(credentials.expect("Missing credentials")),
+            };
 
-        let trusted_hosts_ = trusted_hosts.clone();
         let session_url = format!("{}/.well-known/jmap", url);
-        let session: Session = serde_json::from_slice(
-            &Client::handle_error(
-                HttpClient::builder()
-                    .timeout(self.timeout)
-                    .danger_accept_invalid_certs(self.accept_invalid_certs)
-                    .redirect(redirect::Policy::custom(move |attempt| {
-                        if attempt.previous().len() > 5 {
-                            attempt.error("Too many redirects.")
-                        } else if matches!( attempt.url().host_str(), Some(host) if trusted_hosts_.contains(host) )
-                        {
-                                attempt.follow()
-                        } else {
-                            let message = format!(
-                                "Aborting redirect request to unknown host '{}'.",
-                                attempt.url().host_str().unwrap_or("")
-                            );
-                            attempt.error(message)
-                        }
-                    }))
-                    .default_headers(headers.clone())
-                    .build()?
-                    .get(&session_url)
-                    .send()
-                    .await?,
+        let session_request = HttpRequest::get(&session_url)
+            .with_headers(headers.clone())
+            .with_timeout(self.timeout);
+        let authorization = credentials.authorization().await?;
+        let mut response = transport
+            .send(
+                session_request
+                    .clone()
+                    .with_header(header::AUTHORIZATION, authorization.clone()),
             )
-            .await?
-            .bytes()
-            .await?,
-        )?;
-
-        let default_account_id = session
-            .primary_accounts()
-            .next()
-            .map(|a| a.1.to_string())
-            .unwrap_or_default();
+            .await?;
+        if response.status() == StatusCode::UNAUTHORIZED
+            && credentials.can_refresh()
+            && credentials.refresh(&authorization).await?
+        {
+            response = transport
+                .send(
+                    session_request
+                        .with_header(header::AUTHORIZATION, credentials.authorization().await?),
+                )
+                .await?;
+        }
+        let session: Session = serde_json::from_slice(Client::handle_error(response)?.body())?;
 
         headers.insert(
             header::CONTENT_TYPE,
@@ -250,23 +521,35 @@ impl ClientBuilder {
         );
 
         Ok(Client {
-            download_url: URLPart::parse(session.download_url())?,
-            upload_url: URLPart::parse(session.upload_url())?,
-            #[cfg(feature = "async")]
-            event_source_url: URLPart::parse(session.event_source_url())?,
-            api_url: session.api_url().to_string(),
-            session: parking_lot::Mutex::new(Arc::new(session)),
+            session: parking_lot::Mutex::new(SessionState::new(session, None)?),
             session_url,
             session_updated: true.into(),
+            auto_refresh_session: self.auto_refresh_session,
+            on_session_change: self.on_session_change,
             accept_invalid_certs: self.accept_invalid_certs,
             trusted_hosts,
-            #[cfg(feature = "websockets")]
-            authorization,
+            trusted_domain: self.trusted_domain,
+            credentials,
             timeout: self.timeout,
             headers,
-            default_account_id,
+            transport,
+            custom_transport,
+            oauth,
+            retry_policy: self.retry_policy,
+            #[cfg(feature = "async")]
+            ping_timeout: self.ping_timeout,
             #[cfg(feature = "websockets")]
             ws: None.into(),
+            #[cfg(feature = "websockets")]
+            ws_state: tokio::sync::watch::Sender::new(
+                crate::client_ws::WsConnectionState::Disconnected,
+            ),
+            #[cfg(feature = "websockets")]
+            ws_keepalive: self.ws_keepalive,
+            #[cfg(feature = "websockets")]
+            prefer_websocket: self.prefer_websocket,
+            #[cfg(feature = "websockets")]
+            ws_latency: Default::default(),
         })
     }
 }
@@ -277,16 +560,58 @@ impl Client {
         ClientBuilder::new()
     }
 
-    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
-        self.timeout = timeout;
-        self
+    /// Sets the timeout of the requests sent by the client.
+    ///
+    /// The default transport is rebuilt to apply the new connection timeout, custom transports
+    /// set with [ClientBuilder.transport()](struct.ClientBuilder.html#method.transport) and
+    /// fixture transports are kept, and only receive the new timeout with each request.
+    /// The current transport is kept if the new one cannot be built.
+    pub fn set_timeout(&mut self, timeout: Duration) -> crate::Result<&mut Self> {
+        if self.timeout != timeout {
+            self.rebuild_transport(self.trusted_hosts.clone(), timeout)?;
+        }
+        Ok(self)
     }
 
+    /// Sets the list of trusted hosts that will be checked when a redirect is required.
+    ///
+    /// Redirects are only followed by the default transport, this setting has no effect on
+    /// custom transports set with [ClientBuilder.transport()](struct.ClientBuilder.html#method.transport)
+    /// or on fixture transports. The current transport is kept if the new one cannot be built.
     pub fn set_follow_redirects(
         &mut self,
         trusted_hosts: impl IntoIterator<Item = impl Into<String>>,
-    ) -> &mut Self {
-        self.trusted_hosts = Arc::new(trusted_hosts.into_iter().map(|h| h.into()).collect());
+    ) -> crate::Result<&mut Self> {
+        self.rebuild_transport(
+            Arc::new(trusted_hosts.into_iter().map(|h| h.into()).collect()),
+            self.timeout,
+        )?;
+        Ok(self)
+    }
+
+    fn rebuild_transport(
+        &mut self,
+        trusted_hosts: Arc<AHashSet<String>>,
+        timeout: Duration,
+    ) -> crate::Result<()> {
+        if !self.custom_transport {
+            self.transport = Arc::new(ReqwestTransport::with_trusted_domain(
+                trusted_hosts.clone(),
+                self.trusted_domain.clone(),
+                self.accept_invalid_certs,
+                timeout,
+            )?);
+        }
+        self.trusted_hosts = trusted_hosts;
+        self.timeout = timeout;
+        if let Some(oauth) = &self.oauth {
+            oauth.set_transport(self.transport.clone(), timeout);
+        }
+        Ok(())
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
         self
     }
 
@@ -294,34 +619,40 @@ impl Client {
         self.timeout
     }
 
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    #[cfg(feature = "async")]
+    pub fn ping_timeout(&self) -> u32 {
+        self.ping_timeout
+    }
+
     pub fn session(&self) -> Arc<Session> {
-        self.session.lock().clone()
+        self.session.lock().session.clone()
     }
 
     pub fn session_url(&self) -> &str {
         &self.session_url
     }
 
+    /// Returns the headers sent with every request, the `Authorization` header is
+    /// obtained from the [CredentialProvider](../credentials/trait.CredentialProvider.html) for each request.
     pub fn headers(&self) -> &header::HeaderMap {
         &self.headers
     }
 
-    pub(crate) fn redirect_policy(&self) -> redirect::Policy {
-        let trusted_hosts = self.trusted_hosts.clone();
-        redirect::Policy::custom(move |attempt| {
-            if attempt.previous().len() > 5 {
-                attempt.error("Too many redirects.")
-            } else if matches!( attempt.url().host_str(), Some(host) if trusted_hosts.contains(host) )
-            {
-                attempt.follow()
-            } else {
-                let message = format!(
-                    "Aborting redirect request to unknown host '{}'.",
-                    attempt.url().host_str().unwrap_or("")
-                );
-                attempt.error(message)
-            }
-        })
+    /// Returns the provider of the `Authorization` header, either the one set with
+    /// [ClientBuilder.credential_provider()](struct.ClientBuilder.html#method.credential_provider)
+    /// or the one built from the [Credentials](struct.ClientBuilder.html#method.credentials).
+    pub fn credential_provider(&self) -> &Arc<dyn CredentialProvider> {
+        &self.credentials
+    }
+
+    /// Returns the [Transport](../transport/trait.Transport.html) used to send all HTTP requests,
+    /// which can be used to send requests that are not covered by the client.
+    pub fn transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
     }
 
     #[maybe_async::maybe_async]
@@ -332,51 +663,209 @@ impl Client {
     where
         R: DeserializeOwned,
     {
-        let response: response::Response<R> = serde_json::from_slice(
-            &Client::handle_error(
-                HttpClient::builder()
-                    .redirect(self.redirect_policy())
-                    .danger_accept_invalid_certs(self.accept_invalid_certs)
-                    .timeout(self.timeout)
-                    .default_headers(self.headers.clone())
-                    .build()?
-                    .post(&self.api_url)
-                    .body(serde_json::to_string(&request)?)
-                    .send()
-                    .await?,
+        let session = self.session();
+        let is_idempotent = request.is_idempotent();
+        let body = serde_json::to_vec(request)?;
+        let split = match session.core_capabilities() {
+            Some(capabilities) if SplitRequest::may_exceed(request, body.len(), capabilities) => {
+                SplitRequest::new(&mut serde_json::from_slice(&body)?, capabilities)?
+            }
+            _ => None,
+        };
+
+        let response: response::Response<R> = if let Some(mut split) = split {
+            while let Some(body) = split.next_request() {
+                let response = match self.send_api_ws(&body, is_idempotent).await {
+                    Some(response) => response?,
+                    None => serde_json::from_slice(
+                        self.send_api(session.api_url(), serde_json::to_vec(&body)?, is_idempotent)
+                            .await?
+                            .body(),
+                    )?,
+                };
+                split.add_response(response)?;
+            }
+            serde_json::from_value(split.into_response())?
+        } else if let Some(response) = self.send_api_ws(request, is_idempotent).await {
+            response?
+        } else {
+            serde_json::from_slice(
+                self.send_api(session.api_url(), body, is_idempotent)
+                    .await?
+                    .body(),
+            )?
+        };
+
+        self.update_session_state(session.state(), response.session_state())
+            .await;
+
+        Ok(response)
+    }
+
+    /// Requests are always sent over HTTP without the `websockets` feature.
+    #[cfg(not(feature = "websockets"))]
+    #[maybe_async::maybe_async]
+    async fn send_api_ws<R, T>(
+        &self,
+        _request: &R,
+        _is_idempotent: bool,
+    ) -> Option<crate::Result<T>>
+    where
+        R: serde::Serialize,
+        T: DeserializeOwned,
+    {
+        None
+    }
+
+    /// Marks the session as outdated when a response reports a different session state
+    /// than the one the request was sent with, refreshing it if enabled.
+    #[maybe_async::maybe_async]
+    pub(crate) async fn update_session_state(&self, request_state: &str, session_state: &str) {
+        if session_state != request_state {
+            self.session_updated.store(false, Ordering::Relaxed);
+            if self.auto_refresh_session && session_state != self.session().state() {
+                // A failed refresh leaves the session marked as outdated
+                // and is attempted again on the next request.
+                let _ = self.refresh_session().await;
+            }
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn send_api(
+        &self,
+        api_url: &str,
+        body: Vec<u8>,
+        is_idempotent: bool,
+    ) -> crate::Result<HttpResponse> {
+        let limiter = self.request_limiter();
+        let _permit = limiter.acquire().await;
+        Client::handle_error(
+            self.send_http(
+                HttpRequest::post(api_url)
+                    .with_headers(self.headers.clone())
+                    .with_body(body)
+                    .with_timeout(self.timeout),
+                is_idempotent,
+                true,
             )
-            .await?
-            .bytes()
             .await?,
-        )?;
+        )
+    }
 
-        if response.session_state() != self.session.lock().state() {
-            self.session_updated.store(false, Ordering::Relaxed);
+    /// Sends an HTTP request using the client's transport, retrying transient
+    /// failures according to the configured [RetryPolicy](../retry/struct.RetryPolicy.html).
+    #[maybe_async::maybe_async]
+    pub(crate) async fn send_http(
+        &self,
+        request: HttpRequest,
+        is_idempotent: bool,
+        check_method_errors: bool,
+    ) -> crate::Result<HttpResponse> {
+        let can_retry = self.retry_policy.can_retry(is_idempotent);
+        let mut can_refresh = self.credentials.can_refresh();
+        let mut request = request;
+        let mut attempt = 1;
+        loop {
+            let authorization = self.credentials.authorization().await?;
+            request
+                .headers
+                .insert(header::AUTHORIZATION, authorization.clone());
+            if !can_retry && !can_refresh {
+                return self.transport.send(request).await;
+            }
+
+            let result = self.transport.send(request.clone()).await;
+            if can_refresh
+                && matches!(&result, Ok(response) if response.status() == StatusCode::UNAUTHORIZED)
+            {
+                can_refresh = false;
+                if self.credentials.refresh(&authorization).await? {
+                    continue;
+                }
+            }
+            if !can_retry {
+                return result;
+            }
+            match self
+                .retry_policy
+                .retry_delay(&result, attempt, check_method_errors)
+            {
+                Some(delay) => {
+                    retry::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return result,
+            }
         }
+    }
 
-        Ok(response)
+    /// Sends a streaming HTTP request, refreshing the credentials once if they were rejected.
+    #[maybe_async::maybe_async]
+    pub(crate) async fn send_http_stream(
+        &self,
+        mut request: HttpRequest,
+    ) -> crate::Result<HttpResponse<crate::transport::StreamBody>> {
+        let mut can_refresh = self.credentials.can_refresh();
+        loop {
+            let authorization = self.credentials.authorization().await?;
+            request
+                .headers
+                .insert(header::AUTHORIZATION, authorization.clone());
+            let response = self.transport.send_stream(request.clone()).await?;
+            if can_refresh && response.status() == StatusCode::UNAUTHORIZED {
+                can_refresh = false;
+                if self.credentials.refresh(&authorization).await? {
+                    continue;
+                }
+            }
+            return Ok(response);
+        }
+    }
+
+    /// Sends an HTTP request with a streaming body. As the body can't be replayed,
+    /// the request is neither retried nor resent after refreshing the credentials.
+    #[maybe_async::maybe_async]
+    pub(crate) async fn send_http_upload(
+        &self,
+        mut request: HttpRequest,
+        body: crate::transport::StreamBody,
+    ) -> crate::Result<HttpResponse> {
+        request.headers.insert(
+            header::AUTHORIZATION,
+            self.credentials.authorization().await?,
+        );
+        self.transport.send_upload(request, body).await
     }
 
     #[maybe_async::maybe_async]
     pub async fn refresh_session(&self) -> crate::Result<()> {
         let session: Session = serde_json::from_slice(
-            &Client::handle_error(
-                HttpClient::builder()
-                    .timeout(Duration::from_millis(DEFAULT_TIMEOUT_MS))
-                    .danger_accept_invalid_certs(self.accept_invalid_certs)
-                    .redirect(self.redirect_policy())
-                    .default_headers(self.headers.clone())
-                    .build()?
-                    .get(&self.session_url)
-                    .send()
-                    .await?,
-            )
-            .await?
-            .bytes()
-            .await?,
+            Client::handle_error(
+                self.send_http(
+                    HttpRequest::get(&self.session_url)
+                        .with_headers(self.headers.clone())
+                        .with_timeout(Duration::from_millis(DEFAULT_TIMEOUT_MS)),
+                    true,
+                    false,
+                )
+                .await?,
+            )?
+            .body(),
         )?;
-        *self.session.lock() = Arc::new(session);
+        let old_session = {
+            let mut state = self.session.lock();
+            let new_state = SessionState::new(session, Some(&state))?;
+            std::mem::replace(&mut *state, new_state).session
+        };
         self.session_updated.store(true, Ordering::Relaxed);
+
+        if let Some(on_session_change) = &self.on_session_change {
+            let new_session = self.session();
+            if old_session.state() != new_session.state() {
+                on_session_change(&old_session, &new_session);
+            }
+        }
         Ok(())
     }
 
@@ -385,50 +874,117 @@ impl Client {
     }
 
     pub fn set_default_account_id(&mut self, defaul_account_id: impl Into<String>) -> &mut Self {
-        self.default_account_id = defaul_account_id.into();
+        let state = self.session.get_mut();
+        state.default_account_id = defaul_account_id.into();
+        state.is_default_account_custom = true;
         self
     }
 
-    pub fn default_account_id(&self) -> &str {
-        &self.default_account_id
+    pub fn default_account_id(&self) -> String {
+        self.session.lock().default_account_id.clone()
     }
 
     pub fn build(&self) -> Request<'_> {
         Request::new(self)
     }
 
-    pub fn download_url(&self) -> &[URLPart<blob::URLParameter>] {
-        &self.download_url
+    pub fn download_url(&self) -> Arc<[URLPart<blob::URLParameter>]> {
+        self.session.lock().download_url.clone()
     }
 
-    pub fn upload_url(&self) -> &[URLPart<blob::URLParameter>] {
-        &self.upload_url
+    pub fn upload_url(&self) -> Arc<[URLPart<blob::URLParameter>]> {
+        self.session.lock().upload_url.clone()
     }
 
     #[cfg(feature = "async")]
-    pub fn event_source_url(&self) -> &[URLPart<crate::event_source::URLParameter>] {
-        &self.event_source_url
+    pub fn event_source_url(&self) -> Arc<[URLPart<crate::event_source::URLParameter>]> {
+        self.session.lock().event_source_url.clone()
     }
 
-    #[maybe_async::maybe_async]
-    pub async fn handle_error(response: Response) -> crate::Result<Response> {
+    pub(crate) fn request_limiter(&self) -> Arc<Limiter> {
+        self.session.lock().request_limiter.clone()
+    }
+
+    pub(crate) fn upload_limiter(&self) -> Arc<Limiter> {
+        self.session.lock().upload_limiter.clone()
+    }
+
+    /// Returns the response if it was successful, or the error reported by the server otherwise.
+    pub fn handle_error(response: HttpResponse) -> crate::Result<HttpResponse> {
         if response.status().is_success() {
             Ok(response)
-        } else if let Some(b"application/problem+json") = response
-            .headers()
-            .get(header::CONTENT_TYPE)
-            .map(|h| h.as_bytes())
-        {
-            Err(Error::Problem(serde_json::from_slice(
-                &response.bytes().await?,
-            )?))
         } else {
-            Err(Error::Server(format!("{}", response.status())))
+            Err(Client::response_error(&response))
+        }
+    }
+
+    pub(crate) fn response_error(response: &HttpResponse) -> Error {
+        if let Some("application/problem+json") = response.content_type() {
+            match serde_json::from_slice(response.body()) {
+                Ok(problem) => Error::Problem(problem),
+                Err(err) => err.into(),
+            }
+        } else {
+            Error::Server(format!("{}", response.status()))
         }
     }
 }
 
+impl SessionState {
+    fn new(session: Session, previous: Option<&SessionState>) -> crate::Result<Self> {
+        let (max_concurrent_requests, max_concurrent_upload) = session
+            .core_capabilities()
+            .map(|capabilities| {
+                (
+                    capabilities.max_concurrent_requests(),
+                    capabilities.max_concurrent_upload(),
+                )
+            })
+            .unwrap_or_default();
+        let limiter = |previous: Option<&Arc<Limiter>>, max_concurrent: usize| match previous {
+            Some(limiter) if limiter.max_concurrent() == max_concurrent => limiter.clone(),
+            _ => Arc::new(Limiter::new(max_concurrent)),
+        };
+
+        let (default_account_id, is_default_account_custom) = match previous {
+            Some(previous) if previous.is_default_account_custom => {
+                (previous.default_account_id.clone(), true)
+            }
+            _ => (
+                session
+                    .primary_accounts()
+                    .next()
+                    .map(|a| a.1.to_string())
+                    .unwrap_or_default(),
+                false,
+            ),
+        };
+
+        Ok(SessionState {
+            download_url: URLPart::parse(session.download_url())?.into(),
+            upload_url: URLPart::parse(session.upload_url())?.into(),
+            #[cfg(feature = "async")]
+            event_source_url: URLPart::parse(session.event_source_url())?.into(),
+            session: Arc::new(session),
+            default_account_id,
+            is_default_account_custom,
+            request_limiter: limiter(
+                previous.map(|previous| &previous.request_limiter),
+                max_concurrent_requests,
+            ),
+            upload_limiter: limiter(
+                previous.map(|previous| &previous.upload_limiter),
+                max_concurrent_upload,
+            ),
+        })
+    }
+}
+
 impl Credentials {
+    pub fn oauth(credentials: OAuthCredentials) -> Self {
+        Credentials::OAuth(credentials)
+    }
+
     pub fn basic(username: &str, password: &str) -> Self {
         Credentials::Basic(general_purpose::STANDARD.encode(format!("{}:{}", username, password)))
     }
@@ -450,6 +1006,12 @@ impl From<String> for Credentials {
     }
 }
 
+impl From<OAuthCredentials> for Credentials {
+    fn from(credentials: OAuthCredentials) -> Self {
+        Credentials::OAuth(credentials)
+    }
+}
+
 impl From<(&str, &str)> for Credentials {
     fn from((username, password): (&str, &str)) -> Self {
         Credentials::basic(username, password)
@@ -464,7 +1026,463 @@ impl From<(String, String)> for Credentials {
 
 #[cfg(test)]
 mod tests {
-    use crate::core::response::{Response, TaggedMethodResponse};
+    use crate::{
+        core::response::{Response, TaggedMethodResponse},
+        credentials::CredentialProvider,
+        discovery::{DnsResolver, SrvRecord},
+        oauth::OAuthCredentials,
+        retry::RetryPolicy,
+        transport::{HttpRequest, HttpResponse, Transport},
+    };
+    use ahash::AHashSet;
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, AUTHORIZATION},
+        StatusCode,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    pub(crate) const SESSION: &str = r#"{
+        "capabilities": {
+            "urn:ietf:params:jmap:core": {
+                "maxSizeUpload": 50000000,
+                "maxConcurrentUpload": 4,
+                "maxSizeRequest": 10000000,
+                "maxConcurrentRequests": 4,
+                "maxCallsInRequest": 16,
+                "maxObjectsInGet": 500,
+                "maxObjectsInSet": 500,
+                "collationAlgorithms": []
+            },
+            "urn:ietf:params:jmap:mail": {}
+        },
+        "accounts": {
+            "A1": {
+                "name": "john@example.org",
+                "isPersonal": true,
+                "isReadOnly": false,
+                "accountCapabilities": {}
+            }
+        },
+        "primaryAccounts": { "urn:ietf:params:jmap:mail": "A1" },
+        "username": "john@example.org",
+        "apiUrl": "https://jmap.example.org/api/",
+        "downloadUrl": "https://jmap.example.org/download/{accountId}/{blobId}/{name}?accept={type}",
+        "uploadUrl": "https://jmap.example.org/upload/{accountId}/",
+        "eventSourceUrl": "https://jmap.example.org/eventsource/?types={types}&closeafter={closeafter}&ping={ping}",
+        "state": "s1"
+    }"#;
+
+    #[derive(Default)]
+    struct StubTransport {
+        unavailable: AtomicUsize,
+        api_calls: AtomicUsize,
+        session_v2: AtomicBool,
+        access_token: parking_lot::Mutex<Option<&'static str>>,
+    }
+
+    impl StubTransport {
+        fn session(&self) -> (String, &'static str, &'static str) {
+            if self.session_v2.load(Ordering::Relaxed) {
+                (
+                    SESSION
+                        .replace("A1", "A2")
+                        .replace("/upload/", "/upload/v2/")
+                        .replace("\"s1\"", "\"s2\""),
+                    "A2",
+                    "s2",
+                )
+            } else {
+                (SESSION.to_string(), "A1", "s1")
+            }
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    impl Transport for StubTransport {
+        async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+            let (session, account_id, session_state) = self.session();
+            let access_token = *self.access_token.lock();
+            if let Some(access_token) = access_token.filter(|_| !request.url.contains("/token")) {
+                if request
+                    .headers
+                    .get(AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    != Some(&format!("Bearer {access_token}"))
+                {
+                    return Ok(HttpResponse::new(
+                        StatusCode::UNAUTHORIZED,
+                        HeaderMap::new(),
+                        vec![],
+                    ));
+                }
+            }
+
+            let body = match request.url.as_str() {
+                "https://auth.example.org/token" => {
+                    let form = form_urlencoded::parse(request.body.as_deref().unwrap())
+                        .into_owned()
+                        .collect::<Vec<_>>();
+                    assert!(form.contains(&("grant_type".into(), "refresh_token".into())));
+                    assert!(form.contains(&("client_id".into(), "client".into())));
+                    let access_token = access_token.unwrap();
+                    format!(
+                        r#"{{"access_token": "{access_token}", "token_type": "Bearer",
+                        "refresh_token": "{access_token}-refresh", "expires_in": 3600}}"#
+                    )
+                    .into_bytes()
+                }
+                "https://jmap.example.org/.well-known/jmap" => session.into_bytes(),
+                "https://down.example.com/.well-known/jmap" => {
+                    return Ok(HttpResponse::new(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        HeaderMap::new(),
+                        vec![],
+                    ))
+                }
+                "https://jmap.example.org/api/" => {
+                    self.api_calls.fetch_add(1, Ordering::Relaxed);
+                    if self
+                        .unavailable
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                        .is_ok()
+                    {
+                        return Ok(HttpResponse::new(
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            HeaderMap::new(),
+                            vec![],
+                        ));
+                    }
+                    let request: serde_json::Value =
+                        serde_json::from_slice(request.body.as_deref().unwrap()).unwrap();
+                    assert_eq!(request["methodCalls"][0][0], "Mailbox/get");
+                    assert_eq!(request["methodCalls"][0][1]["accountId"], account_id);
+                    format!(
+                        r#"{{"sessionState": "{session_state}", "methodResponses": [["Mailbox/get", {{
+                        "accountId": "{account_id}", "state": "m1", "list": [{{"id": "inbox", "name": "Inbox"}}],
+                        "notFound": []}}, "s0"]]}}"#
+                    )
+                    .into_bytes()
+                }
+                _ => {
+                    return Ok(HttpResponse::new(
+                        StatusCode::NOT_FOUND,
+                        HeaderMap::new(),
+                        vec![],
+                    ))
+                }
+            };
+            Ok(HttpResponse::new(StatusCode::OK, HeaderMap::new(), body))
+        }
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn custom_transport() {
+        let client = super::Client::new()
+            .credentials("token")
+            .transport(StubTransport::default())
+            .connect("https://jmap.example.org")
+            .await
+            .unwrap();
+        assert_eq!(client.default_account_id(), "A1");
+
+        let mailbox = client
+            .mailbox_get("inbox", None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(mailbox.name(), Some("Inbox"));
+        assert!(client.is_session_updated());
+        let download = client.download("blob").await;
+        assert!(download.is_err());
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn retry_unavailable() {
+        let transport = Arc::new(StubTransport::default());
+        let client = super::Client::new()
+            .credentials("token")
+            .transport(transport.clone())
+            .retry_policy(
+                RetryPolicy::new()
+                    .max_attempts(3)
+                    .backoff(Duration::from_millis(1), Duration::from_millis(10)),
+            )
+            .connect("https://jmap.example.org")
+            .await
+            .unwrap();
+
+        transport.unavailable.store(2, Ordering::Relaxed);
+        let mailbox = client.mailbox_get("inbox", None::<Vec<_>>).await.unwrap();
+        assert!(mailbox.is_some());
+        assert_eq!(transport.api_calls.swap(0, Ordering::Relaxed), 3);
+
+        transport.unavailable.store(3, Ordering::Relaxed);
+        let mailbox = client.mailbox_get("inbox", None::<Vec<_>>).await;
+        assert!(matches!(mailbox, Err(crate::Error::Server(_))));
+        assert_eq!(transport.api_calls.swap(0, Ordering::Relaxed), 3);
+
+        transport.unavailable.store(1, Ordering::Relaxed);
+        let mailbox = client.mailbox_rename("inbox", "Inbox").await;
+        assert!(matches!(mailbox, Err(crate::Error::Server(_))));
+        assert_eq!(transport.api_calls.swap(0, Ordering::Relaxed), 1);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn session_refresh() {
+        let transport = Arc::new(StubTransport::default());
+        let changes = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let changes_ = changes.clone();
+        let client = super::Client::new()
+            .credentials("token")
+            .transport(transport.clone())
+            .connect("https://jmap.example.org")
+            .await
+            .unwrap();
+
+        // Without auto refresh the session is only flagged as outdated
+        transport.session_v2.store(true, Ordering::Relaxed);
+        let mut request = client.build().account_id("A2");
+        request.get_mailbox().ids(["inbox"]);
+        request.send().await.unwrap();
+        assert!(!client.is_session_updated());
+        assert_eq!(client.default_account_id(), "A1");
+
+        transport.session_v2.store(false, Ordering::Relaxed);
+        let client = super::Client::new()
+            .credentials("token")
+            .transport(transport.clone())
+            .auto_refresh_session(true)
+            .on_session_change(move |old, new| {
+                changes_
+                    .lock()
+                    .push((old.state().to_string(), new.state().to_string()));
+            })
+            .connect("https://jmap.example.org")
+            .await
+            .unwrap();
+        assert_eq!(client.upload_url().len(), 3);
+
+        transport.session_v2.store(true, Ordering::Relaxed);
+        let mut request = client.build().account_id("A2");
+        request.get_mailbox().ids(["inbox"]);
+        request.send().await.unwrap();
+        assert!(client.is_session_updated());
+        assert_eq!(client.default_account_id(), "A2");
+        assert_eq!(client.session().state(), "s2");
+        assert!(matches!(
+            &client.upload_url()[0],
+            crate::core::session::URLPart::Value(url) if url.ends_with("/upload/v2/")
+        ));
+        assert_eq!(*changes.lock(), [("s1".to_string(), "s2".to_string())]);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn oauth_refresh() {
+        let transport = Arc::new(StubTransport::default());
+        let refreshed = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let refreshed_ = refreshed.clone();
+        *transport.access_token.lock() = Some("token-1");
+
+        let client = super::Client::new()
+            .credentials(
+                OAuthCredentials::new("expired", "https://auth.example.org/token", "client")
+                    .with_refresh_token("refresh"),
+            )
+            .on_token_refresh(move |credentials| {
+                refreshed_.lock().push(credentials.clone());
+            })
+            .transport(transport.clone())
+            .connect("https://jmap.example.org")
+            .await
+            .unwrap();
+        assert_eq!(refreshed.lock().len(), 1);
+
+        *transport.access_token.lock() = Some("token-2");
+        let mailbox = client.mailbox_get("inbox", None::<Vec<_>>).await.unwrap();
+        assert!(mailbox.is_some());
+        assert_eq!(
+            refreshed.lock().last(),
+            Some(
+                &OAuthCredentials::new("token-2", "https://auth.example.org/token", "client")
+                    .with_refresh_token("token-2-refresh")
+            )
+        );
+        assert_eq!(refreshed.lock().len(), 2);
+
+        // Tokens are not printed
+        let debug = format!("{:?}", refreshed.lock().last().unwrap());
+        assert!(!debug.contains("token-2"), "{debug}");
+
+        // Credentials without a refresh token can't be renewed
+        let result = super::Client::new()
+            .credentials(OAuthCredentials::new(
+                "expired",
+                "https://auth.example.org/token",
+                "client",
+            ))
+            .transport(transport.clone())
+            .connect("https://jmap.example.org")
+            .await;
+        assert!(matches!(result, Err(crate::Error::Server(_))));
+    }
+
+    struct RotatingProvider {
+        version: AtomicUsize,
+    }
+
+    #[maybe_async::maybe_async]
+    impl CredentialProvider for RotatingProvider {
+        async fn authorization(&self) -> crate::Result<HeaderValue> {
+            Ok(HeaderValue::from_str(&format!(
+                "Bearer token-{}",
+                self.version.load(Ordering::Relaxed)
+            ))
+            .unwrap())
+        }
+
+        async fn refresh(&self, _rejected: &HeaderValue) -> crate::Result<bool> {
+            self.version.fetch_add(1, Ordering::Relaxed);
+            Ok(true)
+        }
+
+        fn can_refresh(&self) -> bool {
+            true
+        }
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn credential_provider() {
+        let transport = Arc::new(StubTransport::default());
+        *transport.access_token.lock() = Some("token-1");
+
+        let client = super::Client::new()
+            .credential_provider(RotatingProvider {
+                version: AtomicUsize::new(0),
+            })
+            .transport(transport.clone())
+            .connect("https://jmap.example.org")
+            .await
+            .unwrap();
+
+        *transport.access_token.lock() = Some("token-2");
+        let mailbox = client.mailbox_get("inbox", None::<Vec<_>>).await.unwrap();
+        assert!(mailbox.is_some());
+
+        // Rejected credentials are only refreshed once per request
+        *transport.access_token.lock() = Some("token-4");
+        let mailbox = client.mailbox_get("inbox", None::<Vec<_>>).await;
+        assert!(matches!(mailbox, Err(crate::Error::Server(_))));
+        let mailbox = client.mailbox_get("inbox", None::<Vec<_>>).await.unwrap();
+        assert!(mailbox.is_some());
+    }
+
+    struct StubResolver;
+
+    #[maybe_async::maybe_async]
+    impl DnsResolver for StubResolver {
+        async fn srv_lookup(&self, name: &str) -> crate::Result<Vec<SrvRecord>> {
+            Ok(match name {
+                "_jmap._tcp.example.org." => vec![SrvRecord {
+                    priority: 0,
+                    weight: 1,
+                    port: 443,
+                    target: "jmap.example.org.".to_string(),
+                }],
+                "_jmap._tcp.example.com." => vec![SrvRecord {
+                    priority: 0,
+                    weight: 1,
+                    port: 443,
+                    target: "down.example.com.".to_string(),
+                }],
+                "_jmap._tcp.example.net." => vec![SrvRecord {
+                    priority: 0,
+                    weight: 0,
+                    port: 443,
+                    target: ".".to_string(),
+                }],
+                _ => vec![],
+            })
+        }
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn connect_by_email() {
+        let client = super::Client::new()
+            .credentials("token")
+            .transport(StubTransport::default())
+            .dns_resolver(StubResolver)
+            .connect_by_email("john@example.org")
+            .await
+            .unwrap();
+        assert_eq!(
+            client.session_url(),
+            "https://jmap.example.org/.well-known/jmap"
+        );
+        for (host, is_trusted) in [
+            ("jmap.example.org", true),
+            ("api.jmap.example.org", true),
+            ("example.org", true),
+            ("example.com", false),
+            ("badexample.org", false),
+        ] {
+            assert_eq!(
+                crate::transport::is_trusted_host(
+                    &client.trusted_hosts,
+                    client.trusted_domain.as_deref(),
+                    host
+                ),
+                is_trusted,
+                "{host}"
+            );
+        }
+
+        // Hosts set with follow_redirects are matched exactly
+        let trusted_hosts = AHashSet::from_iter(["*.example.org".to_string()]);
+        assert!(!crate::transport::is_trusted_host(
+            &trusted_hosts,
+            None,
+            "jmap.example.org"
+        ));
+
+        // Falls back to the domain when there are no SRV records
+        let client = super::Client::new()
+            .credentials("token")
+            .transport(StubTransport::default())
+            .dns_resolver(StubResolver)
+            .connect_by_email("john@jmap.example.org")
+            .await
+            .unwrap();
+        assert_eq!(
+            client.session_url(),
+            "https://jmap.example.org/.well-known/jmap"
+        );
+
+        let result = super::Client::new()
+            .credentials("token")
+            .transport(StubTransport::default())
+            .dns_resolver(StubResolver)
+            .connect_by_email("john@example.com")
+            .await;
+        assert!(
+            matches!(&result, Err(crate::Error::Server(status)) if status.starts_with("503")),
+            "The error of the preferred service URL is returned"
+        );
+
+        // A single "." target means that the domain has no JMAP service
+        let result = super::Client::new()
+            .credentials("token")
+            .transport(StubTransport::default())
+            .dns_resolver(StubResolver)
+            .connect_by_email("john@example.net")
+            .await;
+        assert!(matches!(result, Err(crate::Error::Internal(_))));
+    }
 
     #[test]
     fn test_deserialize() {
@@ -9,17 +9,34 @@
  * except according to those terms.
  */
 
-use std::{pin::Pin, sync::Arc};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use ahash::AHashMap;
-use futures_util::{stream::SplitSink, SinkExt, Stream, StreamExt};
+use bytes::Bytes;
+use futures_util::{
+    future::Either,
+    stream::{SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt,
+};
 use reqwest::header::SEC_WEBSOCKET_PROTOCOL;
 use rustls::{
     client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
     ClientConfig, SignatureScheme,
 };
-use serde::{Deserialize, Serialize};
-use tokio::net::TcpStream;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{
+    net::TcpStream,
+    sync::{mpsc, oneshot, watch},
+    time::Instant,
+};
 use tokio_tungstenite::{
     tungstenite::{client::IntoClientRequest, Message},
     Connector, MaybeTlsStream, WebSocketStream,
@@ -29,28 +46,33 @@ use crate::{
     client::Client,
     core::{
         error::{ProblemDetails, ProblemType},
-        request::{Arguments, Request},
+        request::Request,
         response::{Response, TaggedMethodResponse},
     },
-    DataType, Method, PushObject, URI,
+    credentials::CredentialProvider,
+    retry::RetryPolicy,
+    DataType, PushObject,
 };
 
 #[derive(Debug, Serialize)]
-struct WebSocketRequest {
+struct WebSocketRequest<'x, T: Serialize> {
     #[serde(rename = "@type")]
     pub _type: WebSocketRequestType,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
 
-    using: Vec<URI>,
+    #[serde(flatten)]
+    request: &'x T,
+}
 
-    #[serde(rename = "methodCalls")]
-    method_calls: Vec<(Method, Arguments, String)>,
+#[derive(Debug, Deserialize)]
+struct WebSocketEnvelope {
+    #[serde(rename = "@type")]
+    _type: Option<String>,
 
-    #[serde(rename = "createdIds")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    created_ids: Option<AHashMap<String, String>>,
+    #[serde(rename = "requestId")]
+    request_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,7 +98,7 @@ enum WebSocketResponseType {
     Response,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 struct WebSocketPushEnable {
     #[serde(rename = "@type")]
     _type: WebSocketPushEnableType,
@@ -100,7 +122,7 @@ enum WebSocketRequestType {
     Request,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 enum WebSocketPushEnableType {
     WebSocketPushEnable,
 }
@@ -151,13 +173,60 @@ enum WebSocketMessage_ {
 
 #[derive(Debug)]
 pub enum WebSocketMessage {
+    /// Response that does not belong to any request sent with [Client.send_ws()](crate::client::Client::send_ws).
     Response(Response<TaggedMethodResponse>),
     PushNotification(PushObject),
 }
 
+/// State of the WebSocket connection, see [Client.ws_connection_state()](crate::client::Client::ws_connection_state).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsConnectionState {
+    /// No connection has been opened yet or it could not be re-established.
+    Disconnected,
+    Connected,
+    /// The connection was lost and is being re-established.
+    Reconnecting {
+        attempt: u32,
+    },
+}
+
+type WsConnection = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
 pub struct WsStream {
-    tx: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
-    req_id: usize,
+    shared: Arc<WsShared>,
+    _close_tx: oneshot::Sender<()>,
+}
+
+/// State shared between the client and the task serving the connection.
+struct WsShared {
+    tx: tokio::sync::Mutex<Option<SplitSink<WsConnection, Message>>>,
+    req_id: AtomicUsize,
+    pending: parking_lot::Mutex<AHashMap<String, PendingRequest>>,
+    push: parking_lot::Mutex<Option<WebSocketPushEnable>>,
+    /// Internal listeners of push notifications, such as [Client.watch()](crate::client::Client::watch).
+    push_subscribers: parking_lot::Mutex<Vec<mpsc::UnboundedSender<crate::Result<PushObject>>>>,
+    ping_id: AtomicU64,
+    /// Id and send time of the oldest ping without a pong.
+    ping_sent: parking_lot::Mutex<Option<(u64, Instant)>>,
+    latency: Arc<parking_lot::Mutex<Option<Duration>>>,
+}
+
+struct PendingRequest {
+    response_tx: oneshot::Sender<crate::Result<Bytes>>,
+    /// Request to send again after reconnecting, if it can be safely replayed.
+    resend: Option<String>,
+}
+
+struct WsPendingResponse {
+    shared: Arc<WsShared>,
+    request_id: String,
+    response_rx: oneshot::Receiver<crate::Result<Bytes>>,
+}
+
+struct WsConnector {
+    url: String,
+    credentials: Arc<dyn CredentialProvider>,
+    accept_invalid_certs: bool,
 }
 
 #[doc(hidden)]
@@ -214,8 +283,27 @@ impl ServerCertVerifier for DummyVerifier {
 }
 
 impl Client {
+    /// Connects to the WebSocket endpoint of the JMAP server, replacing any previous connection.
+    ///
+    /// Responses to requests sent with [Client.send_ws()](crate::client::Client::send_ws) are
+    /// delivered to the pending requests, the returned stream receives push notifications and
+    /// errors not related to a request. The connection is served in the background whether or
+    /// not the stream is polled.
+    ///
+    /// When the [retry policy](crate::client::ClientBuilder::retry_policy) is enabled, a lost
+    /// connection is re-established with backoff, resuming push notifications from the last
+    /// received `pushState`. Requests waiting for a response are sent again if the policy
+    /// allows retrying them, otherwise they fail.
     pub async fn connect_ws(
         &self,
+    ) -> crate::Result<Pin<Box<impl Stream<Item = crate::Result<WebSocketMessage>>>>> {
+        let mut ws = self.ws.lock().await;
+        self.open_ws(&mut ws).await
+    }
+
+    async fn open_ws(
+        &self,
+        ws: &mut Option<WsStream>,
     ) -> crate::Result<Pin<Box<impl Stream<Item = crate::Result<WebSocketMessage>>>>> {
         let session = self.session();
         let capabilities = session.websocket_capabilities().ok_or_else(|| {
@@ -224,15 +312,261 @@ impl Client {
             )
         })?;
 
-        let mut request = capabilities.url().into_client_request()?;
+        let connector = WsConnector {
+            url: capabilities.url().to_string(),
+            credentials: self.credential_provider().clone(),
+            accept_invalid_certs: self.accept_invalid_certs,
+        };
+        let (tx, rx) = connector.connect().await?;
+        let shared = Arc::new(WsShared {
+            tx: Some(tx).into(),
+            req_id: 0.into(),
+            pending: Default::default(),
+            push: None.into(),
+            push_subscribers: Default::default(),
+            ping_id: 0.into(),
+            ping_sent: None.into(),
+            latency: self.ws_latency.clone(),
+        });
+        let (push_tx, mut push_rx) = mpsc::unbounded_channel();
+        let (close_tx, close_rx) = oneshot::channel();
+
+        // Dropping the previous connection closes it, anything still waiting on it fails
+        if let Some(previous) = ws.take() {
+            previous
+                .shared
+                .fail_pending("WebSocket connection lost: replaced by a new connection.");
+        }
+        *ws = WsStream {
+            shared: shared.clone(),
+            _close_tx: close_tx,
+        }
+        .into();
+        self.ws_state.send_replace(WsConnectionState::Connected);
+        tokio::spawn(serve_ws(
+            connector,
+            shared,
+            rx,
+            WsServeContext {
+                push_tx,
+                state_tx: self.ws_state.clone(),
+                retry_policy: self.retry_policy().clone(),
+                keepalive: self.ws_keepalive,
+                ping_timeout: self.ping_timeout(),
+                close_rx,
+            },
+        ));
+
+        Ok(Box::pin(async_stream::stream! {
+            while let Some(message) = push_rx.recv().await {
+                yield message;
+            }
+        }))
+    }
+
+    /// Returns a stream with the current state of the WebSocket connection followed by
+    /// each of its changes.
+    pub fn ws_connection_state(&self) -> impl Stream<Item = WsConnectionState> + Send + 'static {
+        let mut state_rx = self.ws_state.subscribe();
+        Box::pin(async_stream::stream! {
+            loop {
+                let state = *state_rx.borrow_and_update();
+                yield state;
+                if state_rx.changed().await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Sends a request over the WebSocket connection and waits for its response.
+    pub async fn send_ws(
+        &self,
+        request: Request<'_>,
+    ) -> crate::Result<Response<TaggedMethodResponse>> {
+        let session_state = self.session().state().to_string();
+        let resend = self.retry_policy().can_retry(request.is_idempotent());
+        let response = self
+            .ws_shared()
+            .await?
+            .send_request(&serde_json::to_value(&request)?, resend)
+            .await?;
+        let response: Response<TaggedMethodResponse> = self.ws_response(response).await?;
+        self.update_session_state(&session_state, response.session_state())
+            .await;
+
+        Ok(response)
+    }
+
+    /// Sends an API request over the WebSocket connection if it is preferred over HTTP,
+    /// connecting first if needed. Returns `None` if the request could not be sent.
+    ///
+    /// Requests count towards `maxConcurrentRequests` like the ones sent over HTTP.
+    pub(crate) async fn send_api_ws<R: Serialize, T: DeserializeOwned>(
+        &self,
+        request: &R,
+        is_idempotent: bool,
+    ) -> Option<crate::Result<T>> {
+        if !self.prefer_websocket {
+            return None;
+        }
+
+        let shared = {
+            let mut ws = self.ws.lock().await;
+            if *self.ws_state.borrow() == WsConnectionState::Disconnected
+                && (self.session().websocket_capabilities().is_none()
+                    || self.open_ws(&mut ws).await.is_err())
+            {
+                return None;
+            }
+            ws.as_ref()?.shared.clone()
+        };
+        let limiter = self.request_limiter();
+        let _permit = limiter.acquire().await;
+        let response = shared
+            .send_request(request, self.retry_policy().can_retry(is_idempotent))
+            .await
+            .ok()?;
+        Some(self.ws_response(response).await)
+    }
+
+    async fn ws_response<T: DeserializeOwned>(
+        &self,
+        response: WsPendingResponse,
+    ) -> crate::Result<T> {
+        match tokio::time::timeout(self.timeout(), response.response_rx).await {
+            Ok(Ok(result)) => Ok(serde_json::from_slice(&result?)?),
+            Ok(Err(_)) => Err(crate::Error::Internal(
+                "Websocket connection lost before receiving a response.".to_string(),
+            )),
+            Err(_) => {
+                response.shared.pending.lock().remove(&response.request_id);
+                Err(crate::Error::Internal(
+                    "Timed out waiting for a Websocket response.".to_string(),
+                ))
+            }
+        }
+    }
+
+    pub async fn enable_push_ws(
+        &self,
+        data_types: Option<impl IntoIterator<Item = DataType>>,
+        push_state: Option<impl Into<String>>,
+    ) -> crate::Result<()> {
+        let shared = self.ws_shared().await?;
+        let push_enable = WebSocketPushEnable {
+            _type: WebSocketPushEnableType::WebSocketPushEnable,
+            data_types: data_types.map(|it| it.into_iter().collect()),
+            push_state: push_state.map(|it| it.into()),
+        };
+        let message = Message::text(serde_json::to_string(&push_enable).unwrap_or_default());
+        *shared.push.lock() = push_enable.into();
+        shared.send(message).await
+    }
+
+    pub async fn disable_push_ws(&self) -> crate::Result<()> {
+        let shared = self.ws_shared().await?;
+        *shared.push.lock() = None;
+        shared
+            .send(Message::text(
+                serde_json::to_string(&WebSocketPushDisable {
+                    _type: WebSocketPushDisableType::WebSocketPushDisable,
+                })
+                .unwrap_or_default(),
+            ))
+            .await
+    }
+
+    pub async fn ws_ping(&self) -> crate::Result<()> {
+        self.ws_shared().await?.ping().await
+    }
+
+    /// Returns the round-trip time of the last WebSocket ping answered by the server.
+    pub fn ws_latency(&self) -> Option<Duration> {
+        *self.ws_latency.lock()
+    }
+
+    /// Subscribes to the push notifications of the WebSocket connection, making sure
+    /// they are enabled for the given data types. An existing connection is reused
+    /// along with its push subscription, a new one is only opened if there is none.
+    pub(crate) async fn ws_subscribe_push(
+        &self,
+        data_types: &[DataType],
+    ) -> crate::Result<mpsc::UnboundedReceiver<crate::Result<PushObject>>> {
+        let shared = {
+            let mut ws = self.ws.lock().await;
+            if ws.is_none() {
+                // Only the subscribers receive push notifications of this connection
+                let _stream = self.open_ws(&mut ws).await?;
+            } else if *self.ws_state.borrow() == WsConnectionState::Disconnected {
+                return Err(crate::Error::Internal(
+                    "WebSocket connection lost, reconnect with connect_ws().".to_string(),
+                ));
+            }
+            ws.as_ref().unwrap().shared.clone()
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        shared.push_subscribers.lock().push(tx);
+
+        // Extend the data types of an existing push subscription rather than replacing it
+        let push_enable = {
+            let mut push = shared.push.lock();
+            match push.as_mut() {
+                Some(push_enable) => match &mut push_enable.data_types {
+                    Some(enabled) if data_types.iter().any(|t| !enabled.contains(t)) => {
+                        for data_type in data_types {
+                            if !enabled.contains(data_type) {
+                                enabled.push(data_type.clone());
+                            }
+                        }
+                        Some(push_enable.clone())
+                    }
+                    _ => None,
+                },
+                None => {
+                    let push_enable = WebSocketPushEnable {
+                        _type: WebSocketPushEnableType::WebSocketPushEnable,
+                        data_types: data_types.to_vec().into(),
+                        push_state: None,
+                    };
+                    *push = push_enable.clone().into();
+                    Some(push_enable)
+                }
+            }
+        };
+        if let Some(push_enable) = push_enable {
+            shared
+                .send(Message::text(
+                    serde_json::to_string(&push_enable).unwrap_or_default(),
+                ))
+                .await?;
+        }
+        Ok(rx)
+    }
+
+    async fn ws_shared(&self) -> crate::Result<Arc<WsShared>> {
+        self.ws
+            .lock()
+            .await
+            .as_ref()
+            .map(|ws| ws.shared.clone())
+            .ok_or_else(|| crate::Error::Internal("Websocket stream not set.".to_string()))
+    }
+}
+
+impl WsConnector {
+    async fn connect(
+        &self,
+    ) -> crate::Result<(SplitSink<WsConnection, Message>, SplitStream<WsConnection>)> {
+        let mut request = self.url.as_str().into_client_request()?;
         request
             .headers_mut()
-            .insert("Authorization", self.authorization.parse().unwrap());
+            .insert("Authorization", self.credentials.authorization().await?);
         request
             .headers_mut()
             .insert(SEC_WEBSOCKET_PROTOCOL, "jmap".parse().unwrap());
 
-        let (stream, _) = if self.accept_invalid_certs & capabilities.url().starts_with("wss") {
+        let (stream, _) = if self.accept_invalid_certs & self.url.starts_with("wss") {
             tokio_tungstenite::connect_async_tls_with_config(
                 request,
                 None,
@@ -249,115 +583,299 @@ impl Client {
         } else {
             tokio_tungstenite::connect_async(request).await?
         };
-        let (tx, mut rx) = stream.split();
-
-        *self.ws.lock().await = WsStream { tx, req_id: 0 }.into();
-
-        Ok(Box::pin(async_stream::stream! {
-            while let Some(message) = rx.next().await {
-                match message {
-                    Ok(message) if message.is_text() => {
-                        match serde_json::from_slice::<WebSocketMessage_>(&message.into_data()) {
-                            Ok(message) => match message {
-                                WebSocketMessage_::Response(response) => {
-                                    yield Ok(WebSocketMessage::Response(Response::new(
-                                        response.method_responses,
-                                        response.created_ids,
-                                        response.session_state,
-                                        response.request_id,
-                                    )))
-                                }
-                                WebSocketMessage_::PushNotification(push) => {
-                                    yield Ok(WebSocketMessage::PushNotification(push.push))
-                                }
-                                WebSocketMessage_::Error(err) => yield Err(ProblemDetails::from(err).into()),
-                            },
-                            Err(err) => yield Err(err.into()),
-                        }
-                    }
-                    Ok(_) => (),
-                    Err(err) => yield Err(err.into()),
-                }
-            }
-        }))
+        Ok(stream.split())
     }
+}
 
-    pub async fn send_ws(&self, request: Request<'_>) -> crate::Result<String> {
-        let mut _ws = self.ws.lock().await;
-        let ws = _ws
-            .as_mut()
-            .ok_or_else(|| crate::Error::Internal("Websocket stream not set.".to_string()))?;
-
+impl WsShared {
+    async fn send_request<T: Serialize>(
+        self: &Arc<Self>,
+        request: &T,
+        resend: bool,
+    ) -> crate::Result<WsPendingResponse> {
         // Assign request id
-        let request_id = ws.req_id.to_string();
-        ws.req_id += 1;
+        let request_id = self.req_id.fetch_add(1, Ordering::Relaxed).to_string();
+        let message = serde_json::to_string(&WebSocketRequest {
+            _type: WebSocketRequestType::Request,
+            id: request_id.clone().into(),
+            request,
+        })?;
 
-        ws.tx
-            .send(Message::text(
-                serde_json::to_string(&WebSocketRequest {
-                    _type: WebSocketRequestType::Request,
-                    id: request_id.clone().into(),
-                    using: request.using,
-                    method_calls: request.method_calls,
-                    created_ids: request.created_ids,
-                })
-                .unwrap_or_default(),
-            ))
-            .await?;
+        let (response_tx, response_rx) = oneshot::channel();
+        let mut tx = self.tx.lock().await;
+        let tx = tx.as_mut().ok_or_else(|| {
+            crate::Error::Internal("Websocket connection lost, reconnecting.".to_string())
+        })?;
 
-        Ok(request_id)
+        // Register the request before sending it, as the response can arrive at any time
+        self.pending.lock().insert(
+            request_id.clone(),
+            PendingRequest {
+                response_tx,
+                resend: resend.then(|| message.clone()),
+            },
+        );
+        if let Err(err) = tx.send(Message::text(message)).await {
+            self.pending.lock().remove(&request_id);
+            return Err(err.into());
+        }
+
+        Ok(WsPendingResponse {
+            shared: self.clone(),
+            request_id,
+            response_rx,
+        })
     }
 
-    pub async fn enable_push_ws(
-        &self,
-        data_types: Option<impl IntoIterator<Item = DataType>>,
-        push_state: Option<impl Into<String>>,
-    ) -> crate::Result<()> {
-        self.ws
+    /// Fails the requests waiting for a response and the push subscribers of a lost connection.
+    fn fail_pending(&self, message: &str) {
+        for (_, request) in self.pending.lock().drain() {
+            let _ = request
+                .response_tx
+                .send(Err(crate::Error::Internal(message.to_string())));
+        }
+        for tx in self.push_subscribers.lock().drain(..) {
+            let _ = tx.send(Err(crate::Error::Internal(message.to_string())));
+        }
+    }
+
+    async fn send(&self, message: Message) -> crate::Result<()> {
+        self.tx
             .lock()
             .await
             .as_mut()
-            .ok_or_else(|| crate::Error::Internal("Websocket stream not set.".to_string()))?
-            .tx
-            .send(Message::text(
-                serde_json::to_string(&WebSocketPushEnable {
-                    _type: WebSocketPushEnableType::WebSocketPushEnable,
-                    data_types: data_types.map(|it| it.into_iter().collect()),
-                    push_state: push_state.map(|it| it.into()),
-                })
-                .unwrap_or_default(),
-            ))
+            .ok_or_else(|| {
+                crate::Error::Internal("Websocket connection lost, reconnecting.".to_string())
+            })?
+            .send(message)
             .await
             .map_err(|err| err.into())
     }
 
-    pub async fn disable_push_ws(&self) -> crate::Result<()> {
-        self.ws
+    async fn ping(&self) -> crate::Result<()> {
+        let ping_id = self.ping_id.fetch_add(1, Ordering::Relaxed);
+        self.ping_sent
             .lock()
+            .get_or_insert_with(|| (ping_id, Instant::now()));
+        self.send(Message::Ping(ping_id.to_be_bytes().to_vec().into()))
             .await
-            .as_mut()
-            .ok_or_else(|| crate::Error::Internal("Websocket stream not set.".to_string()))?
-            .tx
-            .send(Message::text(
-                serde_json::to_string(&WebSocketPushDisable {
-                    _type: WebSocketPushDisableType::WebSocketPushDisable,
-                })
-                .unwrap_or_default(),
-            ))
-            .await
-            .map_err(|err| err.into())
     }
 
-    pub async fn ws_ping(&self) -> crate::Result<()> {
-        self.ws
+    /// Delivers a response to its pending request and forwards everything else
+    /// to the push notification channel.
+    fn handle_message(
+        &self,
+        message: Message,
+        push_tx: &mpsc::UnboundedSender<crate::Result<WebSocketMessage>>,
+    ) {
+        if let Message::Pong(payload) = &message {
+            let mut ping_sent = self.ping_sent.lock();
+            if let Some((ping_id, sent)) = *ping_sent {
+                if payload[..] == ping_id.to_be_bytes() {
+                    *self.latency.lock() = Some(sent.elapsed());
+                    *ping_sent = None;
+                }
+            }
+            return;
+        } else if !message.is_text() {
+            return;
+        }
+
+        // Responses to pending requests are parsed by the caller
+        let data = message.into_data();
+        if let Ok(WebSocketEnvelope {
+            _type,
+            request_id: Some(request_id),
+        }) = serde_json::from_slice(&data)
+        {
+            let request = self.pending.lock().remove(&request_id);
+            if let Some(request) = request {
+                let _ = request
+                    .response_tx
+                    .send(if _type.as_deref() == Some("RequestError") {
+                        serde_json::from_slice::<WebSocketError>(&data)
+                            .map_err(crate::Error::from)
+                            .and_then(|err| Err(ProblemDetails::from(err).into()))
+                    } else {
+                        Ok(data)
+                    });
+                return;
+            }
+        }
+
+        let _ = match serde_json::from_slice::<WebSocketMessage_>(&data) {
+            Ok(WebSocketMessage_::Response(response)) => {
+                push_tx.send(Ok(WebSocketMessage::Response(Response::new(
+                    response.method_responses,
+                    response.created_ids,
+                    response.session_state,
+                    response.request_id,
+                ))))
+            }
+            Ok(WebSocketMessage_::PushNotification(push)) => {
+                if let (Some(push_enable), Some(push_state)) =
+                    (self.push.lock().as_mut(), push.push_state)
+                {
+                    push_enable.push_state = push_state.into();
+                }
+                self.push_subscribers
+                    .lock()
+                    .retain(|tx| tx.send(Ok(push.push.clone())).is_ok());
+                push_tx.send(Ok(WebSocketMessage::PushNotification(push.push)))
+            }
+            Ok(WebSocketMessage_::Error(err)) => {
+                push_tx.send(Err(ProblemDetails::from(err).into()))
+            }
+            Err(err) => push_tx.send(Err(err.into())),
+        };
+    }
+}
+
+struct WsServeContext {
+    push_tx: mpsc::UnboundedSender<crate::Result<WebSocketMessage>>,
+    state_tx: watch::Sender<WsConnectionState>,
+    retry_policy: RetryPolicy,
+    keepalive: Option<Duration>,
+    ping_timeout: u32,
+    close_rx: oneshot::Receiver<()>,
+}
+
+impl WsServeContext {
+    /// Waits for a future unless the connection is closed by the client first.
+    async fn or_closed<T>(&mut self, future: impl Future<Output = T>) -> Option<T> {
+        match futures_util::future::select(Box::pin(future), &mut self.close_rx).await {
+            Either::Left((result, _)) => Some(result),
+            Either::Right(_) => None,
+        }
+    }
+}
+
+/// Reads from the connection until it is closed by the client, re-establishing it
+/// when it is lost as long as the retry policy allows.
+async fn serve_ws(
+    connector: WsConnector,
+    shared: Arc<WsShared>,
+    mut rx: SplitStream<WsConnection>,
+    mut context: WsServeContext,
+) {
+    loop {
+        let mut next_ping = context
+            .keepalive
+            .map(|keepalive| Instant::now() + keepalive);
+        let mut error = loop {
+            let message = match next_ping {
+                Some(next_ping) => context
+                    .or_closed(tokio::time::timeout_at(next_ping, rx.next()))
+                    .await
+                    .map(|message| message.ok()),
+                None => context.or_closed(rx.next()).await.map(Some),
+            };
+            match message {
+                Some(Some(Some(Ok(message)))) => shared.handle_message(message, &context.push_tx),
+                Some(Some(Some(Err(err)))) => break Some(crate::Error::from(err)),
+                Some(Some(None)) => break None,
+                Some(None) => {
+                    // Keepalive, the connection is considered lost when a ping
+                    // remains unanswered for too long
+                    let keepalive = context.keepalive.unwrap_or_default();
+                    let ping_sent = *shared.ping_sent.lock();
+                    match ping_sent {
+                        Some((_, sent))
+                            if context.ping_timeout > 0
+                                && sent.elapsed() >= keepalive * context.ping_timeout =>
+                        {
+                            break Some(crate::Error::Internal(
+                                "No pong received from the WebSocket server.".to_string(),
+                            ));
+                        }
+                        Some(_) => (),
+                        None => {
+                            if let Err(err) = shared.ping().await {
+                                break Some(err);
+                            }
+                        }
+                    }
+                    next_ping = Some(Instant::now() + keepalive);
+                }
+                None => {
+                    if let Some(mut tx) = shared.tx.lock().await.take() {
+                        let _ = tx.close().await;
+                    }
+                    return;
+                }
+            }
+        };
+        shared.tx.lock().await.take();
+        shared.ping_sent.lock().take();
+
+        // Requests that cannot be replayed fail once their sender is dropped
+        shared
+            .pending
             .lock()
-            .await
-            .as_mut()
-            .ok_or_else(|| crate::Error::Internal("Websocket stream not set.".to_string()))?
-            .tx
-            .send(Message::Ping(vec![].into()))
-            .await
-            .map_err(|err| err.into())
+            .retain(|_, request| request.resend.is_some());
+
+        let mut attempt = 0;
+        let reconnected = loop {
+            attempt += 1;
+            let Some(delay) = context
+                .retry_policy
+                .reconnect_delay(attempt)
+                .filter(|_| context.retry_policy.is_enabled())
+            else {
+                break None;
+            };
+            context
+                .state_tx
+                .send_replace(WsConnectionState::Reconnecting { attempt });
+            match context
+                .or_closed(async {
+                    tokio::time::sleep(delay).await;
+                    connector.connect().await
+                })
+                .await
+            {
+                Some(Ok(connection)) => break Some(connection),
+                Some(Err(err)) => error = Some(err),
+                None => return,
+            }
+        };
+
+        let Some((mut tx, new_rx)) = reconnected else {
+            context
+                .state_tx
+                .send_replace(WsConnectionState::Disconnected);
+            shared.fail_pending(&match &error {
+                Some(error) => format!("WebSocket connection lost: {}", error),
+                None => "WebSocket connection lost.".to_string(),
+            });
+            if let Some(error) = error {
+                let _ = context.push_tx.send(Err(error));
+            }
+            return;
+        };
+
+        // Resume push notifications and send again the requests waiting for a response
+        let mut messages = Vec::new();
+        let push_enable = shared.push.lock().clone();
+        if let Some(push_enable) = push_enable {
+            messages.push(serde_json::to_string(&push_enable).unwrap_or_default());
+        }
+        messages.extend(
+            shared
+                .pending
+                .lock()
+                .values()
+                .filter_map(|request| request.resend.clone()),
+        );
+        let mut shared_tx = shared.tx.lock().await;
+        for message in messages {
+            let _ = tx.send(Message::text(message)).await;
+        }
+        *shared_tx = Some(tx);
+        drop(shared_tx);
+
+        rx = new_rx;
+        context.state_tx.send_replace(WsConnectionState::Connected);
     }
 }
 
@@ -373,3 +891,271 @@ impl From<WebSocketError> for ProblemDetails {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use crate::{
+        client::Client,
+        client_ws::{WebSocketMessage, WsConnectionState},
+        core::set::SetObject,
+        mailbox::Role,
+        retry::RetryPolicy,
+        testing::MockServer,
+        transport::{HttpRequest, HttpResponse, Transport},
+        DataType, PushObject,
+    };
+
+    #[tokio::test]
+    async fn send_ws() {
+        let server = MockServer::new();
+        server.listen_ws().await.unwrap();
+        let client = server.connect().await.unwrap();
+        let mut push_stream = client.connect_ws().await.unwrap();
+
+        // Concurrent requests receive their own responses
+        let mut create_request = client.build();
+        let create_id = create_request
+            .set_mailbox()
+            .create()
+            .name("WebSocket")
+            .create_id()
+            .unwrap();
+        let mut get_request = client.build();
+        get_request.get_mailbox();
+        let (created, listed) =
+            futures_util::join!(create_request.send_ws(), get_request.send_ws());
+        let mailbox_id = created
+            .unwrap()
+            .pop_method_response()
+            .unwrap()
+            .unwrap_set_mailbox()
+            .unwrap()
+            .created(&create_id)
+            .unwrap()
+            .take_id();
+        assert!(listed
+            .unwrap()
+            .pop_method_response()
+            .unwrap()
+            .unwrap_get_mailbox()
+            .is_ok());
+
+        // Push notifications are delivered to the stream
+        client
+            .enable_push_ws(Some([DataType::Mailbox]), None::<&str>)
+            .await
+            .unwrap();
+        let mut request = client.build();
+        request
+            .set_mailbox()
+            .update(&mailbox_id)
+            .role(Role::Archive);
+        request.send_ws().await.unwrap();
+        match push_stream.next().await.unwrap().unwrap() {
+            WebSocketMessage::PushNotification(PushObject::StateChange { changed }) => {
+                assert!(changed[server.account_id()].contains_key(&DataType::Mailbox));
+            }
+            message => panic!("Unexpected message: {message:?}"),
+        }
+
+        // Request errors are returned to the caller
+        let mut request = client.build();
+        request.get_mailbox().account_id("unknown");
+        let result = request.send_ws().await;
+        assert!(result.is_ok_and(|mut response| response
+            .pop_method_response()
+            .unwrap()
+            .unwrap_get_mailbox()
+            .is_err()));
+    }
+
+    #[tokio::test]
+    async fn ws_reconnect() {
+        let server = MockServer::new();
+        server.listen_ws().await.unwrap();
+        let client = Client::new()
+            .credentials("secret")
+            .transport(server.clone())
+            .retry_policy(
+                RetryPolicy::new()
+                    .backoff(Duration::from_millis(1), Duration::from_millis(5))
+                    .jitter(false),
+            )
+            .connect(server.url())
+            .await
+            .unwrap();
+        let mut states = client.ws_connection_state();
+        assert_eq!(states.next().await, Some(WsConnectionState::Disconnected));
+
+        let mut push_stream = client.connect_ws().await.unwrap();
+        assert_eq!(states.next().await, Some(WsConnectionState::Connected));
+        client
+            .enable_push_ws(Some([DataType::Mailbox]), None::<&str>)
+            .await
+            .unwrap();
+
+        // Connections closed by the server are re-established
+        server.close_ws();
+        assert_eq!(
+            states.next().await,
+            Some(WsConnectionState::Reconnecting { attempt: 1 })
+        );
+        assert_eq!(states.next().await, Some(WsConnectionState::Connected));
+
+        // Requests and push notifications continue over the new connection
+        let mut request = client.build();
+        request.set_mailbox().create().name("Reconnected");
+        request.send_ws().await.unwrap();
+        assert!(matches!(
+            push_stream.next().await,
+            Some(Ok(WebSocketMessage::PushNotification(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn ws_keepalive() {
+        let server = MockServer::new();
+        server.listen_ws().await.unwrap();
+        let client = Client::new()
+            .credentials("secret")
+            .transport(server.clone())
+            .retry_policy(
+                RetryPolicy::new().backoff(Duration::from_millis(1), Duration::from_millis(5)),
+            )
+            .ws_keepalive(Duration::from_millis(20))
+            .ping_timeout(2)
+            .connect(server.url())
+            .await
+            .unwrap();
+        let mut states = client.ws_connection_state();
+        let _push_stream = client.connect_ws().await.unwrap();
+        assert_eq!(states.next().await, Some(WsConnectionState::Connected));
+
+        // Pongs to the keepalive pings provide the latency
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client.ws_latency().is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // Connections that stop answering pings are re-established
+        server.stall_ws();
+        assert_eq!(
+            states.next().await,
+            Some(WsConnectionState::Reconnecting { attempt: 1 })
+        );
+        assert_eq!(states.next().await, Some(WsConnectionState::Connected));
+    }
+
+    #[tokio::test]
+    async fn ws_replace() {
+        let server = MockServer::new();
+        server.listen_ws().await.unwrap();
+        let client = server.connect().await.unwrap();
+        let _push_stream = client.connect_ws().await.unwrap();
+
+        // Requests waiting on a replaced connection fail right away
+        server.stall_ws();
+        let mut request = client.build();
+        request.get_mailbox();
+        let started = std::time::Instant::now();
+        let (result, _) = tokio::join!(client.send_ws(request), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            client.connect_ws().await.unwrap()
+        });
+        match result {
+            Err(crate::Error::Internal(err)) => {
+                assert!(err.starts_with("WebSocket connection lost"))
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+        assert!(started.elapsed() < client.timeout());
+    }
+
+    #[tokio::test]
+    async fn prefer_websocket() {
+        // Rejects API requests sent over HTTP
+        struct WebSocketOnly(MockServer);
+
+        #[async_trait::async_trait]
+        impl Transport for WebSocketOnly {
+            async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+                if request.url.contains("/api/") {
+                    Err(crate::Error::Internal("Sent over HTTP".to_string()))
+                } else {
+                    self.0.send(request).await
+                }
+            }
+        }
+
+        let server = MockServer::new();
+        server.listen_ws().await.unwrap();
+        let client = Client::new()
+            .credentials("secret")
+            .transport(WebSocketOnly(server.clone()))
+            .prefer_websocket(true)
+            .connect(server.url())
+            .await
+            .unwrap();
+        let mailbox_id = client
+            .mailbox_create("WebSocket", None::<String>, Role::None)
+            .await
+            .unwrap()
+            .take_id();
+        assert_eq!(
+            client
+                .mailbox_get(&mailbox_id, None::<Vec<_>>)
+                .await
+                .unwrap()
+                .unwrap()
+                .name(),
+            Some("WebSocket")
+        );
+        assert_eq!(
+            client.ws_connection_state().next().await,
+            Some(WsConnectionState::Connected)
+        );
+
+        // Requests split to fit the server limits are also sent over WebSocket
+        let mut request = client.build();
+        for _ in 0..17 {
+            request.get_mailbox().ids([&mailbox_id]);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(response.method_responses().len(), 17);
+
+        // Calls over maxObjectsInGet are split and their responses merged back
+        let mut request = client.build();
+        request.get_mailbox().ids(
+            (0..501)
+                .map(|id| format!("unknown{id}"))
+                .chain([mailbox_id.clone()]),
+        );
+        let response = request
+            .send_single::<crate::core::get::GetResponse<crate::mailbox::Mailbox>>()
+            .await
+            .unwrap();
+        assert_eq!(response.list().len(), 1);
+        assert_eq!(response.not_found().len(), 501);
+
+        // Requests fall back to HTTP when WebSocket is not supported
+        let server = MockServer::new();
+        let client = Client::new()
+            .credentials("secret")
+            .transport(server.clone())
+            .prefer_websocket(true)
+            .connect(server.url())
+            .await
+            .unwrap();
+        assert!(client
+            .mailbox_create("HTTP", None::<String>, Role::None)
+            .await
+            .is_ok());
+    }
+}
@@ -53,6 +53,7 @@ pub struct GetResponse<T> {
     #[serde(rename = "accountId")]
     account_id: Option<String>,
 
+    #[serde(default)]
     state: String,
 
     list: Vec<T>,
@@ -117,6 +118,10 @@ impl<O: GetObject> GetRequest<O> {
         &mut self.arguments
     }
 
+    pub(crate) fn ids_count(&self) -> usize {
+        self.ids.as_ref().map_or(0, Vec::len)
+    }
+
     pub fn result_reference(&self, property: O::Property) -> ResultReference {
         ResultReference::new(
             self.method.0,
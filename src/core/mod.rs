@@ -25,6 +25,7 @@ pub mod request;
 pub mod response;
 pub mod session;
 pub mod set;
+pub(crate) mod split;
 
 pub struct RequestParams {
     pub account_id: String,
@@ -10,7 +10,9 @@
  */
 
 use crate::{
-    blob::copy::CopyBlobRequest,
+    blob::{
+        copy::CopyBlobRequest, lookup::LookupBlobRequest, upload_blob::UploadBlobRequest, Blob,
+    },
     client::Client,
     email::{
         import::EmailImportRequest, parse::EmailParseRequest,
@@ -19,8 +21,10 @@ use crate::{
     email_submission::EmailSubmission,
     identity::Identity,
     mailbox::Mailbox,
+    mdn::{parse::MDNParseRequest, send::MDNSendRequest},
     principal::Principal,
     push_subscription::PushSubscription,
+    quota::Quota,
     sieve::{validate::SieveScriptValidateRequest, SieveScript},
     thread::Thread,
     vacation_response::VacationResponse,
@@ -72,6 +76,9 @@ pub enum Arguments {
     PushGet(GetRequest<PushSubscription<Set>>),
     PushSet(SetRequest<PushSubscription<Set>>),
     BlobCopy(CopyBlobRequest),
+    BlobUpload(UploadBlobRequest),
+    BlobGet(GetRequest<Blob>),
+    BlobLookup(LookupBlobRequest),
     MailboxGet(GetRequest<Mailbox<Set>>),
     MailboxQuery(QueryRequest<Mailbox<Set>>),
     MailboxQueryChanges(QueryChangesRequest<Mailbox<Set>>),
@@ -101,6 +108,11 @@ pub enum Arguments {
     PrincipalQuery(QueryRequest<Principal<Set>>),
     PrincipalQueryChanges(QueryChangesRequest<Principal<Set>>),
     PrincipalSet(SetRequest<Principal<Set>>),
+    QuotaGet(GetRequest<Quota>),
+    QuotaQuery(QueryRequest<Quota>),
+    QuotaQueryChanges(QueryChangesRequest<Quota>),
+    MdnSend(MDNSendRequest),
+    MdnParse(MDNParseRequest),
 }
 
 impl Arguments {
@@ -120,6 +132,18 @@ impl Arguments {
         Arguments::BlobCopy(CopyBlobRequest::new(params, from_account_id))
     }
 
+    pub fn blob_upload(params: RequestParams) -> Self {
+        Arguments::BlobUpload(UploadBlobRequest::new(params))
+    }
+
+    pub fn blob_get(params: RequestParams) -> Self {
+        Arguments::BlobGet(GetRequest::new(params))
+    }
+
+    pub fn blob_lookup(params: RequestParams) -> Self {
+        Arguments::BlobLookup(LookupBlobRequest::new(params))
+    }
+
     pub fn mailbox_get(params: RequestParams) -> Self {
         Arguments::MailboxGet(GetRequest::new(params))
     }
@@ -239,6 +263,26 @@ impl Arguments {
         Arguments::PrincipalSet(SetRequest::new(params))
     }
 
+    pub fn quota_get(params: RequestParams) -> Self {
+        Arguments::QuotaGet(GetRequest::new(params))
+    }
+
+    pub fn quota_query(params: RequestParams) -> Self {
+        Arguments::QuotaQuery(QueryRequest::new(params))
+    }
+
+    pub fn quota_query_changes(params: RequestParams, since_query_state: String) -> Self {
+        Arguments::QuotaQueryChanges(QueryChangesRequest::new(params, since_query_state))
+    }
+
+    pub fn mdn_send(params: RequestParams, identity_id: impl Into<String>) -> Self {
+        Arguments::MdnSend(MDNSendRequest::new(params, identity_id))
+    }
+
+    pub fn mdn_parse(params: RequestParams) -> Self {
+        Arguments::MdnParse(MDNParseRequest::new(params))
+    }
+
     pub fn changes_mut(&mut self) -> &mut ChangesRequest {
         match self {
             Arguments::Changes(ref mut r) => r,
@@ -267,6 +311,27 @@ impl Arguments {
         }
     }
 
+    pub fn blob_upload_mut(&mut self) -> &mut UploadBlobRequest {
+        match self {
+            Arguments::BlobUpload(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn blob_get_mut(&mut self) -> &mut GetRequest<Blob> {
+        match self {
+            Arguments::BlobGet(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn blob_lookup_mut(&mut self) -> &mut LookupBlobRequest {
+        match self {
+            Arguments::BlobLookup(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
     pub fn mailbox_get_mut(&mut self) -> &mut GetRequest<Mailbox<Set>> {
         match self {
             Arguments::MailboxGet(ref mut r) => r,
@@ -471,6 +536,68 @@ impl Arguments {
             _ => unreachable!(),
         }
     }
+
+    pub fn quota_get_mut(&mut self) -> &mut GetRequest<Quota> {
+        match self {
+            Arguments::QuotaGet(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn quota_query_mut(&mut self) -> &mut QueryRequest<Quota> {
+        match self {
+            Arguments::QuotaQuery(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn quota_query_changes_mut(&mut self) -> &mut QueryChangesRequest<Quota> {
+        match self {
+            Arguments::QuotaQueryChanges(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn mdn_send_mut(&mut self) -> &mut MDNSendRequest {
+        match self {
+            Arguments::MdnSend(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn mdn_parse_mut(&mut self) -> &mut MDNParseRequest {
+        match self {
+            Arguments::MdnParse(ref mut r) => r,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the number of objects requested by a `/get` call and
+    /// changed by a `/set` call.
+    pub(crate) fn object_count(&self) -> (usize, usize) {
+        match self {
+            Arguments::PushGet(r) => (r.ids_count(), 0),
+            Arguments::BlobGet(r) => (r.ids_count(), 0),
+            Arguments::MailboxGet(r) => (r.ids_count(), 0),
+            Arguments::ThreadGet(r) => (r.ids_count(), 0),
+            Arguments::EmailGet(r) => (r.ids_count(), 0),
+            Arguments::IdentityGet(r) => (r.ids_count(), 0),
+            Arguments::EmailSubmissionGet(r) => (r.ids_count(), 0),
+            Arguments::VacationResponseGet(r) => (r.ids_count(), 0),
+            Arguments::SieveScriptGet(r) => (r.ids_count(), 0),
+            Arguments::PrincipalGet(r) => (r.ids_count(), 0),
+            Arguments::QuotaGet(r) => (r.ids_count(), 0),
+            Arguments::PushSet(r) => (0, r.object_count()),
+            Arguments::MailboxSet(r) => (0, r.object_count()),
+            Arguments::EmailSet(r) => (0, r.object_count()),
+            Arguments::IdentitySet(r) => (0, r.object_count()),
+            Arguments::EmailSubmissionSet(r) => (0, r.object_count()),
+            Arguments::VacationResponseSet(r) => (0, r.object_count()),
+            Arguments::SieveScriptSet(r) => (0, r.object_count()),
+            Arguments::PrincipalSet(r) => (0, r.object_count()),
+            _ => (0, 0),
+        }
+    }
 }
 
 impl<'x> Request<'x> {
@@ -479,7 +606,7 @@ impl<'x> Request<'x> {
             using: vec![URI::Core, URI::Mail],
             method_calls: vec![],
             created_ids: None,
-            account_id: client.default_account_id().to_string(),
+            account_id: client.default_account_id(),
             client,
         }
     }
@@ -495,7 +622,7 @@ impl<'x> Request<'x> {
     }
 
     #[cfg(feature = "websockets")]
-    pub async fn send_ws(self) -> crate::Result<String> {
+    pub async fn send_ws(self) -> crate::Result<Response<TaggedMethodResponse>> {
         self.client.send_ws(self).await
     }
 
@@ -529,6 +656,12 @@ impl<'x> Request<'x> {
         &mut self.method_calls.last_mut().unwrap().1
     }
 
+    pub fn is_idempotent(&self) -> bool {
+        self.method_calls
+            .iter()
+            .all(|(method, _, _)| method.is_idempotent())
+    }
+
     pub fn add_capability(&mut self, uri: URI) {
         if !self.using.contains(&uri) {
             self.using.push(uri);
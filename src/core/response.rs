@@ -14,7 +14,9 @@ use serde::{de::Visitor, Deserialize};
 use std::fmt;
 
 use crate::{
-    blob::copy::CopyBlobResponse,
+    blob::{
+        copy::CopyBlobResponse, lookup::LookupBlobResponse, upload_blob::UploadBlobResponse, Blob,
+    },
     email::{
         import::EmailImportResponse, parse::EmailParseResponse,
         search_snippet::SearchSnippetGetResponse, Email,
@@ -22,8 +24,10 @@ use crate::{
     email_submission::EmailSubmission,
     identity::Identity,
     mailbox::Mailbox,
+    mdn::{parse::MDNParseResponse, send::MDNSendResponse},
     principal::Principal,
     push_subscription::PushSubscription,
+    quota::Quota,
     sieve::{validate::SieveScriptValidateResponse, SieveScript},
     thread::Thread,
     vacation_response::VacationResponse,
@@ -46,6 +50,7 @@ pub struct Response<T> {
     #[serde(rename = "sessionState")]
     session_state: String,
 
+    #[serde(rename = "requestId")]
     request_id: Option<String>,
 }
 
@@ -114,6 +119,7 @@ pub enum Error {
     Error,
 }
 
+pub type BlobGetResponse = GetResponse<Blob>;
 pub type PushSubscriptionSetResponse = SetResponse<PushSubscription<Get>>;
 pub type PushSubscriptionGetResponse = GetResponse<PushSubscription<Get>>;
 pub type MailboxChangesResponse = ChangesResponse<Mailbox<Get>>;
@@ -138,6 +144,8 @@ pub type SieveScriptSetResponse = SetResponse<SieveScript<Get>>;
 pub type PrincipalChangesResponse = ChangesResponse<Principal<Get>>;
 pub type PrincipalSetResponse = SetResponse<Principal<Get>>;
 pub type PrincipalGetResponse = GetResponse<Principal<Get>>;
+pub type QuotaGetResponse = GetResponse<Quota>;
+pub type QuotaChangesResponse = ChangesResponse<Quota>;
 
 #[derive(Debug)]
 pub struct TaggedMethodResponse {
@@ -148,6 +156,9 @@ pub struct TaggedMethodResponse {
 #[derive(Debug)]
 pub enum MethodResponse {
     CopyBlob(CopyBlobResponse),
+    UploadBlob(UploadBlobResponse),
+    GetBlob(BlobGetResponse),
+    LookupBlob(LookupBlobResponse),
     GetPushSubscription(PushSubscriptionGetResponse),
     SetPushSubscription(PushSubscriptionSetResponse),
     GetMailbox(MailboxGetResponse),
@@ -187,6 +198,14 @@ pub enum MethodResponse {
     QueryChangesPrincipal(QueryChangesResponse),
     SetPrincipal(PrincipalSetResponse),
 
+    GetQuota(QuotaGetResponse),
+    ChangesQuota(QuotaChangesResponse),
+    QueryQuota(QueryResponse),
+    QueryChangesQuota(QueryChangesResponse),
+
+    SendMdn(MDNSendResponse),
+    ParseMdn(MDNParseResponse),
+
     Echo(serde_json::Value),
     Error(MethodError),
 }
@@ -200,6 +219,9 @@ impl TaggedMethodResponse {
         matches!(
             (&self.response, type_),
             (MethodResponse::CopyBlob(_), Method::CopyBlob)
+                | (MethodResponse::UploadBlob(_), Method::UploadBlob)
+                | (MethodResponse::GetBlob(_), Method::GetBlob)
+                | (MethodResponse::LookupBlob(_), Method::LookupBlob)
                 | (
                     MethodResponse::GetPushSubscription(_),
                     Method::GetPushSubscription
@@ -285,6 +307,15 @@ impl TaggedMethodResponse {
                     Method::QueryChangesPrincipal
                 )
                 | (MethodResponse::SetPrincipal(_), Method::SetPrincipal)
+                | (MethodResponse::GetQuota(_), Method::GetQuota)
+                | (MethodResponse::ChangesQuota(_), Method::ChangesQuota)
+                | (MethodResponse::QueryQuota(_), Method::QueryQuota)
+                | (
+                    MethodResponse::QueryChangesQuota(_),
+                    Method::QueryChangesQuota
+                )
+                | (MethodResponse::SendMdn(_), Method::SendMdn)
+                | (MethodResponse::ParseMdn(_), Method::ParseMdn)
                 | (MethodResponse::Echo(_), Method::Echo)
                 | (MethodResponse::Error(_), Method::Error)
         )
@@ -302,6 +333,30 @@ impl TaggedMethodResponse {
         }
     }
 
+    pub fn unwrap_upload_blob(self) -> crate::Result<UploadBlobResponse> {
+        match self.response {
+            MethodResponse::UploadBlob(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_get_blob(self) -> crate::Result<BlobGetResponse> {
+        match self.response {
+            MethodResponse::GetBlob(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_lookup_blob(self) -> crate::Result<LookupBlobResponse> {
+        match self.response {
+            MethodResponse::LookupBlob(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
     pub fn unwrap_get_push_subscription(self) -> crate::Result<PushSubscriptionGetResponse> {
         match self.response {
             MethodResponse::GetPushSubscription(response) => Ok(response),
@@ -598,6 +653,54 @@ impl TaggedMethodResponse {
         }
     }
 
+    pub fn unwrap_get_quota(self) -> crate::Result<QuotaGetResponse> {
+        match self.response {
+            MethodResponse::GetQuota(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_changes_quota(self) -> crate::Result<QuotaChangesResponse> {
+        match self.response {
+            MethodResponse::ChangesQuota(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_query_quota(self) -> crate::Result<QueryResponse> {
+        match self.response {
+            MethodResponse::QueryQuota(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_query_changes_quota(self) -> crate::Result<QueryChangesResponse> {
+        match self.response {
+            MethodResponse::QueryChangesQuota(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_send_mdn(self) -> crate::Result<MDNSendResponse> {
+        match self.response {
+            MethodResponse::SendMdn(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
+    pub fn unwrap_parse_mdn(self) -> crate::Result<MDNParseResponse> {
+        match self.response {
+            MethodResponse::ParseMdn(response) => Ok(response),
+            MethodResponse::Error(err) => Err(err.into()),
+            _ => Err("Response type mismatch".into()),
+        }
+    }
+
     pub fn unwrap_echo(self) -> crate::Result<serde_json::Value> {
         match self.response {
             MethodResponse::Echo(response) => Ok(response),
@@ -645,6 +748,18 @@ impl<'de> Visitor<'de> for TaggedMethodResponseVisitor {
                 seq.next_element()?
                     .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
             ),
+            Method::UploadBlob => MethodResponse::UploadBlob(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::GetBlob => MethodResponse::GetBlob(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::LookupBlob => MethodResponse::LookupBlob(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
             Method::GetPushSubscription => MethodResponse::GetPushSubscription(
                 seq.next_element()?
                     .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
@@ -793,6 +908,30 @@ impl<'de> Visitor<'de> for TaggedMethodResponseVisitor {
                 seq.next_element()?
                     .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
             ),
+            Method::GetQuota => MethodResponse::GetQuota(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::ChangesQuota => MethodResponse::ChangesQuota(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::QueryQuota => MethodResponse::QueryQuota(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::QueryChangesQuota => MethodResponse::QueryChangesQuota(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::SendMdn => MethodResponse::SendMdn(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
+            Method::ParseMdn => MethodResponse::ParseMdn(
+                seq.next_element()?
+                    .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
+            ),
             Method::Error => MethodResponse::Error(
                 seq.next_element()?
                     .ok_or_else(|| serde::de::Error::custom("Expected a method response"))?,
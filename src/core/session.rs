@@ -10,6 +10,7 @@
  */
 
 use crate::{
+    blob::BlobCapabilities,
     email::{MailCapabilities, SubmissionCapabilities},
     URI,
 };
@@ -69,6 +70,7 @@ pub enum Capabilities {
     Submission(SubmissionCapabilities),
     WebSocket(WebSocketCapabilities),
     Sieve(SieveCapabilities),
+    Blob(BlobCapabilities),
     Empty(EmptyCapabilities),
     Other(serde_json::Value),
 }
@@ -246,6 +248,15 @@ impl Account {
     pub fn capability(&self, capability: &str) -> Option<&Capabilities> {
         self.account_capabilities.get(capability)
     }
+
+    pub fn blob_capabilities(&self) -> Option<&BlobCapabilities> {
+        self.account_capabilities
+            .get(URI::Blob.as_ref())
+            .and_then(|v| match v {
+                Capabilities::Blob(capabilities) => Some(capabilities),
+                _ => None,
+            })
+    }
 }
 
 impl CoreCapabilities {
@@ -145,6 +145,8 @@ pub enum SetErrorType {
     InvalidScript,
     #[serde(rename = "scriptIsActive")]
     ScriptIsActive,
+    #[serde(rename = "mdnAlreadySent")]
+    MdnAlreadySent,
 }
 
 impl<O: SetObject> SetRequest<O> {
@@ -241,6 +243,12 @@ impl<O: SetObject> SetRequest<O> {
     pub fn arguments(&mut self) -> &mut O::SetArguments {
         &mut self.arguments
     }
+
+    pub(crate) fn object_count(&self) -> usize {
+        self.create.as_ref().map_or(0, |objects| objects.len())
+            + self.update.as_ref().map_or(0, |objects| objects.len())
+            + self.destroy.as_ref().map_or(0, Vec::len)
+    }
 }
 
 impl<O: SetObject> SetResponse<O> {
@@ -433,6 +441,7 @@ impl Display for SetErrorType {
             SetErrorType::AlreadyExists => write!(f, "alreadyExists"),
             SetErrorType::InvalidScript => write!(f, "invalidScript"),
             SetErrorType::ScriptIsActive => write!(f, "scriptIsActive"),
+            SetErrorType::MdnAlreadySent => write!(f, "mdnAlreadySent"),
         }
     }
 }
@@ -0,0 +1,492 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashSet;
+use serde_json::{Map, Value};
+
+use crate::Error;
+
+use super::{request::Request, session::CoreCapabilities};
+
+// Approximate size of the request envelope excluding "using" and "methodCalls".
+const REQUEST_OVERHEAD: usize = 64;
+
+/// A request that exceeds the server's `CoreCapabilities` limits, split into
+/// several HTTP requests.
+///
+/// Oversized `/get` and `/set` calls are divided in chunks that share the call id
+/// of the original call, and their responses are merged back into a single
+/// response. Calls referenced by a result reference are never split, and a
+/// call is always sent in the same HTTP request as the calls it references.
+/// Creation ids are carried between HTTP requests using `createdIds`.
+pub(crate) struct SplitRequest {
+    using: Value,
+    batches: std::vec::IntoIter<Vec<Value>>,
+    split_calls: AHashSet<String>,
+    method_responses: Vec<Value>,
+    created_ids: Map<String, Value>,
+    session_state: Value,
+}
+
+impl SplitRequest {
+    /// Splits a serialized request, returning `None` if it is within the limits.
+    pub(crate) fn new(
+        request: &mut Value,
+        capabilities: &CoreCapabilities,
+    ) -> crate::Result<Option<Self>> {
+        let max_calls = limit(capabilities.max_calls_in_request());
+        let max_size = limit(capabilities.max_size_request());
+        let method_calls = match request.get_mut("methodCalls") {
+            Some(Value::Array(method_calls)) => method_calls,
+            _ => return Err(Error::Internal("Invalid request.".to_string())),
+        };
+
+        // Calls referenced by other calls have to keep their full results
+        let referenced = method_calls
+            .iter()
+            .flat_map(|call| references(call).map(str::to_string))
+            .collect::<AHashSet<_>>();
+
+        let mut split_calls = AHashSet::new();
+        let mut calls = Vec::with_capacity(method_calls.len());
+        for call in method_calls.iter() {
+            let call_id = call_id(call);
+            match (!referenced.contains(call_id))
+                .then(|| split_call(call, capabilities))
+                .flatten()
+            {
+                Some(chunks) => {
+                    split_calls.insert(call_id.to_string());
+                    calls.extend(chunks);
+                }
+                None => calls.push(call.clone()),
+            }
+        }
+
+        let sizes = calls
+            .iter()
+            .map(|call| serde_json::to_vec(call).map(|call| call.len() + 1))
+            .collect::<Result<Vec<_>, _>>()?;
+        let using = request.get("using").cloned().unwrap_or(Value::Null);
+        let overhead = REQUEST_OVERHEAD + serde_json::to_vec(&using)?.len();
+        if split_calls.is_empty()
+            && calls.len() <= max_calls
+            && overhead + sizes.iter().sum::<usize>() <= max_size
+        {
+            return Ok(None);
+        }
+
+        // A batch may only end before `pos` if no later call references an earlier one
+        let mut first_ref = calls
+            .iter()
+            .enumerate()
+            .map(|(pos, call)| {
+                references(call)
+                    .filter_map(|id| calls.iter().position(|call| call_id(call) == id))
+                    .fold(pos, usize::min)
+            })
+            .collect::<Vec<_>>();
+        for pos in (0..first_ref.len().saturating_sub(1)).rev() {
+            first_ref[pos] = first_ref[pos].min(first_ref[pos + 1]);
+        }
+        let can_split_at = |pos: usize| first_ref[pos] >= pos;
+
+        let mut batches = Vec::new();
+        let mut batch_start = 0;
+        while batch_start < calls.len() {
+            let mut batch_end = batch_start + 1;
+            let mut batch_size = overhead + sizes[batch_start];
+            while batch_end < calls.len()
+                && batch_end - batch_start < max_calls
+                && batch_size + sizes[batch_end] <= max_size
+            {
+                batch_size += sizes[batch_end];
+                batch_end += 1;
+            }
+
+            let mut split_at = batch_end;
+            while split_at > batch_start && split_at < calls.len() && !can_split_at(split_at) {
+                split_at -= 1;
+            }
+            if split_at == batch_start {
+                // The calls can't be divided, send them exceeding the limits
+                split_at = batch_end;
+                while split_at < calls.len() && !can_split_at(split_at) {
+                    split_at += 1;
+                }
+            }
+            batches.push(calls[batch_start..split_at].to_vec());
+            batch_start = split_at;
+        }
+
+        Ok(Some(SplitRequest {
+            using,
+            batches: batches.into_iter(),
+            split_calls,
+            method_responses: Vec::new(),
+            created_ids: match request.get_mut("createdIds").map(Value::take) {
+                Some(Value::Object(created_ids)) => created_ids,
+                _ => Map::new(),
+            },
+            session_state: Value::Null,
+        }))
+    }
+
+    /// Returns whether a request of `size` bytes may exceed the limits and has to be
+    /// passed to [SplitRequest::new].
+    pub(crate) fn may_exceed(
+        request: &Request<'_>,
+        size: usize,
+        capabilities: &CoreCapabilities,
+    ) -> bool {
+        let max_objects_in_get = limit(capabilities.max_objects_in_get());
+        let max_objects_in_set = limit(capabilities.max_objects_in_set());
+        size > limit(capabilities.max_size_request())
+            || request.method_calls.len() > limit(capabilities.max_calls_in_request())
+            || request.method_calls.iter().any(|(_, arguments, _)| {
+                let (get_objects, set_objects) = arguments.object_count();
+                get_objects > max_objects_in_get || set_objects > max_objects_in_set
+            })
+    }
+
+    /// Returns the body of the next HTTP request to send.
+    pub(crate) fn next_request(&mut self) -> Option<Value> {
+        self.batches.next().map(|method_calls| {
+            serde_json::json!({
+                "using": self.using,
+                "methodCalls": method_calls,
+                "createdIds": self.created_ids,
+            })
+        })
+    }
+
+    pub(crate) fn add_response(&mut self, mut response: Value) -> crate::Result<()> {
+        match response.get_mut("methodResponses").map(Value::take) {
+            Some(Value::Array(method_responses)) => {
+                for method_response in method_responses {
+                    match self.method_responses.last_mut() {
+                        Some(last)
+                            if call_id(last) == call_id(&method_response)
+                                && self.split_calls.contains(call_id(last)) =>
+                        {
+                            merge_response(last, method_response);
+                        }
+                        _ => self.method_responses.push(method_response),
+                    }
+                }
+            }
+            _ => return Err(Error::Internal("Invalid response.".to_string())),
+        }
+        if let Some(Value::Object(created_ids)) = response.get_mut("createdIds").map(Value::take) {
+            self.created_ids.extend(created_ids);
+        }
+        if let Some(session_state) = response.get_mut("sessionState").map(Value::take) {
+            self.session_state = session_state;
+        }
+        Ok(())
+    }
+
+    /// Returns the merged response of all the HTTP requests.
+    pub(crate) fn into_response(self) -> Value {
+        serde_json::json!({
+            "methodResponses": self.method_responses,
+            "createdIds": self.created_ids,
+            "sessionState": self.session_state,
+        })
+    }
+}
+
+fn limit(value: usize) -> usize {
+    if value > 0 {
+        value
+    } else {
+        usize::MAX
+    }
+}
+
+fn call_id(call: &Value) -> &str {
+    call.get(2).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn references(call: &Value) -> impl Iterator<Item = &str> {
+    call.get(1)
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|arguments| arguments.iter())
+        .filter(|(name, _)| name.starts_with('#'))
+        .filter_map(|(_, reference)| reference.get("resultOf").and_then(Value::as_str))
+}
+
+fn split_call(call: &Value, capabilities: &CoreCapabilities) -> Option<Vec<Value>> {
+    let method = call.get(0)?.as_str()?;
+    let arguments = call.get(1)?.as_object()?;
+    let chunk = |arguments: Map<String, Value>| {
+        Value::Array(vec![
+            call[0].clone(),
+            Value::Object(arguments),
+            call[2].clone(),
+        ])
+    };
+
+    if method.ends_with("/get") {
+        let max_objects = limit(capabilities.max_objects_in_get());
+        let ids = arguments.get("ids")?.as_array()?;
+        if ids.len() <= max_objects {
+            return None;
+        }
+        Some(
+            ids.chunks(max_objects)
+                .map(|ids| {
+                    let mut arguments = arguments.clone();
+                    arguments.insert("ids".to_string(), Value::Array(ids.to_vec()));
+                    chunk(arguments)
+                })
+                .collect(),
+        )
+    } else if method.ends_with("/set") {
+        // Chunks after the first one would fail the state check, and
+        // the onSuccess arguments refer to objects in other chunks.
+        if arguments
+            .get("ifInState")
+            .is_some_and(|state| !state.is_null())
+            || arguments.keys().any(|name| name.starts_with("onSuccess"))
+        {
+            return None;
+        }
+        let max_objects = limit(capabilities.max_objects_in_set());
+        let create = arguments.get("create").and_then(Value::as_object);
+        let update = arguments.get("update").and_then(Value::as_object);
+        let destroy = arguments.get("destroy").and_then(Value::as_array);
+        let total =
+            create.map_or(0, Map::len) + update.map_or(0, Map::len) + destroy.map_or(0, Vec::len);
+        if total <= max_objects {
+            return None;
+        }
+
+        let mut base = arguments.clone();
+        base.remove("create");
+        base.remove("update");
+        base.remove("destroy");
+        let objects = create
+            .into_iter()
+            .flatten()
+            .map(|(id, object)| ("create", Some((id, object)), None))
+            .chain(
+                update
+                    .into_iter()
+                    .flatten()
+                    .map(|(id, object)| ("update", Some((id, object)), None)),
+            )
+            .chain(
+                destroy
+                    .into_iter()
+                    .flatten()
+                    .map(|id| ("destroy", None, Some(id))),
+            )
+            .collect::<Vec<_>>();
+
+        Some(
+            objects
+                .chunks(max_objects)
+                .map(|objects| {
+                    let mut arguments = base.clone();
+                    for (name, object, id) in objects {
+                        let value = arguments.entry(name.to_string()).or_insert_with(|| {
+                            if id.is_some() {
+                                Value::Array(Vec::new())
+                            } else {
+                                Value::Object(Map::new())
+                            }
+                        });
+                        match (value, object, id) {
+                            (Value::Object(map), Some((id, object)), _) => {
+                                map.insert(id.to_string(), (*object).clone());
+                            }
+                            (Value::Array(list), _, Some(id)) => {
+                                list.push((*id).clone());
+                            }
+                            _ => unreachable!(),
+                        }
+                    }
+                    chunk(arguments)
+                })
+                .collect(),
+        )
+    } else {
+        None
+    }
+}
+
+fn merge_response(target: &mut Value, mut response: Value) {
+    if target.get(0).and_then(Value::as_str) == Some("error") {
+        return;
+    } else if response.get(0).and_then(Value::as_str) == Some("error") {
+        *target = response;
+        return;
+    }
+
+    let method = target.get(0).and_then(Value::as_str).unwrap_or_default();
+    let fields: &[&str] = if method.ends_with("/get") {
+        &["list", "notFound"]
+    } else if method.ends_with("/set") {
+        &[
+            "created",
+            "updated",
+            "destroyed",
+            "notCreated",
+            "notUpdated",
+            "notDestroyed",
+        ]
+    } else {
+        &[]
+    };
+
+    if let (Some(Value::Object(target)), Some(Value::Object(mut response))) =
+        (target.get_mut(1), response.get_mut(1).map(Value::take))
+    {
+        for field in fields {
+            match (target.get_mut(*field), response.remove(*field)) {
+                (Some(Value::Array(target)), Some(Value::Array(values))) => {
+                    target.extend(values);
+                }
+                (Some(Value::Object(target)), Some(Value::Object(values))) => {
+                    target.extend(values);
+                }
+                (Some(Value::Null) | None, Some(value)) => {
+                    target.insert(field.to_string(), value);
+                }
+                _ => (),
+            }
+        }
+        if let Some(new_state) = response.remove("newState") {
+            target.insert("newState".to_string(), new_state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::SplitRequest;
+    use crate::core::session::CoreCapabilities;
+
+    fn capabilities(max_calls: usize, max_get: usize, max_set: usize) -> CoreCapabilities {
+        serde_json::from_value(json!({
+            "maxSizeUpload": 0,
+            "maxConcurrentUpload": 0,
+            "maxSizeRequest": 0,
+            "maxConcurrentRequests": 0,
+            "maxCallsInRequest": max_calls,
+            "maxObjectsInGet": max_get,
+            "maxObjectsInSet": max_set,
+            "collationAlgorithms": []
+        }))
+        .unwrap()
+    }
+
+    fn batches(split: &mut SplitRequest) -> Vec<Vec<Value>> {
+        let mut batches = Vec::new();
+        while let Some(body) = split.next_request() {
+            batches.push(body["methodCalls"].as_array().unwrap().clone());
+        }
+        batches
+    }
+
+    #[test]
+    fn split_request() {
+        let mut request = json!({
+            "using": ["urn:ietf:params:jmap:core", "urn:ietf:params:jmap:mail"],
+            "methodCalls": [
+                ["Email/get", {"accountId": "A1", "ids": ["1", "2", "3", "4", "5"]}, "s0"],
+                ["Email/query", {"accountId": "A1"}, "s1"],
+                ["Email/get", {"accountId": "A1", "#ids": {
+                    "resultOf": "s1", "name": "Email/query", "path": "/ids"}}, "s2"],
+                ["Email/set", {"accountId": "A1", "create": {"c1": {}},
+                    "update": {"1": {}}, "destroy": ["2"]}, "s3"],
+                ["Email/set", {"accountId": "A1", "ifInState": "x",
+                    "destroy": ["3", "4", "5"]}, "s4"],
+            ]
+        });
+
+        // Within limits
+        assert!(
+            SplitRequest::new(&mut request.clone(), &capabilities(5, 5, 3))
+                .unwrap()
+                .is_none()
+        );
+
+        let mut split = SplitRequest::new(&mut request, &capabilities(3, 2, 2))
+            .unwrap()
+            .unwrap();
+        let batches = batches(&mut split);
+        let ids = batches
+            .iter()
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|call| call[2].as_str().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                vec!["s0", "s0", "s0"],
+                vec!["s1", "s2", "s3"],
+                vec!["s3", "s4"]
+            ]
+        );
+        assert_eq!(batches[0][2][1]["ids"], json!(["5"]));
+        assert_eq!(
+            batches[1][2][1],
+            json!({"accountId": "A1", "create": {"c1": {}}, "update": {"1": {}}})
+        );
+        assert_eq!(
+            batches[2][0][1],
+            json!({"accountId": "A1", "destroy": ["2"]})
+        );
+
+        for response in [
+            json!({"sessionState": "a", "methodResponses": [
+                ["Email/get", {"state": "1", "list": [{"id": "1"}, {"id": "2"}], "notFound": []}, "s0"],
+                ["Email/get", {"state": "1", "list": [{"id": "3"}], "notFound": ["4"]}, "s0"],
+                ["Email/get", {"state": "1", "list": [], "notFound": ["5"]}, "s0"],
+            ]}),
+            json!({"sessionState": "a", "createdIds": {"c1": "6"}, "methodResponses": [
+                ["Email/query", {"ids": []}, "s1"],
+                ["Email/get", {"state": "1", "list": [], "notFound": []}, "s2"],
+                ["Email/set", {"oldState": "1", "newState": "2", "created": {"c1": {"id": "6"}},
+                    "updated": {"1": null}, "destroyed": null}, "s3"],
+            ]}),
+            json!({"sessionState": "b", "methodResponses": [
+                ["Email/set", {"oldState": "2", "newState": "3", "created": null,
+                    "destroyed": ["2"]}, "s3"],
+                ["error", {"type": "stateMismatch"}, "s4"],
+            ]}),
+        ] {
+            split.add_response(response).unwrap();
+        }
+
+        assert_eq!(
+            split.into_response(),
+            json!({"sessionState": "b", "createdIds": {"c1": "6"}, "methodResponses": [
+                ["Email/get", {"state": "1", "list": [{"id": "1"}, {"id": "2"}, {"id": "3"}],
+                    "notFound": ["4", "5"]}, "s0"],
+                ["Email/query", {"ids": []}, "s1"],
+                ["Email/get", {"state": "1", "list": [], "notFound": []}, "s2"],
+                ["Email/set", {"oldState": "1", "newState": "3", "created": {"c1": {"id": "6"}},
+                    "updated": {"1": null}, "destroyed": ["2"]}, "s3"],
+                ["error", {"type": "stateMismatch"}, "s4"],
+            ]})
+        );
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use reqwest::header::HeaderValue;
+
+use crate::{client::Credentials, Error};
+
+/// Source of the `Authorization` header sent with every request to the JMAP server,
+/// including the WebSocket handshake.
+///
+/// A custom provider can be installed with [ClientBuilder.credential_provider()](crate::client::ClientBuilder::credential_provider),
+/// for example to fetch credentials from a secrets service and rotate them
+/// without rebuilding the [`Client`](crate::client::Client).
+#[maybe_async::maybe_async]
+pub trait CredentialProvider: Send + Sync {
+    /// Returns the `Authorization` header value to use for the next request.
+    async fn authorization(&self) -> crate::Result<HeaderValue>;
+
+    /// Called when the server rejected `rejected` with `401 Unauthorized`.
+    ///
+    /// Returns `true` if new credentials are available and the request should be sent again.
+    /// Concurrent requests may report the same rejected value, implementations should
+    /// return `true` without refreshing again if the credentials already changed.
+    async fn refresh(&self, rejected: &HeaderValue) -> crate::Result<bool> {
+        let _ = rejected;
+        Ok(false)
+    }
+
+    /// Whether [CredentialProvider.refresh()](CredentialProvider::refresh) can ever succeed.
+    /// Requests are only kept around to be replayed when this returns `true`.
+    fn can_refresh(&self) -> bool {
+        false
+    }
+}
+
+#[maybe_async::maybe_async]
+impl CredentialProvider for Credentials {
+    async fn authorization(&self) -> crate::Result<HeaderValue> {
+        HeaderValue::from_str(&match self {
+            Credentials::Basic(s) => format!("Basic {}", s),
+            Credentials::Bearer(s) => format!("Bearer {}", s),
+            Credentials::OAuth(oauth) => format!("Bearer {}", oauth.access_token()),
+        })
+        .map_err(|_| Error::Internal("Invalid credentials.".to_string()))
+    }
+}
@@ -0,0 +1,252 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+};
+
+use crate::Error;
+
+/// DNS SRV record (RFC 2782).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// DNS resolver used to discover the JMAP service of a domain, see
+/// [ClientBuilder.connect_by_email()](crate::client::ClientBuilder::connect_by_email).
+#[maybe_async::maybe_async]
+pub trait DnsResolver: Send + Sync {
+    /// Returns the SRV records published for `name`, or an empty list if there are none.
+    async fn srv_lookup(&self, name: &str) -> crate::Result<Vec<SrvRecord>>;
+}
+
+/// [`DnsResolver`] using the system's DNS configuration.
+#[cfg(feature = "dns")]
+pub struct SystemResolver {
+    resolver: hickory_resolver::TokioResolver,
+}
+
+#[cfg(feature = "dns")]
+impl SystemResolver {
+    pub fn new() -> crate::Result<Self> {
+        Ok(SystemResolver {
+            resolver: hickory_resolver::TokioResolver::builder_tokio()
+                .map_err(|err| Error::Internal(format!("Failed to create DNS resolver: {}", err)))?
+                .build(),
+        })
+    }
+
+    async fn lookup(&self, name: &str) -> crate::Result<Vec<SrvRecord>> {
+        match self.resolver.srv_lookup(name).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .map(|srv| SrvRecord {
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                    port: srv.port(),
+                    target: srv.target().to_utf8(),
+                })
+                .collect()),
+            Err(err) if err.is_no_records_found() || err.is_nx_domain() => Ok(Vec::new()),
+            Err(err) => Err(Error::Internal(format!("DNS lookup failed: {}", err))),
+        }
+    }
+}
+
+#[cfg(all(feature = "dns", feature = "async"))]
+#[async_trait::async_trait]
+impl DnsResolver for SystemResolver {
+    async fn srv_lookup(&self, name: &str) -> crate::Result<Vec<SrvRecord>> {
+        self.lookup(name).await
+    }
+}
+
+#[cfg(all(feature = "dns", feature = "blocking"))]
+impl DnsResolver for SystemResolver {
+    fn srv_lookup(&self, name: &str) -> crate::Result<Vec<SrvRecord>> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| Error::Internal(format!("Failed to start DNS resolver: {}", err)))?
+            .block_on(self.lookup(name))
+    }
+}
+
+#[cfg(feature = "dns")]
+pub(crate) fn default_resolver() -> Option<Arc<dyn DnsResolver>> {
+    SystemResolver::new()
+        .ok()
+        .map(|resolver| Arc::new(resolver) as Arc<dyn DnsResolver>)
+}
+
+#[cfg(not(feature = "dns"))]
+pub(crate) fn default_resolver() -> Option<Arc<dyn DnsResolver>> {
+    None
+}
+
+/// Returns the domain part of an email address.
+pub(crate) fn email_domain(email: &str) -> crate::Result<&str> {
+    email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim_end_matches('.'))
+        .filter(|domain| !domain.is_empty())
+        .ok_or_else(|| Error::Internal(format!("Invalid email address '{}'.", email)))
+}
+
+/// Returns the candidate base URLs of the JMAP service, ordered by SRV priority
+/// and weight, followed by the domain itself (RFC 8620, Section 2.2).
+pub(crate) fn service_urls(domain: &str, records: Vec<SrvRecord>) -> crate::Result<Vec<String>> {
+    service_urls_with(domain, records, || {
+        RandomState::new().build_hasher().finish()
+    })
+}
+
+fn service_urls_with(
+    domain: &str,
+    records: Vec<SrvRecord>,
+    random: impl FnMut() -> u64,
+) -> crate::Result<Vec<String>> {
+    // A single target of "." means that the service is decidedly not available at this domain
+    if let [record] = records.as_slice() {
+        if record.target.trim_end_matches('.').is_empty() {
+            return Err(Error::Internal(format!(
+                "JMAP service is not available for domain '{}'.",
+                domain
+            )));
+        }
+    }
+
+    let mut urls = Vec::with_capacity(records.len() + 1);
+    for record in order_records(records, random) {
+        let target = record.target.trim_end_matches('.');
+        if target.is_empty() {
+            continue;
+        }
+        let url = if record.port == 443 {
+            format!("https://{}", target)
+        } else {
+            format!("https://{}:{}", target, record.port)
+        };
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    let url = format!("https://{}", domain);
+    if !urls.contains(&url) {
+        urls.push(url);
+    }
+    Ok(urls)
+}
+
+/// Orders SRV records by ascending priority, picking the records of the same
+/// priority at random in proportion to their weight (RFC 2782).
+fn order_records(mut records: Vec<SrvRecord>, mut random: impl FnMut() -> u64) -> Vec<SrvRecord> {
+    // Records with a weight of 0 are placed first, so they have a small chance of being picked
+    records.sort_by_key(|record| (record.priority, record.weight != 0));
+
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let count = records
+            .iter()
+            .take_while(|record| record.priority == priority)
+            .count();
+        let mut group = records.drain(..count).collect::<Vec<_>>();
+        while !group.is_empty() {
+            let total = group
+                .iter()
+                .map(|record| u64::from(record.weight))
+                .sum::<u64>();
+            let pick = random() % (total + 1);
+            let mut running_sum = 0;
+            let pos = group
+                .iter()
+                .position(|record| {
+                    running_sum += u64::from(record.weight);
+                    running_sum >= pick
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(pos));
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{email_domain, service_urls, service_urls_with, SrvRecord};
+
+    #[test]
+    fn discover_service_urls() {
+        let record = |priority, weight, port, target: &str| SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.to_string(),
+        };
+
+        assert_eq!(email_domain("john@example.org").unwrap(), "example.org");
+        assert!(email_domain("example.org").is_err());
+        assert!(email_domain("john@").is_err());
+
+        let records = vec![
+            record(20, 0, 443, "backup.example.org."),
+            record(10, 5, 8443, "jmap2.example.org."),
+            record(10, 10, 443, "jmap.example.org."),
+            record(30, 0, 443, "."),
+        ];
+        assert_eq!(
+            service_urls_with("example.org", records.clone(), || 15).unwrap(),
+            [
+                "https://jmap.example.org",
+                "https://jmap2.example.org:8443",
+                "https://backup.example.org",
+                "https://example.org"
+            ]
+        );
+        assert_eq!(
+            service_urls_with("example.org", records, || 5).unwrap(),
+            [
+                "https://jmap2.example.org:8443",
+                "https://jmap.example.org",
+                "https://backup.example.org",
+                "https://example.org"
+            ]
+        );
+        assert_eq!(
+            service_urls("example.org", vec![]).unwrap(),
+            ["https://example.org"]
+        );
+        assert!(service_urls("example.org", vec![record(0, 0, 443, ".")]).is_err());
+
+        // Records with a weight of 0 are only picked first when the random number is 0
+        let records = vec![
+            record(10, 1, 443, "jmap.example.org."),
+            record(10, 0, 443, "jmap2.example.org."),
+        ];
+        for (random, first) in [
+            (0, "https://jmap2.example.org"),
+            (1, "https://jmap.example.org"),
+        ] {
+            assert_eq!(
+                service_urls_with("example.org", records.clone(), || random).unwrap()[0],
+                first
+            );
+        }
+    }
+}
@@ -47,7 +47,7 @@ impl Client {
         W: Into<String>,
     {
         self.email_import_account(
-            self.default_account_id(),
+            &self.default_account_id(),
             raw_message,
             mailbox_ids,
             keywords,
@@ -204,7 +204,7 @@ impl Client {
             query_request.filter(filter);
         }
         if let Some(sort) = sort {
-            query_request.sort(sort.into_iter());
+            query_request.sort(sort);
         }
         request.send_single::<QueryResponse>().await
     }
@@ -432,3 +432,142 @@ impl Request<'_> {
         self.send_single().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core,
+        email::{query::Comparator, query::Filter, Property},
+        mailbox::Role,
+        testing::MockServer,
+        DataType,
+    };
+
+    const MESSAGE: &str = concat!(
+        "From: Jane Doe <jane@example.org>\r\n",
+        "To: john@example.org\r\n",
+        "Subject: Lunch\r\n",
+        "\r\n",
+        "Shall we meet at noon?\r\n"
+    );
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn email_helpers() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let trash_id = client
+            .mailbox_create("Trash", None::<String>, Role::Trash)
+            .await
+            .unwrap()
+            .take_id();
+
+        let email = client
+            .email_import(
+                MESSAGE.as_bytes().to_vec(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                Some(1_000_000),
+            )
+            .await
+            .unwrap();
+        let email_id = email.id().unwrap().to_string();
+        let thread_id = email.thread_id().unwrap().to_string();
+        let reply_id = client
+            .email_import(
+                MESSAGE.replace("Lunch", "Re: Lunch").into_bytes(),
+                [&inbox_id],
+                Some(["$seen"]),
+                Some(2_000_000),
+            )
+            .await
+            .unwrap()
+            .take_id();
+        let state = server.state(DataType::Email).unwrap();
+
+        // Both messages belong to the same thread
+        let thread = client.thread_get(&thread_id).await.unwrap().unwrap();
+        assert_eq!(thread.email_ids(), [email_id.as_str(), reply_id.as_str()]);
+
+        let email = client
+            .email_get(&email_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(email.subject(), Some("Lunch"));
+        assert_eq!(email.from().unwrap()[0].email(), "jane@example.org");
+        assert_eq!(email.received_at(), Some(1_000_000));
+        assert_eq!(email.preview(), Some("Shall we meet at noon?"));
+        let blob = client.download(email.blob_id().unwrap()).await.unwrap();
+        assert_eq!(blob, MESSAGE.as_bytes());
+        let parsed = client
+            .email_parse(
+                email.blob_id().unwrap(),
+                None::<Vec<_>>,
+                None::<Vec<_>>,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(parsed.subject(), Some("Lunch"));
+
+        client
+            .email_set_keyword(&email_id, "$flagged", true)
+            .await
+            .unwrap();
+        client
+            .email_set_mailboxes(&reply_id, [&trash_id])
+            .await
+            .unwrap();
+        let email = client
+            .email_get(&email_id, [Property::Keywords].into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(email.keywords(), ["$flagged"]);
+        assert!(email.subject().is_none());
+        let result = client
+            .email_set_mailboxes(&email_id, Vec::<String>::new())
+            .await;
+        assert!(result.is_err());
+
+        let inbox = client
+            .mailbox_get(&inbox_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(inbox.total_emails(), 1);
+        assert_eq!(inbox.unread_emails(), 1);
+
+        let query = client
+            .email_query(
+                core::query::Filter::or([
+                    Filter::in_mailbox(&trash_id),
+                    Filter::has_keyword("$flagged"),
+                ])
+                .into(),
+                [Comparator::received_at().descending()].into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(query.ids(), [reply_id.as_str(), email_id.as_str()]);
+
+        client.email_destroy(&reply_id).await.unwrap();
+        let changes = client.email_changes(&state, None).await.unwrap();
+        assert_eq!(changes.updated(), [email_id.as_str()]);
+        assert_eq!(changes.destroyed(), [reply_id.as_str()]);
+        let changes = client.email_changes(&state, Some(1)).await.unwrap();
+        assert!(changes.has_more_changes());
+        assert_eq!(changes.total_changes(), 1);
+
+        // Destroying the mailbox removes the emails that are only in that mailbox
+        client.mailbox_destroy(&inbox_id, true).await.unwrap();
+        assert!(server.object(DataType::Email, &email_id).is_none());
+        let thread = client.thread_get(&thread_id).await.unwrap();
+        assert!(thread.is_none());
+    }
+}
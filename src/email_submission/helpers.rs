@@ -110,7 +110,7 @@ impl Client {
         let mut request = self.build();
         let get_request = request.get_email_submission().ids([id]);
         if let Some(properties) = properties {
-            get_request.properties(properties.into_iter());
+            get_request.properties(properties);
         }
         request
             .send_single::<EmailSubmissionGetResponse>()
@@ -130,7 +130,7 @@ impl Client {
             query_request.filter(filter);
         }
         if let Some(sort) = sort {
-            query_request.sort(sort.into_iter());
+            query_request.sort(sort);
         }
         request.send_single::<QueryResponse>().await
     }
@@ -234,3 +234,101 @@ impl Request<'_> {
         self.send_single().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        email_submission::{query::Filter, UndoStatus},
+        mailbox::Role,
+        testing::MockServer,
+        DataType,
+    };
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn email_submission_helpers() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let mailbox_id = client
+            .mailbox_create("Sent", None::<String>, Role::Sent)
+            .await
+            .unwrap()
+            .take_id();
+        let email_id = client
+            .email_import(
+                b"From: john@example.org\r\nTo: jane@example.org, bill@example.org\r\n\r\nHi!"
+                    .to_vec(),
+                [&mailbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+        let identity_id = client
+            .identity_create("John Doe", "john@example.org")
+            .await
+            .unwrap()
+            .take_id();
+        let state = server.state(DataType::EmailSubmission).unwrap();
+
+        // The envelope is derived from the identity and the message recipients
+        let submission_id = client
+            .email_submission_create(&email_id, &identity_id)
+            .await
+            .unwrap()
+            .take_id();
+        let submission = client
+            .email_submission_get(&submission_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(submission.email_id(), Some(email_id.as_str()));
+        assert_eq!(submission.mail_from().unwrap().email(), "john@example.org");
+        assert_eq!(submission.rcpt_to().unwrap().len(), 2);
+        assert_eq!(submission.undo_status(), Some(&UndoStatus::Pending));
+
+        let envelope_id = client
+            .email_submission_create_envelope(
+                &email_id,
+                &identity_id,
+                "john@example.org",
+                ["jane@example.org"],
+            )
+            .await
+            .unwrap()
+            .take_id();
+        let envelope = client
+            .email_submission_get(&envelope_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(envelope.rcpt_to().unwrap()[0].email(), "jane@example.org");
+        let result = client.email_submission_create(&email_id, "unknown").await;
+        assert!(result.is_err());
+
+        client
+            .email_submission_change_status(&submission_id, UndoStatus::Canceled)
+            .await
+            .unwrap();
+        let result = client
+            .email_submission_change_status(&submission_id, UndoStatus::Pending)
+            .await;
+        assert!(result.is_err());
+        let query = client
+            .email_submission_query(
+                Filter::undo_status(UndoStatus::Pending).into(),
+                None::<Vec<_>>,
+            )
+            .await
+            .unwrap();
+        assert_eq!(query.ids(), [envelope_id.as_str()]);
+
+        client
+            .email_submission_destroy(&submission_id)
+            .await
+            .unwrap();
+        let changes = client.email_submission_changes(&state, 10).await.unwrap();
+        assert_eq!(changes.created(), [envelope_id.as_str()]);
+        assert!(changes.destroyed().is_empty());
+    }
+}
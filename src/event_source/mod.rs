@@ -37,6 +37,9 @@ impl URLParser for URLParameter {
 pub enum PushNotification {
     StateChange(Changes),
     CalendarAlert(CalendarAlert),
+    /// The connection was re-established by [Client.event_source_reconnect()](crate::client::Client::event_source_reconnect),
+    /// changes that happened while disconnected may have been missed.
+    Reconnected,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -9,6 +9,8 @@
  * except according to those terms.
  */
 
+use std::time::Duration;
+
 use super::Changes;
 use crate::{
     event_source::{CalendarAlert, PushNotification},
@@ -17,19 +19,14 @@ use crate::{
 
 const MAX_EVENT_SIZE: usize = 1024 * 1024;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 pub enum EventType {
     Ping,
+    #[default]
     State,
     CalendarAlert,
 }
 
-impl Default for EventType {
-    fn default() -> Self {
-        Self::State
-    }
-}
-
 #[derive(Default, Debug)]
 pub struct Event {
     pub event: EventType,
@@ -37,20 +34,15 @@ pub struct Event {
     pub data: Vec<u8>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default)]
 enum EventParserState {
+    #[default]
     Init,
     Comment,
     Field,
     Value,
 }
 
-impl Default for EventParserState {
-    fn default() -> Self {
-        Self::Init
-    }
-}
-
 #[derive(Default, Debug)]
 pub struct EventParser {
     state: EventParserState,
@@ -59,6 +51,14 @@ pub struct EventParser {
     bytes: Option<Vec<u8>>,
     pos: usize,
     result: Event,
+    retry: Option<u64>,
+    ping_interval: Option<u64>,
+    has_activity: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct Ping {
+    interval: u64,
 }
 
 impl EventParser {
@@ -70,10 +70,33 @@ impl EventParser {
         self.bytes.is_none()
     }
 
+    /// Returns the reconnection time last set by the server using the `retry` field.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry.map(Duration::from_millis)
+    }
+
+    /// Returns the ping interval reported by the server in the last ping event.
+    pub fn ping_interval(&self) -> Option<Duration> {
+        self.ping_interval.map(Duration::from_secs)
+    }
+
+    /// Returns whether any event was received since the last call.
+    pub fn take_activity(&mut self) -> bool {
+        std::mem::take(&mut self.has_activity)
+    }
+
     pub fn filter_notification(&mut self) -> Option<crate::Result<PushNotification>> {
         #[allow(clippy::never_loop)]
         #[allow(clippy::while_let_on_iterator)]
         while let Some(event) = self.next() {
+            if let Ok(event) = &event {
+                self.has_activity = true;
+                if event.event == EventType::Ping {
+                    if let Ok(ping) = serde_json::from_slice::<Ping>(&event.data) {
+                        self.ping_interval = Some(ping.interval).filter(|interval| *interval > 0);
+                    }
+                }
+            }
             match event {
                 Ok(Event {
                     event: EventType::State,
@@ -196,6 +219,15 @@ impl Iterator for EventParser {
                             b"data" => {
                                 self.result.data.extend_from_slice(&self.value);
                             }
+                            b"retry" => {
+                                if let Some(retry) = std::str::from_utf8(&self.value)
+                                    .ok()
+                                    .filter(|value| value.bytes().all(|b| b.is_ascii_digit()))
+                                    .and_then(|value| value.parse().ok())
+                                {
+                                    self.retry = Some(retry);
+                                }
+                            }
                             b"event" => match &self.value[..] {
                                 b"calendarAlert" => {
                                     self.result.event = EventType::CalendarAlert;
@@ -277,6 +309,7 @@ mod tests {
             Vec::from("id\n\n"),
             Vec::from("data:  third event\n\n"),
             Vec::from("data:hello\n\ndata: world\n\n"),
+            Vec::from("retry: 1500\nretry: 15s\n"),
         ] {
             parser.push_bytes(frame);
 
@@ -286,6 +319,7 @@ mod tests {
             }
         }
 
+        assert_eq!(parser.retry(), Some(std::time::Duration::from_millis(1500)));
         assert_eq!(
             results,
             vec![
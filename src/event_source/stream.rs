@@ -13,10 +13,14 @@ use crate::{
     client::Client,
     core::session::URLPart,
     event_source::{parser::EventParser, PushNotification},
+    retry::RetryPolicy,
+    transport::{ByteStream, HttpRequest},
     DataType,
 };
 use futures_util::{Stream, StreamExt};
 use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use std::time::Duration;
+use tokio::time::Instant;
 
 impl Client {
     pub async fn event_source(
@@ -26,19 +30,139 @@ impl Client {
         ping: Option<u32>,
         last_event_id: Option<&str>,
     ) -> crate::Result<impl Stream<Item = crate::Result<PushNotification>> + Unpin> {
+        let types = Option::take(&mut types).map(|types| types.into_iter().collect::<Vec<_>>());
+        let mut stream = self
+            .event_source_connect(types.as_deref(), close_after_state, ping, last_event_id)
+            .await?;
+        let mut parser = EventParser::default();
+        let ping_timeout = self.ping_timeout();
+        let mut deadline = ping_deadline(&parser, ping, ping_timeout);
+
+        Ok(Box::pin(async_stream::stream! {
+            loop {
+                if let Some(notification) = parser.filter_notification() {
+                    yield notification;
+                    continue;
+                }
+                if parser.take_activity() {
+                    deadline = ping_deadline(&parser, ping, ping_timeout);
+                }
+                if let Some(result) = next_bytes(&mut stream, deadline).await {
+                    match result {
+                        Ok(bytes) => {
+                            parser.push_bytes(bytes.to_vec());
+                            continue;
+                        }
+                        Err(err) => {
+                            yield Err(err);
+                            break;
+                        }
+                    }
+                } else {
+                    break;
+                }
+            }
+        }))
+    }
+
+    /// Opens an EventSource connection that is re-established whenever it ends or fails.
+    ///
+    /// Reconnections resume from the id of the last state change received using the
+    /// `Last-Event-ID` header, waiting at least the reconnection time sent by the server
+    /// in the `retry` field. The `retry_policy` controls the backoff between consecutive
+    /// reconnection attempts and how many are made before the last error is returned.
+    /// Connections that stop receiving pings for longer than the [ping timeout](crate::client::ClientBuilder::ping_timeout)
+    /// are also re-established.
+    ///
+    /// A [PushNotification::Reconnected] is returned after each successful reconnection,
+    /// as changes that happened while disconnected may not be reported by the server.
+    pub async fn event_source_reconnect(
+        &self,
+        types: Option<impl IntoIterator<Item = DataType>>,
+        ping: Option<u32>,
+        last_event_id: Option<&str>,
+        retry_policy: RetryPolicy,
+    ) -> crate::Result<impl Stream<Item = crate::Result<PushNotification>> + Unpin + '_> {
+        let types = types.map(|types| types.into_iter().collect::<Vec<_>>());
+        let mut last_event_id = last_event_id.map(|id| id.to_string());
+        let mut stream = self
+            .event_source_connect(types.as_deref(), false, ping, last_event_id.as_deref())
+            .await?;
+        let mut parser = EventParser::default();
+        let ping_timeout = self.ping_timeout();
+        let mut deadline = ping_deadline(&parser, ping, ping_timeout);
+        let mut retry = None;
+
+        Ok(Box::pin(async_stream::stream! {
+            loop {
+                if let Some(notification) = parser.filter_notification() {
+                    if let Ok(PushNotification::StateChange(changes)) = &notification {
+                        if let Some(id) = changes.id() {
+                            last_event_id = Some(id.to_string());
+                        }
+                    }
+                    yield notification;
+                    continue;
+                }
+                if parser.take_activity() {
+                    deadline = ping_deadline(&parser, ping, ping_timeout);
+                }
+                let mut last_error = match next_bytes(&mut stream, deadline).await {
+                    Some(Ok(bytes)) => {
+                        parser.push_bytes(bytes.to_vec());
+                        continue;
+                    }
+                    Some(Err(err)) => err,
+                    None => crate::Error::Internal("EventSource connection closed.".to_string()),
+                };
+
+                retry = parser.retry().or(retry);
+                let mut attempt = 0;
+                loop {
+                    attempt += 1;
+                    let Some(delay) = retry_policy.reconnect_delay(attempt) else {
+                        yield Err(last_error);
+                        return;
+                    };
+                    tokio::time::sleep(retry.map_or(delay, |retry| retry.max(delay))).await;
+                    match self
+                        .event_source_connect(types.as_deref(), false, ping, last_event_id.as_deref())
+                        .await
+                    {
+                        Ok(new_stream) => {
+                            stream = new_stream;
+                            parser = EventParser::default();
+                            deadline = ping_deadline(&parser, ping, ping_timeout);
+                            yield Ok(PushNotification::Reconnected);
+                            break;
+                        }
+                        Err(err) => last_error = err,
+                    }
+                }
+            }
+        }))
+    }
+
+    async fn event_source_connect(
+        &self,
+        types: Option<&[DataType]>,
+        close_after_state: bool,
+        ping: Option<u32>,
+        last_event_id: Option<&str>,
+    ) -> crate::Result<ByteStream> {
         let mut event_source_url = String::with_capacity(self.session().event_source_url().len());
 
-        for part in self.event_source_url() {
+        for part in self.event_source_url().iter() {
             match part {
                 URLPart::Value(value) => {
                     event_source_url.push_str(value);
                 }
                 URLPart::Parameter(param) => match param {
                     super::URLParameter::Types => {
-                        if let Some(types) = Option::take(&mut types) {
+                        if let Some(types) = types {
                             event_source_url.push_str(
                                 &types
-                                    .into_iter()
+                                    .iter()
                                     .map(|state| state.to_string())
                                     .collect::<Vec<_>>()
                                     .join(","),
@@ -65,49 +189,199 @@ impl Client {
         let mut headers = self.headers().clone();
         headers.remove(CONTENT_TYPE);
         headers.insert(ACCEPT, HeaderValue::from_static("text/event-stream"));
-        if let Some(last_event_id) = last_event_id {
-            headers.insert(
-                "Last-Event-ID",
-                HeaderValue::from_str(last_event_id).unwrap(),
-            );
+        if let Some(last_event_id) = last_event_id.and_then(|id| HeaderValue::from_str(id).ok()) {
+            headers.insert("Last-Event-ID", last_event_id);
         }
 
-        let mut stream = Client::handle_error(
-            reqwest::Client::builder()
-                .connect_timeout(self.timeout())
-                .danger_accept_invalid_certs(self.accept_invalid_certs)
-                .redirect(self.redirect_policy())
-                .default_headers(headers)
-                .build()?
-                .get(event_source_url)
-                .send()
-                .await?,
-        )
-        .await?
-        .bytes_stream();
-        let mut parser = EventParser::default();
+        let response = self
+            .send_http_stream(
+                HttpRequest::get(event_source_url)
+                    .with_headers(headers)
+                    .with_timeout(self.timeout()),
+            )
+            .await?;
+        if response.status().is_success() {
+            Ok(response.body)
+        } else {
+            Err(Client::response_error(&response.collect().await?))
+        }
+    }
+}
 
-        Ok(Box::pin(async_stream::stream! {
-            loop {
-                if let Some(notification) = parser.filter_notification() {
-                    yield notification;
-                    continue;
-                }
-                if let Some(result) = stream.next().await {
-                    match result {
-                        Ok(bytes) => {
-                            parser.push_bytes(bytes.to_vec());
-                            continue;
-                        }
-                        Err(err) => {
-                            yield Err(err.into());
-                            break;
-                        }
-                    }
+/// Returns the time by which the next event has to be received when pings were requested.
+fn ping_deadline(parser: &EventParser, ping: Option<u32>, ping_timeout: u32) -> Option<Instant> {
+    let ping = ping.filter(|ping| *ping > 0 && ping_timeout > 0)?;
+    let interval = parser
+        .ping_interval()
+        .unwrap_or_else(|| Duration::from_secs(ping as u64));
+    Some(Instant::now() + interval * ping_timeout)
+}
+
+async fn next_bytes(
+    stream: &mut ByteStream,
+    deadline: Option<Instant>,
+) -> Option<crate::Result<bytes::Bytes>> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, stream.next())
+            .await
+            .unwrap_or_else(|_| {
+                Some(Err(crate::Error::Internal(
+                    "No ping received from the EventSource server.".to_string(),
+                )))
+            }),
+        None => stream.next().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use futures_util::StreamExt;
+    use reqwest::{header::HeaderMap, StatusCode};
+
+    use crate::{
+        event_source::PushNotification,
+        retry::RetryPolicy,
+        testing::MockServer,
+        transport::{ByteStream, HttpRequest, HttpResponse, Transport},
+    };
+
+    #[derive(Clone)]
+    struct ScriptedTransport {
+        server: MockServer,
+        bodies: Arc<parking_lot::Mutex<Vec<&'static str>>>,
+        keep_open: bool,
+        last_event_ids: Arc<parking_lot::Mutex<Vec<Option<String>>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for ScriptedTransport {
+        async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+            self.server.send(request).await
+        }
+
+        async fn send_stream(
+            &self,
+            request: HttpRequest,
+        ) -> crate::Result<HttpResponse<ByteStream>> {
+            self.last_event_ids.lock().push(
+                request
+                    .headers
+                    .get("Last-Event-ID")
+                    .map(|id| id.to_str().unwrap().to_string()),
+            );
+            let (status, body) = match self.bodies.lock().pop() {
+                Some(body) => (StatusCode::OK, body),
+                None => (StatusCode::SERVICE_UNAVAILABLE, ""),
+            };
+            let stream = futures_util::stream::once(async move {
+                Ok(bytes::Bytes::from_static(body.as_bytes()))
+            });
+            Ok(HttpResponse::new(
+                status,
+                HeaderMap::new(),
+                if self.keep_open {
+                    Box::pin(stream.chain(futures_util::stream::pending()))
                 } else {
-                    break;
+                    Box::pin(stream)
+                },
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn event_source_reconnect() {
+        let server = MockServer::new();
+        let transport = ScriptedTransport {
+            server: server.clone(),
+            bodies: Arc::new(parking_lot::Mutex::new(vec![
+                concat!(
+                    "event: state\nid: 2\n",
+                    "data: {\"@type\":\"StateChange\",\"changed\":{\"a\":{\"Mailbox\":\"2\"}}}\n\n"
+                ),
+                concat!(
+                    "retry: 10\n",
+                    "event: state\nid: 1\n",
+                    "data: {\"@type\":\"StateChange\",\"changed\":{\"a\":{\"Mailbox\":\"1\"}}}\n\n"
+                ),
+            ])),
+            keep_open: false,
+            last_event_ids: Default::default(),
+        };
+        let client = crate::client::Client::new()
+            .credentials("secret")
+            .transport(transport.clone())
+            .connect(server.url())
+            .await
+            .unwrap();
+        let mut stream = client
+            .event_source_reconnect(
+                None::<Vec<_>>,
+                None,
+                None,
+                RetryPolicy::new()
+                    .max_attempts(2)
+                    .backoff(Duration::from_millis(1), Duration::from_millis(5)),
+            )
+            .await
+            .unwrap();
+
+        for expected_id in ["1", "2"] {
+            match stream.next().await.unwrap().unwrap() {
+                PushNotification::StateChange(changes) => {
+                    assert_eq!(changes.id(), Some(expected_id))
                 }
+                notification => panic!("Unexpected notification: {notification:?}"),
             }
-        }))
+            if expected_id == "1" {
+                assert_eq!(
+                    stream.next().await.unwrap().unwrap(),
+                    PushNotification::Reconnected
+                );
+            }
+        }
+
+        // Gives up after the configured number of failed attempts
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+        assert_eq!(
+            *transport.last_event_ids.lock(),
+            [
+                None,
+                Some("1".to_string()),
+                Some("2".to_string()),
+                Some("2".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn event_source_ping_timeout() {
+        let server = MockServer::new();
+        let client = crate::client::Client::new()
+            .credentials("secret")
+            .ping_timeout(2)
+            .transport(ScriptedTransport {
+                server: server.clone(),
+                bodies: Arc::new(parking_lot::Mutex::new(vec![
+                    "event: ping\ndata: {\"interval\":5}\n\n",
+                ])),
+                keep_open: true,
+                last_event_ids: Default::default(),
+            })
+            .connect(server.url())
+            .await
+            .unwrap();
+        let mut stream = client
+            .event_source(None::<Vec<_>>, false, Some(1), None)
+            .await
+            .unwrap();
+
+        // The interval reported by the server replaces the requested one
+        let started = tokio::time::Instant::now();
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(started.elapsed() >= Duration::from_secs(10));
+        assert!(stream.next().await.is_none());
     }
 }
@@ -63,7 +63,7 @@ impl Client {
         let mut request = self.build();
         let get_request = request.get_identity().ids([id]);
         if let Some(properties) = properties {
-            get_request.properties(properties.into_iter());
+            get_request.properties(properties);
         }
         request
             .send_single::<IdentityGetResponse>()
@@ -125,3 +125,38 @@ impl Request<'_> {
         self.send_single().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::MockServer, DataType};
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn identity_helpers() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let state = server.state(DataType::Identity).unwrap();
+
+        let identity_id = client
+            .identity_create("John Doe", "john@example.org")
+            .await
+            .unwrap()
+            .take_id();
+        let identity = client
+            .identity_get(&identity_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.name(), Some("John Doe"));
+        assert_eq!(identity.email(), Some("john@example.org"));
+        assert!(identity.may_delete());
+
+        let changes = client.identity_changes(&state, 10).await.unwrap();
+        assert_eq!(changes.created(), [identity_id.as_str()]);
+
+        client.identity_destroy(&identity_id).await.unwrap();
+        let result = client.identity_destroy(&identity_id).await;
+        assert!(result.is_err());
+        let changes = client.identity_changes(&state, 10).await.unwrap();
+        assert_eq!(changes.total_changes(), 0);
+    }
+}
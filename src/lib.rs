@@ -184,16 +184,27 @@
 pub mod blob;
 pub mod client;
 pub mod core;
+pub mod credentials;
+pub mod discovery;
 pub mod email;
 pub mod email_submission;
 #[cfg(feature = "async")]
 pub mod event_source;
 pub mod identity;
+pub(crate) mod limiter;
 pub mod mailbox;
+pub mod mdn;
+pub mod oauth;
 pub mod principal;
 pub mod push_subscription;
+pub mod quota;
+pub mod retry;
 pub mod sieve;
+pub mod sync;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod thread;
+pub mod transport;
 pub mod vacation_response;
 
 use crate::core::error::MethodError;
@@ -228,6 +239,12 @@ pub enum URI {
     Principals,
     #[serde(rename = "urn:ietf:params:jmap:principals:owner")]
     PrincipalsOwner,
+    #[serde(rename = "urn:ietf:params:jmap:blob")]
+    Blob,
+    #[serde(rename = "urn:ietf:params:jmap:quota")]
+    Quota,
+    #[serde(rename = "urn:ietf:params:jmap:mdn")]
+    Mdn,
 }
 
 impl AsRef<str> for URI {
@@ -243,6 +260,9 @@ impl AsRef<str> for URI {
             URI::Sieve => "urn:ietf:params:jmap:sieve",
             URI::Principals => "urn:ietf:params:jmap:principals",
             URI::PrincipalsOwner => "urn:ietf:params:jmap:principals:owner",
+            URI::Blob => "urn:ietf:params:jmap:blob",
+            URI::Quota => "urn:ietf:params:jmap:quota",
+            URI::Mdn => "urn:ietf:params:jmap:mdn",
         }
     }
 }
@@ -253,6 +273,12 @@ pub enum Method {
     Echo,
     #[serde(rename = "Blob/copy")]
     CopyBlob,
+    #[serde(rename = "Blob/upload")]
+    UploadBlob,
+    #[serde(rename = "Blob/get")]
+    GetBlob,
+    #[serde(rename = "Blob/lookup")]
+    LookupBlob,
     #[serde(rename = "PushSubscription/get")]
     GetPushSubscription,
     #[serde(rename = "PushSubscription/set")]
@@ -327,10 +353,66 @@ pub enum Method {
     QueryChangesPrincipal,
     #[serde(rename = "Principal/set")]
     SetPrincipal,
+    #[serde(rename = "Quota/get")]
+    GetQuota,
+    #[serde(rename = "Quota/changes")]
+    ChangesQuota,
+    #[serde(rename = "Quota/query")]
+    QueryQuota,
+    #[serde(rename = "Quota/queryChanges")]
+    QueryChangesQuota,
+    #[serde(rename = "MDN/send")]
+    SendMdn,
+    #[serde(rename = "MDN/parse")]
+    ParseMdn,
     #[serde(rename = "error")]
     Error,
 }
 
+impl Method {
+    /// Returns `true` for methods that do not modify any server state and can be safely retried.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Method::Echo
+                | Method::GetBlob
+                | Method::LookupBlob
+                | Method::GetPushSubscription
+                | Method::GetMailbox
+                | Method::ChangesMailbox
+                | Method::QueryMailbox
+                | Method::QueryChangesMailbox
+                | Method::GetThread
+                | Method::ChangesThread
+                | Method::GetEmail
+                | Method::ChangesEmail
+                | Method::QueryEmail
+                | Method::QueryChangesEmail
+                | Method::ParseEmail
+                | Method::GetSearchSnippet
+                | Method::GetIdentity
+                | Method::ChangesIdentity
+                | Method::GetEmailSubmission
+                | Method::ChangesEmailSubmission
+                | Method::QueryEmailSubmission
+                | Method::QueryChangesEmailSubmission
+                | Method::GetVacationResponse
+                | Method::GetSieveScript
+                | Method::QuerySieveScript
+                | Method::ValidateSieveScript
+                | Method::GetPrincipal
+                | Method::ChangesPrincipal
+                | Method::QueryPrincipal
+                | Method::QueryChangesPrincipal
+                | Method::GetQuota
+                | Method::ChangesQuota
+                | Method::QueryQuota
+                | Method::QueryChangesQuota
+                | Method::ParseMdn
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Clone)]
 pub enum DataType {
     #[serde(rename = "Email")]
@@ -381,7 +463,7 @@ pub enum DataType {
     CalendarAlert = 22,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "@type")]
 pub enum PushObject {
     StateChange {
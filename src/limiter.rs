@@ -0,0 +1,173 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+/// Limits the number of concurrent operations, such as API requests or uploads,
+/// to the maximum advertised by the server. A limit of zero means unlimited.
+///
+/// Resizing is done by replacing the limiter, permits acquired from the previous
+/// one are released as usual.
+pub(crate) struct Limiter {
+    max_concurrent: usize,
+    #[cfg(feature = "async")]
+    semaphore: tokio::sync::Semaphore,
+    #[cfg(feature = "blocking")]
+    in_use: parking_lot::Mutex<usize>,
+    #[cfg(feature = "blocking")]
+    released: parking_lot::Condvar,
+}
+
+#[cfg(feature = "async")]
+pub(crate) type Permit<'x> = tokio::sync::SemaphorePermit<'x>;
+
+#[cfg(feature = "blocking")]
+pub(crate) struct Permit<'x> {
+    limiter: &'x Limiter,
+}
+
+impl Limiter {
+    #[cfg(feature = "async")]
+    pub(crate) fn new(max_concurrent: usize) -> Self {
+        Limiter {
+            max_concurrent,
+            semaphore: tokio::sync::Semaphore::new(if max_concurrent > 0 {
+                max_concurrent.min(tokio::sync::Semaphore::MAX_PERMITS)
+            } else {
+                tokio::sync::Semaphore::MAX_PERMITS
+            }),
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn new(max_concurrent: usize) -> Self {
+        Limiter {
+            max_concurrent,
+            in_use: parking_lot::Mutex::new(0),
+            released: parking_lot::Condvar::new(),
+        }
+    }
+
+    pub(crate) fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Waits until a permit is available.
+    #[cfg(feature = "async")]
+    pub(crate) async fn acquire(&self) -> Permit<'_> {
+        self.semaphore
+            .acquire()
+            .await
+            .expect("Limiter semaphore is never closed")
+    }
+
+    /// Blocks until a permit is available.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn acquire(&self) -> Permit<'_> {
+        let mut in_use = self.in_use.lock();
+        while self.max_concurrent > 0 && *in_use >= self.max_concurrent {
+            self.released.wait(&mut in_use);
+        }
+        *in_use += 1;
+        Permit { limiter: self }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.limiter.in_use.lock() -= 1;
+        self.limiter.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::Limiter;
+
+    struct Tracker {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl Tracker {
+        fn start(&self) {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+        }
+
+        fn stop(&self) {
+            self.running.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn limit_concurrency() {
+        let limiter = Arc::new(Limiter::new(2));
+        let tracker = Arc::new(Tracker {
+            running: AtomicUsize::new(0),
+            max_running: AtomicUsize::new(0),
+        });
+
+        let tasks = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let tracker = tracker.clone();
+                tokio::spawn(async move {
+                    let _permit = limiter.acquire().await;
+                    tracker.start();
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    tracker.stop();
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(tracker.max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn limit_concurrency() {
+        let limiter = Arc::new(Limiter::new(2));
+        let tracker = Arc::new(Tracker {
+            running: AtomicUsize::new(0),
+            max_running: AtomicUsize::new(0),
+        });
+
+        let threads = (0..6)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let tracker = tracker.clone();
+                std::thread::spawn(move || {
+                    let _permit = limiter.acquire();
+                    tracker.start();
+                    std::thread::sleep(Duration::from_millis(10));
+                    tracker.stop();
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(tracker.max_running.load(Ordering::SeqCst), 2);
+    }
+}
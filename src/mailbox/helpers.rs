@@ -160,7 +160,7 @@ impl Client {
         let mut request = self.build();
         let get_request = request.get_mailbox().ids([id]);
         if let Some(properties) = properties {
-            get_request.properties(properties.into_iter());
+            get_request.properties(properties);
         }
         request
             .send_single::<MailboxGetResponse>()
@@ -180,7 +180,7 @@ impl Client {
             query_request.filter(filter);
         }
         if let Some(sort) = sort {
-            query_request.sort(sort.into_iter());
+            query_request.sort(sort);
         }
         request.send_single::<QueryResponse>().await
     }
@@ -271,3 +271,80 @@ impl Request<'_> {
         self.send_single().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mailbox::{query::Comparator, query::Filter, Role},
+        testing::MockServer,
+        DataType,
+    };
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn mailbox_helpers() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let child_id = client
+            .mailbox_create("Archive", Some(&inbox_id), Role::Archive)
+            .await
+            .unwrap()
+            .take_id();
+        let state = server.state(DataType::Mailbox).unwrap();
+        client.mailbox_rename(&child_id, "Old Mail").await.unwrap();
+        client
+            .mailbox_update_sort_order(&child_id, 3)
+            .await
+            .unwrap();
+        client.mailbox_subscribe(&child_id, true).await.unwrap();
+
+        let child = client
+            .mailbox_get(&child_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(child.name(), Some("Old Mail"));
+        assert_eq!(child.parent_id(), Some(inbox_id.as_str()));
+        assert_eq!(child.role(), Role::Archive);
+        assert_eq!(child.sort_order(), 3);
+        assert!(child.is_subscribed());
+        assert_eq!(child.total_emails(), 0);
+
+        // A mailbox can not become its own ancestor or be destroyed while it has children
+        let result = client.mailbox_move(&inbox_id, Some(&child_id)).await;
+        assert!(result.is_err());
+        let result = client.mailbox_destroy(&inbox_id, false).await;
+        assert!(result.is_err());
+
+        let query = client
+            .mailbox_query(
+                Filter::has_any_role(true).into(),
+                [Comparator::name()].into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(query.ids(), [inbox_id.as_str(), child_id.as_str()]);
+        let query = client
+            .mailbox_query(Filter::parent_id(None::<String>).into(), None::<Vec<_>>)
+            .await
+            .unwrap();
+        assert_eq!(query.ids(), [inbox_id.as_str()]);
+
+        client.mailbox_destroy(&child_id, false).await.unwrap();
+        let changes = client.mailbox_changes(&state, 10).await.unwrap();
+        assert!(changes.created().is_empty());
+        assert!(changes.updated().is_empty());
+        assert_eq!(changes.destroyed(), [child_id.as_str()]);
+        assert_eq!(
+            changes.new_state(),
+            server.state(DataType::Mailbox).unwrap()
+        );
+        let result = client.mailbox_changes("unknown", 10).await;
+        assert!(result.is_err());
+    }
+}
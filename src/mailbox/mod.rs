@@ -261,7 +261,7 @@ impl<'de> Deserialize<'de> for Role {
     where
         D: serde::Deserializer<'de>,
     {
-        match <&str>::deserialize(deserializer)?
+        match <std::borrow::Cow<str>>::deserialize(deserializer)?
             .to_ascii_lowercase()
             .as_str()
         {
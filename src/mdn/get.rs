@@ -0,0 +1,67 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::Get;
+
+use super::{Disposition, MDN};
+
+impl MDN<Get> {
+    pub fn for_email_id(&self) -> Option<&str> {
+        self.for_email_id.as_deref()
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    pub fn text_body(&self) -> Option<&str> {
+        self.text_body.as_deref()
+    }
+
+    pub fn include_original_message(&self) -> bool {
+        self.include_original_message.unwrap_or(false)
+    }
+
+    pub fn reporting_ua(&self) -> Option<&str> {
+        self.reporting_ua.as_deref()
+    }
+
+    pub fn disposition(&self) -> Option<&Disposition> {
+        self.disposition.as_ref()
+    }
+
+    pub fn mdn_gateway(&self) -> Option<&str> {
+        self.mdn_gateway.as_deref()
+    }
+
+    pub fn original_recipient(&self) -> Option<&str> {
+        self.original_recipient.as_deref()
+    }
+
+    pub fn final_recipient(&self) -> Option<&str> {
+        self.final_recipient.as_deref()
+    }
+
+    pub fn original_message_id(&self) -> Option<&str> {
+        self.original_message_id.as_deref()
+    }
+
+    pub fn error(&self) -> Option<&[String]> {
+        self.error.as_deref()
+    }
+
+    pub fn extension_field(&self, name: &str) -> Option<&str> {
+        self.extension_fields
+            .as_ref()
+            .and_then(|fields| fields.get(name))
+            .map(|value| value.as_str())
+    }
+}
@@ -0,0 +1,213 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    client::Client,
+    core::request::{Arguments, Request},
+    Method, URI,
+};
+
+use super::{
+    parse::{MDNParseRequest, MDNParseResponse},
+    send::{MDNSendRequest, MDNSendResponse},
+    Disposition, MDN,
+};
+
+impl Client {
+    /// Sends a read receipt for an email and sets its `$mdnsent` keyword.
+    #[maybe_async::maybe_async]
+    pub async fn mdn_send(
+        &self,
+        identity_id: impl Into<String>,
+        email_id: impl Into<String>,
+        disposition: Disposition,
+    ) -> crate::Result<MDN> {
+        let mut request = self.build();
+        let send_request = request.send_mdn(identity_id);
+        let id = send_request
+            .send()
+            .for_email_id(email_id)
+            .disposition(disposition)
+            .create_id()
+            .unwrap();
+        send_request
+            .on_success_update_email(&id)
+            .keyword("$mdnsent", true);
+        request.send_send_mdn().await?.sent(&id)
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn mdn_parse(&self, blob_id: &str) -> crate::Result<MDN> {
+        let mut request = self.build();
+        request.parse_mdn().blob_ids([blob_id]);
+        request
+            .send_single::<MDNParseResponse>()
+            .await?
+            .parsed(blob_id)
+    }
+}
+
+impl Request<'_> {
+    pub fn send_mdn(&mut self, identity_id: impl Into<String>) -> &mut MDNSendRequest {
+        self.add_capability(URI::Mdn);
+        self.add_method_call(
+            Method::SendMdn,
+            Arguments::mdn_send(self.params(Method::SendMdn), identity_id),
+        )
+        .mdn_send_mut()
+    }
+
+    /// Sends the request and returns the `MDN/send` response, skipping the
+    /// implicit `Email/set` response produced by `onSuccessUpdateEmail`.
+    #[maybe_async::maybe_async]
+    pub async fn send_send_mdn(self) -> crate::Result<MDNSendResponse> {
+        self.send()
+            .await?
+            .unwrap_method_responses()
+            .into_iter()
+            .next()
+            .ok_or_else(|| crate::Error::Internal("Server returned no results".to_string()))?
+            .unwrap_send_mdn()
+    }
+
+    pub fn parse_mdn(&mut self) -> &mut MDNParseRequest {
+        self.add_capability(URI::Mdn);
+        self.add_method_call(
+            Method::ParseMdn,
+            Arguments::mdn_parse(self.params(Method::ParseMdn)),
+        )
+        .mdn_parse_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_parse_mdn(self) -> crate::Result<MDNParseResponse> {
+        self.send_single().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        email,
+        mailbox::Role,
+        mdn::{ActionMode, Disposition, DispositionType, SendingMode},
+        testing::MockServer,
+    };
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn mdn() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let identity_id = client
+            .identity_create("John Doe", "john@example.org")
+            .await
+            .unwrap()
+            .take_id();
+        let email_id = client
+            .email_import(
+                concat!(
+                    "From: jane@example.org\r\n",
+                    "To: john@example.org\r\n",
+                    "Subject: Read me\r\n",
+                    "Message-ID: <1234@example.org>\r\n",
+                    "Disposition-Notification-To: jane@example.org\r\n",
+                    "\r\n",
+                    "Hello\r\n",
+                )
+                .as_bytes()
+                .to_vec(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+
+        let disposition = Disposition::new(
+            ActionMode::Manual,
+            SendingMode::Manual,
+            DispositionType::Displayed,
+        );
+        let mdn = client
+            .mdn_send(&identity_id, &email_id, disposition.clone())
+            .await
+            .unwrap();
+        assert_eq!(mdn.final_recipient(), Some("rfc822; john@example.org"));
+        assert_eq!(mdn.original_message_id(), Some("<1234@example.org>"));
+        let email = client
+            .email_get(&email_id, [email::Property::Keywords].into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(email.keywords(), ["$mdnsent"]);
+
+        // A second receipt for the same email is rejected
+        let result = client
+            .mdn_send(&identity_id, &email_id, disposition.clone())
+            .await;
+        assert!(result.is_err());
+
+        let blob_id = client
+            .upload(
+                None,
+                concat!(
+                    "From: john@example.org\r\n",
+                    "To: jane@example.org\r\n",
+                    "Subject: Read: Read me\r\n",
+                    "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+                    "\tboundary=\"report\"\r\n",
+                    "\r\n",
+                    "--report\r\n",
+                    "Content-Type: text/plain\r\n",
+                    "\r\n",
+                    "Your message was displayed.\r\n",
+                    "--report\r\n",
+                    "Content-Type: message/disposition-notification\r\n",
+                    "\r\n",
+                    "Reporting-UA: example.org; Mail 1.0\r\n",
+                    "Final-Recipient: rfc822; john@example.org\r\n",
+                    "Original-Message-ID: <1234@example.org>\r\n",
+                    "Disposition: manual-action/MDN-sent-manually; displayed\r\n",
+                    "X-Custom: value\r\n",
+                    "--report--\r\n",
+                )
+                .as_bytes()
+                .to_vec(),
+                None,
+            )
+            .await
+            .unwrap()
+            .take_blob_id();
+        let mdn = client.mdn_parse(&blob_id).await.unwrap();
+        assert_eq!(mdn.subject(), Some("Read: Read me"));
+        assert_eq!(mdn.text_body(), Some("Your message was displayed."));
+        assert_eq!(mdn.reporting_ua(), Some("example.org; Mail 1.0"));
+        assert_eq!(mdn.original_message_id(), Some("<1234@example.org>"));
+        assert_eq!(mdn.disposition(), Some(&disposition));
+        assert_eq!(mdn.extension_field("X-Custom"), Some("value"));
+        assert!(!mdn.include_original_message());
+
+        let uploaded = client
+            .upload(None, b"Subject: Not a report\r\n\r\nHello".to_vec(), None)
+            .await
+            .unwrap()
+            .take_blob_id();
+        let result = client.mdn_parse(&uploaded).await;
+        assert!(result.is_err());
+    }
+}
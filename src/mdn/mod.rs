@@ -0,0 +1,140 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+pub mod get;
+pub mod helpers;
+pub mod parse;
+pub mod send;
+pub mod set;
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::Get;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MDN<State = Get> {
+    #[serde(skip)]
+    _create_id: Option<usize>,
+
+    #[serde(skip)]
+    _state: std::marker::PhantomData<State>,
+
+    #[serde(rename = "forEmailId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    for_email_id: Option<String>,
+
+    #[serde(rename = "subject")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subject: Option<String>,
+
+    #[serde(rename = "textBody")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text_body: Option<String>,
+
+    #[serde(rename = "includeOriginalMessage")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include_original_message: Option<bool>,
+
+    #[serde(rename = "reportingUA")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reporting_ua: Option<String>,
+
+    #[serde(rename = "disposition")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disposition: Option<Disposition>,
+
+    #[serde(rename = "mdnGateway")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mdn_gateway: Option<String>,
+
+    #[serde(rename = "originalRecipient")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_recipient: Option<String>,
+
+    #[serde(rename = "finalRecipient")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    final_recipient: Option<String>,
+
+    #[serde(rename = "originalMessageId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_message_id: Option<String>,
+
+    #[serde(rename = "error")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Vec<String>>,
+
+    #[serde(rename = "extensionFields")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extension_fields: Option<AHashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Disposition {
+    #[serde(rename = "actionMode")]
+    action_mode: ActionMode,
+
+    #[serde(rename = "sendingMode")]
+    sending_mode: SendingMode,
+
+    #[serde(rename = "type")]
+    type_: DispositionType,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ActionMode {
+    #[serde(rename = "manual-action")]
+    Manual,
+    #[serde(rename = "automatic-action")]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum SendingMode {
+    #[serde(rename = "mdn-sent-manually")]
+    Manual,
+    #[serde(rename = "mdn-sent-automatically")]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum DispositionType {
+    #[serde(rename = "deleted")]
+    Deleted,
+    #[serde(rename = "dispatched")]
+    Dispatched,
+    #[serde(rename = "displayed")]
+    Displayed,
+    #[serde(rename = "processed")]
+    Processed,
+}
+
+impl Disposition {
+    pub fn new(action_mode: ActionMode, sending_mode: SendingMode, type_: DispositionType) -> Self {
+        Disposition {
+            action_mode,
+            sending_mode,
+            type_,
+        }
+    }
+
+    pub fn action_mode(&self) -> ActionMode {
+        self.action_mode
+    }
+
+    pub fn sending_mode(&self) -> SendingMode {
+        self.sending_mode
+    }
+
+    pub fn type_(&self) -> DispositionType {
+        self.type_
+    }
+}
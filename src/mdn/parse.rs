@@ -0,0 +1,100 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{core::RequestParams, Error};
+
+use super::MDN;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MDNParseRequest {
+    #[serde(rename = "accountId")]
+    account_id: String,
+
+    #[serde(rename = "blobIds")]
+    blob_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MDNParseResponse {
+    #[serde(rename = "accountId")]
+    account_id: String,
+
+    #[serde(rename = "parsed")]
+    parsed: Option<AHashMap<String, MDN>>,
+
+    #[serde(rename = "notParsable")]
+    not_parsable: Option<Vec<String>>,
+
+    #[serde(rename = "notFound")]
+    not_found: Option<Vec<String>>,
+}
+
+impl MDNParseRequest {
+    pub fn new(params: RequestParams) -> Self {
+        MDNParseRequest {
+            account_id: params.account_id,
+            blob_ids: Vec::new(),
+        }
+    }
+
+    pub fn account_id(&mut self, account_id: impl Into<String>) -> &mut Self {
+        self.account_id = account_id.into();
+        self
+    }
+
+    pub fn blob_ids<U, V>(&mut self, blob_ids: U) -> &mut Self
+    where
+        U: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.blob_ids = blob_ids.into_iter().map(|v| v.into()).collect();
+        self
+    }
+}
+
+impl MDNParseResponse {
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn parsed(&mut self, blob_id: &str) -> crate::Result<MDN> {
+        if let Some(result) = self.parsed.as_mut().and_then(|r| r.remove(blob_id)) {
+            Ok(result)
+        } else if self
+            .not_parsable
+            .as_ref()
+            .map(|np| np.iter().any(|id| id == blob_id))
+            .unwrap_or(false)
+        {
+            Err(Error::Internal(format!(
+                "blobId {} is not parsable.",
+                blob_id
+            )))
+        } else {
+            Err(Error::Internal(format!("blobId {} not found.", blob_id)))
+        }
+    }
+
+    pub fn parsed_list(&self) -> Option<impl Iterator<Item = (&String, &MDN)>> {
+        self.parsed.as_ref().map(|map| map.iter())
+    }
+
+    pub fn not_parsable(&self) -> Option<&[String]> {
+        self.not_parsable.as_deref()
+    }
+
+    pub fn not_found(&self) -> Option<&[String]> {
+        self.not_found.as_deref()
+    }
+}
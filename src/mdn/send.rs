@@ -0,0 +1,113 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{
+        set::{SetError, SetObject},
+        RequestParams,
+    },
+    email::Email,
+    Error, Get, Set,
+};
+
+use super::MDN;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MDNSendRequest {
+    #[serde(rename = "accountId")]
+    account_id: String,
+
+    #[serde(rename = "identityId")]
+    identity_id: String,
+
+    #[serde(rename = "send")]
+    send: AHashMap<String, MDN<Set>>,
+
+    #[serde(rename = "onSuccessUpdateEmail")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_success_update_email: Option<AHashMap<String, Email<Set>>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MDNSendResponse {
+    #[serde(rename = "accountId")]
+    account_id: String,
+
+    #[serde(rename = "sent")]
+    sent: Option<AHashMap<String, MDN<Get>>>,
+
+    #[serde(rename = "notSent")]
+    not_sent: Option<AHashMap<String, SetError<String>>>,
+}
+
+impl MDNSendRequest {
+    pub fn new(params: RequestParams, identity_id: impl Into<String>) -> Self {
+        MDNSendRequest {
+            account_id: params.account_id,
+            identity_id: identity_id.into(),
+            send: AHashMap::new(),
+            on_success_update_email: None,
+        }
+    }
+
+    pub fn account_id(&mut self, account_id: impl Into<String>) -> &mut Self {
+        self.account_id = account_id.into();
+        self
+    }
+
+    pub fn identity_id(&mut self, identity_id: impl Into<String>) -> &mut Self {
+        self.identity_id = identity_id.into();
+        self
+    }
+
+    pub fn send(&mut self) -> &mut MDN<Set> {
+        let create_id = self.send.len();
+        let create_id_str = format!("c{}", create_id);
+        self.send.insert(create_id_str.clone(), MDN::new(create_id));
+        self.send.get_mut(&create_id_str).unwrap()
+    }
+
+    /// Patches the email an MDN was sent for, usually to set the `$mdnsent` keyword.
+    pub fn on_success_update_email(&mut self, create_id: impl Into<String>) -> &mut Email<Set> {
+        let id = format!("#{}", create_id.into());
+        self.on_success_update_email
+            .get_or_insert_with(AHashMap::new)
+            .entry(id)
+            .or_insert_with(|| Email::new(None))
+    }
+}
+
+impl MDNSendResponse {
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn sent(&mut self, id: &str) -> crate::Result<MDN> {
+        if let Some(result) = self.sent.as_mut().and_then(|r| r.remove(id)) {
+            Ok(result)
+        } else if let Some(error) = self.not_sent.as_mut().and_then(|r| r.remove(id)) {
+            Err(error.to_string_error().into())
+        } else {
+            Err(Error::Internal(format!("Id {} not found.", id)))
+        }
+    }
+
+    pub fn sent_ids(&self) -> Option<impl Iterator<Item = &String>> {
+        self.sent.as_ref().map(|map| map.keys())
+    }
+
+    pub fn not_sent_ids(&self) -> Option<impl Iterator<Item = &String>> {
+        self.not_sent.as_ref().map(|map| map.keys())
+    }
+}
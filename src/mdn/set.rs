@@ -0,0 +1,87 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+
+use crate::Set;
+
+use super::{Disposition, MDN};
+
+impl MDN<Set> {
+    pub(crate) fn new(create_id: usize) -> Self {
+        MDN {
+            _create_id: create_id.into(),
+            _state: Default::default(),
+            for_email_id: None,
+            subject: None,
+            text_body: None,
+            include_original_message: None,
+            reporting_ua: None,
+            disposition: None,
+            mdn_gateway: None,
+            original_recipient: None,
+            final_recipient: None,
+            original_message_id: None,
+            error: None,
+            extension_fields: None,
+        }
+    }
+
+    pub fn for_email_id(&mut self, for_email_id: impl Into<String>) -> &mut Self {
+        self.for_email_id = Some(for_email_id.into());
+        self
+    }
+
+    pub fn subject(&mut self, subject: impl Into<String>) -> &mut Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub fn text_body(&mut self, text_body: impl Into<String>) -> &mut Self {
+        self.text_body = Some(text_body.into());
+        self
+    }
+
+    pub fn include_original_message(&mut self, include_original_message: bool) -> &mut Self {
+        self.include_original_message = include_original_message.into();
+        self
+    }
+
+    pub fn reporting_ua(&mut self, reporting_ua: impl Into<String>) -> &mut Self {
+        self.reporting_ua = Some(reporting_ua.into());
+        self
+    }
+
+    pub fn disposition(&mut self, disposition: Disposition) -> &mut Self {
+        self.disposition = disposition.into();
+        self
+    }
+
+    pub fn final_recipient(&mut self, final_recipient: impl Into<String>) -> &mut Self {
+        self.final_recipient = Some(final_recipient.into());
+        self
+    }
+
+    pub fn extension_field(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> &mut Self {
+        self.extension_fields
+            .get_or_insert_with(AHashMap::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
+    pub fn create_id(&self) -> Option<String> {
+        self._create_id.map(|id| format!("c{}", id))
+    }
+}
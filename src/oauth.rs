@@ -0,0 +1,232 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use reqwest::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use serde::Deserialize;
+
+use crate::{
+    credentials::CredentialProvider,
+    limiter::Limiter,
+    transport::{HttpRequest, Transport},
+    Error,
+};
+
+pub type TokenCallback = Arc<dyn Fn(&OAuthCredentials) + Send + Sync>;
+
+/// OAuth 2.0 bearer token that can be renewed using a refresh token.
+#[derive(Clone, PartialEq, Eq)]
+pub struct OAuthCredentials {
+    access_token: String,
+    refresh_token: Option<String>,
+    token_endpoint: String,
+    client_id: String,
+    client_secret: Option<String>,
+}
+
+/// [`CredentialProvider`] that renews the OAuth access token when it is rejected.
+pub(crate) struct OAuthProvider {
+    credentials: parking_lot::Mutex<OAuthCredentials>,
+    transport: parking_lot::Mutex<Arc<dyn Transport>>,
+    timeout: parking_lot::Mutex<Duration>,
+    on_token_refresh: Option<TokenCallback>,
+    refresh_lock: Limiter,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl OAuthCredentials {
+    pub fn new(
+        access_token: impl Into<String>,
+        token_endpoint: impl Into<String>,
+        client_id: impl Into<String>,
+    ) -> Self {
+        OAuthCredentials {
+            access_token: access_token.into(),
+            refresh_token: None,
+            token_endpoint: token_endpoint.into(),
+            client_id: client_id.into(),
+            client_secret: None,
+        }
+    }
+
+    pub fn with_refresh_token(mut self, refresh_token: impl Into<String>) -> Self {
+        self.refresh_token = Some(refresh_token.into());
+        self
+    }
+
+    pub fn with_client_secret(mut self, client_secret: impl Into<String>) -> Self {
+        self.client_secret = Some(client_secret.into());
+        self
+    }
+
+    pub fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    pub fn refresh_token(&self) -> Option<&str> {
+        self.refresh_token.as_deref()
+    }
+
+    pub fn token_endpoint(&self) -> &str {
+        &self.token_endpoint
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn can_refresh(&self) -> bool {
+        self.refresh_token.is_some()
+    }
+
+    /// Obtains a new access token from the token endpoint using the refresh token
+    /// grant (RFC 6749, Section 6). The refresh token is kept unless the server
+    /// rotates it.
+    #[maybe_async::maybe_async]
+    pub(crate) async fn refresh(
+        &self,
+        transport: &dyn Transport,
+        timeout: Duration,
+    ) -> crate::Result<OAuthCredentials> {
+        let refresh_token = self
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| Error::Internal("No refresh token available.".to_string()))?;
+        let body = {
+            let mut body = form_urlencoded::Serializer::new(String::new());
+            body.append_pair("grant_type", "refresh_token")
+                .append_pair("refresh_token", refresh_token)
+                .append_pair("client_id", &self.client_id);
+            if let Some(client_secret) = &self.client_secret {
+                body.append_pair("client_secret", client_secret);
+            }
+            body.finish()
+        };
+
+        let response = transport
+            .send(
+                HttpRequest::post(&self.token_endpoint)
+                    .with_header(
+                        CONTENT_TYPE,
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
+                    )
+                    .with_header(ACCEPT, HeaderValue::from_static("application/json"))
+                    .with_body(body)
+                    .with_timeout(timeout),
+            )
+            .await?;
+
+        if response.status().is_success() {
+            let token: TokenResponse = serde_json::from_slice(response.body())?;
+            Ok(OAuthCredentials {
+                access_token: token.access_token,
+                refresh_token: token.refresh_token.or_else(|| self.refresh_token.clone()),
+                ..self.clone()
+            })
+        } else if let Ok(error) = serde_json::from_slice::<TokenError>(response.body()) {
+            Err(Error::Server(format!(
+                "Token refresh failed: {}{}",
+                error.error,
+                error
+                    .error_description
+                    .map(|description| format!(" ({})", description))
+                    .unwrap_or_default()
+            )))
+        } else {
+            Err(Error::Server(format!(
+                "Token refresh failed: {}",
+                response.status()
+            )))
+        }
+    }
+}
+
+impl std::fmt::Debug for OAuthCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("OAuthCredentials")
+            .field("access_token", &"<redacted>")
+            .field("refresh_token", &redacted(&self.refresh_token))
+            .field("token_endpoint", &self.token_endpoint)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &redacted(&self.client_secret))
+            .finish()
+    }
+}
+
+impl OAuthProvider {
+    pub(crate) fn new(
+        credentials: OAuthCredentials,
+        transport: Arc<dyn Transport>,
+        timeout: Duration,
+        on_token_refresh: Option<TokenCallback>,
+    ) -> Self {
+        OAuthProvider {
+            credentials: credentials.into(),
+            transport: transport.into(),
+            timeout: timeout.into(),
+            on_token_refresh,
+            refresh_lock: Limiter::new(1),
+        }
+    }
+
+    /// Replaces the transport used to reach the token endpoint when the client's is rebuilt.
+    pub(crate) fn set_transport(&self, transport: Arc<dyn Transport>, timeout: Duration) {
+        *self.transport.lock() = transport;
+        *self.timeout.lock() = timeout;
+    }
+}
+
+#[maybe_async::maybe_async]
+impl CredentialProvider for OAuthProvider {
+    async fn authorization(&self) -> crate::Result<HeaderValue> {
+        HeaderValue::from_str(&format!("Bearer {}", self.credentials.lock().access_token))
+            .map_err(|_| Error::Internal("Invalid credentials.".to_string()))
+    }
+
+    async fn refresh(&self, rejected: &HeaderValue) -> crate::Result<bool> {
+        let _permit = self.refresh_lock.acquire().await;
+
+        // Another request might have refreshed the token while waiting
+        if self.authorization().await? != rejected {
+            return Ok(true);
+        }
+        let credentials = self.credentials.lock().clone();
+        if !credentials.can_refresh() {
+            return Ok(false);
+        }
+
+        let transport = self.transport.lock().clone();
+        let timeout = *self.timeout.lock();
+        let credentials = credentials.refresh(transport.as_ref(), timeout).await?;
+        if let Some(on_token_refresh) = &self.on_token_refresh {
+            on_token_refresh(&credentials);
+        }
+        *self.credentials.lock() = credentials;
+        Ok(true)
+    }
+
+    fn can_refresh(&self) -> bool {
+        self.credentials.lock().can_refresh()
+    }
+}
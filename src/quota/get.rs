@@ -0,0 +1,69 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{core::get::GetObject, DataType};
+
+use super::{Quota, ResourceType, Scope};
+
+impl Quota {
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn take_id(&mut self) -> String {
+        self.id.take().unwrap_or_default()
+    }
+
+    pub fn resource_type(&self) -> Option<ResourceType> {
+        self.resource_type
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.unwrap_or(0)
+    }
+
+    pub fn hard_limit(&self) -> u64 {
+        self.hard_limit.unwrap_or(0)
+    }
+
+    pub fn scope(&self) -> Option<Scope> {
+        self.scope
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn types(&self) -> &[DataType] {
+        self.types.as_deref().unwrap_or(&[])
+    }
+
+    pub fn warn_limit(&self) -> Option<u64> {
+        self.warn_limit
+    }
+
+    pub fn soft_limit(&self) -> Option<u64> {
+        self.soft_limit
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns how much of the hard limit is still available.
+    pub fn remaining(&self) -> u64 {
+        self.hard_limit().saturating_sub(self.used())
+    }
+}
+
+impl GetObject for Quota {
+    type GetArguments = ();
+}
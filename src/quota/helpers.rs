@@ -0,0 +1,239 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    client::Client,
+    core::{
+        changes::{ChangesRequest, ChangesResponse},
+        get::GetRequest,
+        query::{Comparator, Filter, QueryRequest, QueryResponse},
+        query_changes::{QueryChangesRequest, QueryChangesResponse},
+        request::{Arguments, Request},
+        response::QuotaGetResponse,
+    },
+    Method, URI,
+};
+
+use super::{Property, Quota};
+
+impl Client {
+    #[maybe_async::maybe_async]
+    pub async fn quota_get(
+        &self,
+        id: &str,
+        properties: Option<impl IntoIterator<Item = Property>>,
+    ) -> crate::Result<Option<Quota>> {
+        let mut request = self.build();
+        let get_request = request.get_quota().ids([id]);
+        if let Some(properties) = properties {
+            get_request.properties(properties);
+        }
+        request
+            .send_single::<QuotaGetResponse>()
+            .await
+            .map(|mut r| r.take_list().pop())
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn quota_get_all(&self) -> crate::Result<Vec<Quota>> {
+        let mut request = self.build();
+        request.get_quota();
+        request
+            .send_single::<QuotaGetResponse>()
+            .await
+            .map(|mut r| r.take_list())
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn quota_query(
+        &self,
+        filter: Option<impl Into<Filter<super::query::Filter>>>,
+        sort: Option<impl IntoIterator<Item = Comparator<super::query::Comparator>>>,
+    ) -> crate::Result<QueryResponse> {
+        let mut request = self.build();
+        let query_request = request.query_quota();
+        if let Some(filter) = filter {
+            query_request.filter(filter);
+        }
+        if let Some(sort) = sort {
+            query_request.sort(sort);
+        }
+        request.send_single::<QueryResponse>().await
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn quota_changes(
+        &self,
+        since_state: impl Into<String>,
+        max_changes: usize,
+    ) -> crate::Result<ChangesResponse<Quota>> {
+        let mut request = self.build();
+        request.changes_quota(since_state).max_changes(max_changes);
+        request.send_single().await
+    }
+}
+
+impl Request<'_> {
+    pub fn get_quota(&mut self) -> &mut GetRequest<Quota> {
+        self.add_capability(URI::Quota);
+        self.add_method_call(
+            Method::GetQuota,
+            Arguments::quota_get(self.params(Method::GetQuota)),
+        )
+        .quota_get_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_get_quota(self) -> crate::Result<QuotaGetResponse> {
+        self.send_single().await
+    }
+
+    pub fn changes_quota(&mut self, since_state: impl Into<String>) -> &mut ChangesRequest {
+        self.add_capability(URI::Quota);
+        self.add_method_call(
+            Method::ChangesQuota,
+            Arguments::changes(self.params(Method::ChangesQuota), since_state.into()),
+        )
+        .changes_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_changes_quota(self) -> crate::Result<ChangesResponse<Quota>> {
+        self.send_single().await
+    }
+
+    pub fn query_quota(&mut self) -> &mut QueryRequest<Quota> {
+        self.add_capability(URI::Quota);
+        self.add_method_call(
+            Method::QueryQuota,
+            Arguments::quota_query(self.params(Method::QueryQuota)),
+        )
+        .quota_query_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_query_quota(self) -> crate::Result<QueryResponse> {
+        self.send_single().await
+    }
+
+    pub fn query_quota_changes(
+        &mut self,
+        since_query_state: impl Into<String>,
+    ) -> &mut QueryChangesRequest<Quota> {
+        self.add_capability(URI::Quota);
+        self.add_method_call(
+            Method::QueryChangesQuota,
+            Arguments::quota_query_changes(
+                self.params(Method::QueryChangesQuota),
+                since_query_state.into(),
+            ),
+        )
+        .quota_query_changes_mut()
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn send_query_quota_changes(self) -> crate::Result<QueryChangesResponse> {
+        self.send_single().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::query::Filter,
+        mailbox::Role,
+        quota::{
+            query::{self, Comparator},
+            ResourceType, Scope,
+        },
+        testing::MockServer,
+        DataType,
+    };
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn quota() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+
+        let ids = client
+            .quota_query(
+                Filter::and([
+                    query::Filter::resource_type(ResourceType::Octets),
+                    query::Filter::data_type(DataType::Email),
+                ])
+                .into(),
+                None::<Vec<_>>,
+            )
+            .await
+            .unwrap()
+            .take_ids();
+        assert_eq!(ids.len(), 1);
+        let quota = client
+            .quota_get(&ids[0], None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quota.name(), Some("Mail storage"));
+        assert_eq!(quota.scope(), Some(Scope::Account));
+        assert_eq!(quota.types(), [DataType::Email]);
+        assert_eq!(quota.used(), 0);
+        assert_eq!(quota.remaining(), quota.hard_limit());
+        assert!(quota.warn_limit().is_some());
+        assert_eq!(quota.soft_limit(), None);
+
+        let mut request = client.build();
+        request.get_quota();
+        let state = request.send_get_quota().await.unwrap().state().to_string();
+
+        // Importing an email updates the usage of every quota
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let message = b"Subject: Quota\r\n\r\nHello\r\n".to_vec();
+        let size = message.len() as u64;
+        client
+            .email_import(message, [&inbox_id], None::<Vec<&str>>, None)
+            .await
+            .unwrap();
+
+        let changes = client.quota_changes(state, 10).await.unwrap();
+        assert_eq!(changes.updated().len(), 2);
+        assert!(changes.created().is_empty());
+        let updated_properties = changes.arguments().updated_properties().unwrap();
+        assert_eq!(updated_properties, [crate::quota::Property::Used]);
+
+        let quotas = client.quota_get_all().await.unwrap();
+        assert_eq!(quotas.len(), 2);
+        for quota in quotas {
+            match quota.resource_type().unwrap() {
+                ResourceType::Octets => assert_eq!(quota.used(), size),
+                ResourceType::Count => assert_eq!(quota.used(), 1),
+            }
+        }
+
+        let ids = client
+            .quota_query(
+                None::<query::Filter>,
+                [Comparator::used().descending()].into(),
+            )
+            .await
+            .unwrap()
+            .take_ids();
+        let quota = client
+            .quota_get(&ids[0], None::<Vec<_>>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quota.resource_type(), Some(ResourceType::Octets));
+    }
+}
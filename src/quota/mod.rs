@@ -0,0 +1,158 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+pub mod get;
+pub mod helpers;
+pub mod query;
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::{changes::ChangesObject, Object},
+    DataType,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quota {
+    #[serde(rename = "id")]
+    id: Option<String>,
+
+    #[serde(rename = "resourceType")]
+    resource_type: Option<ResourceType>,
+
+    #[serde(rename = "used")]
+    used: Option<u64>,
+
+    #[serde(rename = "hardLimit")]
+    hard_limit: Option<u64>,
+
+    #[serde(rename = "scope")]
+    scope: Option<Scope>,
+
+    #[serde(rename = "name")]
+    name: Option<String>,
+
+    #[serde(rename = "types")]
+    types: Option<Vec<DataType>>,
+
+    #[serde(rename = "warnLimit")]
+    warn_limit: Option<u64>,
+
+    #[serde(rename = "softLimit")]
+    soft_limit: Option<u64>,
+
+    #[serde(rename = "description")]
+    description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ResourceType {
+    #[serde(rename = "count")]
+    Count,
+    #[serde(rename = "octets")]
+    Octets,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Scope {
+    #[serde(rename = "account")]
+    Account,
+    #[serde(rename = "domain")]
+    Domain,
+    #[serde(rename = "global")]
+    Global,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ChangesResponse {
+    #[serde(rename = "updatedProperties")]
+    updated_properties: Option<Vec<Property>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Copy)]
+pub enum Property {
+    #[serde(rename = "id")]
+    Id,
+    #[serde(rename = "resourceType")]
+    ResourceType,
+    #[serde(rename = "used")]
+    Used,
+    #[serde(rename = "hardLimit")]
+    HardLimit,
+    #[serde(rename = "scope")]
+    Scope,
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "types")]
+    Types,
+    #[serde(rename = "warnLimit")]
+    WarnLimit,
+    #[serde(rename = "softLimit")]
+    SoftLimit,
+    #[serde(rename = "description")]
+    Description,
+}
+
+impl Display for ResourceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceType::Count => write!(f, "count"),
+            ResourceType::Octets => write!(f, "octets"),
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Account => write!(f, "account"),
+            Scope::Domain => write!(f, "domain"),
+            Scope::Global => write!(f, "global"),
+        }
+    }
+}
+
+impl Display for Property {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Property::Id => write!(f, "id"),
+            Property::ResourceType => write!(f, "resourceType"),
+            Property::Used => write!(f, "used"),
+            Property::HardLimit => write!(f, "hardLimit"),
+            Property::Scope => write!(f, "scope"),
+            Property::Name => write!(f, "name"),
+            Property::Types => write!(f, "types"),
+            Property::WarnLimit => write!(f, "warnLimit"),
+            Property::SoftLimit => write!(f, "softLimit"),
+            Property::Description => write!(f, "description"),
+        }
+    }
+}
+
+impl ChangesResponse {
+    pub fn updated_properties(&self) -> Option<&[Property]> {
+        self.updated_properties.as_deref()
+    }
+}
+
+impl Object for Quota {
+    type Property = Property;
+
+    fn requires_account_id() -> bool {
+        true
+    }
+}
+
+impl ChangesObject for Quota {
+    type ChangesResponse = ChangesResponse;
+}
@@ -0,0 +1,87 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use serde::Serialize;
+
+use crate::{
+    core::query::{self, QueryObject},
+    DataType,
+};
+
+use super::{Quota, ResourceType, Scope};
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Filter {
+    Name {
+        #[serde(rename = "name")]
+        value: String,
+    },
+    Scope {
+        #[serde(rename = "scope")]
+        value: Scope,
+    },
+    ResourceType {
+        #[serde(rename = "resourceType")]
+        value: ResourceType,
+    },
+    Type {
+        #[serde(rename = "type")]
+        value: DataType,
+    },
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "property")]
+pub enum Comparator {
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "used")]
+    Used,
+}
+
+impl Filter {
+    pub fn name(value: impl Into<String>) -> Self {
+        Filter::Name {
+            value: value.into(),
+        }
+    }
+
+    pub fn scope(value: Scope) -> Self {
+        Filter::Scope { value }
+    }
+
+    pub fn resource_type(value: ResourceType) -> Self {
+        Filter::ResourceType { value }
+    }
+
+    pub fn data_type(value: DataType) -> Self {
+        Filter::Type { value }
+    }
+}
+
+impl Comparator {
+    pub fn name() -> query::Comparator<Comparator> {
+        query::Comparator::new(Comparator::Name)
+    }
+
+    pub fn used() -> query::Comparator<Comparator> {
+        query::Comparator::new(Comparator::Used)
+    }
+}
+
+impl QueryObject for Quota {
+    type QueryArguments = ();
+
+    type Filter = Filter;
+
+    type Sort = Comparator;
+}
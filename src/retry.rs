@@ -0,0 +1,295 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde::{de::IgnoredAny, Deserialize};
+
+use crate::{transport::HttpResponse, Error};
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_INITIAL_BACKOFF_MS: u64 = 250;
+const DEFAULT_MAX_BACKOFF_MS: u64 = 30 * 1000;
+
+/// Retry policy for transient transport and server errors.
+///
+/// Only idempotent requests (`/get`, `/query`, `/changes`, `/queryChanges` and
+/// blob downloads) are retried unless [RetryPolicy.retry_set()](struct.RetryPolicy.html#method.retry_set)
+/// is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    retry_set: bool,
+}
+
+#[derive(Deserialize)]
+struct MethodResponses {
+    #[serde(rename = "methodResponses")]
+    method_responses: Vec<(String, MethodErrorProbe, IgnoredAny)>,
+}
+
+#[derive(Deserialize)]
+struct MethodErrorProbe {
+    #[serde(rename = "type")]
+    p_type: Option<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that makes up to 3 attempts with exponential backoff and jitter.
+    pub fn new() -> Self {
+        RetryPolicy {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(DEFAULT_INITIAL_BACKOFF_MS),
+            max_backoff: Duration::from_millis(DEFAULT_MAX_BACKOFF_MS),
+            jitter: true,
+            retry_set: false,
+        }
+    }
+
+    /// Creates a policy that never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Self::new()
+        }
+    }
+
+    /// Maximum number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Backoff before the first retry, doubled on each subsequent attempt up to `max_backoff`.
+    ///
+    /// `max_backoff` also caps the `Retry-After` delays the client is willing to wait for.
+    pub fn backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff.max(initial_backoff);
+        self
+    }
+
+    /// Randomizes each backoff between half and the full computed delay.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Also retry requests containing non-idempotent method calls such as `/set`.
+    ///
+    /// A request that failed due to a dropped connection may have been executed by the
+    /// server, so enable this only if the calls can be safely replayed (for example by
+    /// using `ifInState`).
+    pub fn retry_set(mut self, retry_set: bool) -> Self {
+        self.retry_set = retry_set;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts > 1
+    }
+
+    pub(crate) fn can_retry(&self, is_idempotent: bool) -> bool {
+        self.is_enabled() && (is_idempotent || self.retry_set)
+    }
+
+    /// Returns how long to wait before attempting the request again, or `None` if the
+    /// result should be returned to the caller.
+    pub(crate) fn retry_delay(
+        &self,
+        result: &crate::Result<HttpResponse>,
+        attempt: u32,
+        check_method_errors: bool,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        let retry_after = match result {
+            Ok(response) if response.status().is_success() => {
+                if check_method_errors && has_server_unavailable(response.body()) {
+                    None
+                } else {
+                    return None;
+                }
+            }
+            Ok(response) => match response.status() {
+                StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT => parse_retry_after(response),
+                _ => return None,
+            },
+            Err(Error::Transport(err))
+                if err.is_connect() || err.is_timeout() || err.is_request() =>
+            {
+                None
+            }
+            Err(_) => return None,
+        };
+
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff_delay(attempt)),
+        }
+    }
+
+    /// Returns how long to wait before a reconnection attempt, or `None` once
+    /// `max_attempts` consecutive attempts have been made.
+    #[cfg(feature = "async")]
+    pub(crate) fn reconnect_delay(&self, attempt: u32) -> Option<Duration> {
+        (attempt <= self.max_attempts).then(|| self.backoff_delay(attempt))
+    }
+
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1u32 << (attempt.saturating_sub(1)).min(16))
+            .min(self.max_backoff);
+        if self.jitter && !delay.is_zero() {
+            let half = delay / 2;
+            half + Duration::from_nanos(
+                RandomState::new().build_hasher().finish() % (half.as_nanos() as u64).max(1),
+            )
+        } else {
+            delay
+        }
+    }
+}
+
+fn has_server_unavailable(body: &[u8]) -> bool {
+    body.windows(b"serverUnavailable".len())
+        .any(|window| window == b"serverUnavailable")
+        && serde_json::from_slice::<MethodResponses>(body).is_ok_and(|response| {
+            response.method_responses.iter().any(|(name, probe, _)| {
+                name == "error" && probe.p_type.as_deref() == Some("serverUnavailable")
+            })
+        })
+}
+
+fn parse_retry_after(response: &HttpResponse) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        Some(Duration::from_secs(seconds))
+    } else {
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        Some(
+            (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn sleep(duration: Duration) {
+    std::thread::sleep(duration);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, RETRY_AFTER},
+        StatusCode,
+    };
+
+    use super::RetryPolicy;
+    use crate::transport::HttpResponse;
+
+    #[test]
+    fn retry_delay() {
+        let policy = RetryPolicy::new()
+            .max_attempts(3)
+            .backoff(Duration::from_millis(100), Duration::from_secs(5))
+            .jitter(false);
+        let response = |status: StatusCode, retry_after: Option<&'static str>, body: &str| {
+            let mut headers = HeaderMap::new();
+            if let Some(retry_after) = retry_after {
+                headers.insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+            }
+            Ok(HttpResponse::new(status, headers, body.as_bytes().to_vec()))
+        };
+
+        for (result, attempt, expected) in [
+            (response(StatusCode::OK, None, "{}"), 1, None),
+            (
+                response(StatusCode::SERVICE_UNAVAILABLE, None, ""),
+                1,
+                Some(Duration::from_millis(100)),
+            ),
+            (
+                response(StatusCode::BAD_GATEWAY, None, ""),
+                2,
+                Some(Duration::from_millis(200)),
+            ),
+            (response(StatusCode::GATEWAY_TIMEOUT, None, ""), 3, None),
+            (
+                response(StatusCode::TOO_MANY_REQUESTS, Some("2"), ""),
+                1,
+                Some(Duration::from_secs(2)),
+            ),
+            (
+                response(StatusCode::TOO_MANY_REQUESTS, Some("3600"), ""),
+                1,
+                None,
+            ),
+            (response(StatusCode::BAD_REQUEST, None, ""), 1, None),
+            (
+                response(
+                    StatusCode::OK,
+                    None,
+                    r#"{"sessionState": "s1", "methodResponses": [
+                        ["error", {"type": "serverUnavailable"}, "s0"]]}"#,
+                ),
+                1,
+                Some(Duration::from_millis(100)),
+            ),
+            (
+                response(
+                    StatusCode::OK,
+                    None,
+                    r#"{"sessionState": "s1", "methodResponses": [
+                        ["Email/get", {"description": "serverUnavailable"}, "s0"]]}"#,
+                ),
+                1,
+                None,
+            ),
+        ] {
+            assert_eq!(policy.retry_delay(&result, attempt, true), expected);
+        }
+
+        assert!(!RetryPolicy::none().can_retry(true));
+        assert!(!RetryPolicy::new().can_retry(false));
+        assert!(RetryPolicy::new().retry_set(true).can_retry(false));
+    }
+}
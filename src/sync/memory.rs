@@ -0,0 +1,127 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+use serde_json::Value;
+
+use crate::DataType;
+
+use super::SyncStore;
+
+/// [`SyncStore`] that keeps the replica in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    collections: parking_lot::RwLock<AHashMap<(String, DataType), Collection>>,
+}
+
+#[derive(Debug, Default)]
+struct Collection {
+    state: Option<String>,
+    objects: AHashMap<String, Value>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SyncStore for MemoryStore {
+    fn state(&self, account_id: &str, data_type: &DataType) -> crate::Result<Option<String>> {
+        Ok(self
+            .collections
+            .read()
+            .get(&(account_id.to_string(), data_type.clone()))
+            .and_then(|collection| collection.state.clone()))
+    }
+
+    fn set_state(&self, account_id: &str, data_type: &DataType, state: &str) -> crate::Result<()> {
+        self.collections
+            .write()
+            .entry((account_id.to_string(), data_type.clone()))
+            .or_default()
+            .state = Some(state.to_string());
+        Ok(())
+    }
+
+    fn get(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        id: &str,
+    ) -> crate::Result<Option<Value>> {
+        Ok(self
+            .collections
+            .read()
+            .get(&(account_id.to_string(), data_type.clone()))
+            .and_then(|collection| collection.objects.get(id).cloned()))
+    }
+
+    fn get_all(&self, account_id: &str, data_type: &DataType) -> crate::Result<Vec<Value>> {
+        Ok(self
+            .collections
+            .read()
+            .get(&(account_id.to_string(), data_type.clone()))
+            .map(|collection| collection.objects.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn insert(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        id: &str,
+        object: Value,
+    ) -> crate::Result<()> {
+        self.collections
+            .write()
+            .entry((account_id.to_string(), data_type.clone()))
+            .or_default()
+            .objects
+            .insert(id.to_string(), object);
+        Ok(())
+    }
+
+    fn remove(&self, account_id: &str, data_type: &DataType, id: &str) -> crate::Result<()> {
+        if let Some(collection) = self
+            .collections
+            .write()
+            .get_mut(&(account_id.to_string(), data_type.clone()))
+        {
+            collection.objects.remove(id);
+        }
+        Ok(())
+    }
+
+    fn clear(&self, account_id: &str, data_type: &DataType) -> crate::Result<()> {
+        self.collections
+            .write()
+            .remove(&(account_id.to_string(), data_type.clone()));
+        Ok(())
+    }
+
+    fn replace_all(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        objects: Vec<(String, Value)>,
+        state: &str,
+    ) -> crate::Result<()> {
+        let collection = Collection {
+            state: Some(state.to_string()),
+            objects: objects.into_iter().collect(),
+        };
+        self.collections
+            .write()
+            .insert((account_id.to_string(), data_type.clone()), collection);
+        Ok(())
+    }
+}
@@ -0,0 +1,746 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Local replica of an account kept up to date with incremental `/changes` calls.
+//!
+//! A [`SyncEngine`] stores the `Mailbox`, `Email` (metadata only), `Thread` and
+//! `Identity` objects of an account in a [`SyncStore`]. The first synchronization
+//! fetches all objects, later ones only fetch what changed since the stored state.
+//! When the server is unable to calculate the changes the data type is fetched again
+//! from scratch.
+//!
+//! ```ignore
+//! let engine = SyncEngine::new(MemoryStore::new(), client.default_account_id());
+//! engine.sync(&client).await?;
+//! for mailbox in engine.mailboxes()? {
+//!     println!("{:?}", mailbox.name());
+//! }
+//! ```
+
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "async")]
+pub mod watch;
+
+use ahash::AHashSet;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    client::Client,
+    core::{
+        changes::{ChangesObject, ChangesRequest, ChangesResponse},
+        error::MethodErrorType,
+        get::GetResponse,
+        request::Request,
+        Object,
+    },
+    email::{self, Email},
+    identity::Identity,
+    mailbox::{self, Mailbox},
+    thread::Thread,
+    DataType, Error, Get,
+};
+
+pub use self::memory::MemoryStore;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStore;
+#[cfg(feature = "async")]
+pub use self::watch::WatchEvent;
+
+const DEFAULT_MAX_CHANGES: usize = 500;
+const QUERY_PAGE_SIZE: usize = 1000;
+
+/// Email properties kept in the local replica.
+pub const EMAIL_PROPERTIES: [email::Property; 20] = [
+    email::Property::Id,
+    email::Property::BlobId,
+    email::Property::ThreadId,
+    email::Property::MailboxIds,
+    email::Property::Keywords,
+    email::Property::Size,
+    email::Property::ReceivedAt,
+    email::Property::MessageId,
+    email::Property::InReplyTo,
+    email::Property::References,
+    email::Property::Sender,
+    email::Property::From,
+    email::Property::To,
+    email::Property::Cc,
+    email::Property::Bcc,
+    email::Property::ReplyTo,
+    email::Property::Subject,
+    email::Property::SentAt,
+    email::Property::HasAttachment,
+    email::Property::Preview,
+];
+
+/// Storage backend of a [`SyncEngine`].
+///
+/// Objects are stored as JSON together with the state string they were last
+/// synchronized at, per account and data type.
+pub trait SyncStore: Send + Sync {
+    fn state(&self, account_id: &str, data_type: &DataType) -> crate::Result<Option<String>>;
+
+    fn set_state(&self, account_id: &str, data_type: &DataType, state: &str) -> crate::Result<()>;
+
+    fn get(&self, account_id: &str, data_type: &DataType, id: &str)
+        -> crate::Result<Option<Value>>;
+
+    fn get_all(&self, account_id: &str, data_type: &DataType) -> crate::Result<Vec<Value>>;
+
+    fn insert(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        id: &str,
+        object: Value,
+    ) -> crate::Result<()>;
+
+    fn remove(&self, account_id: &str, data_type: &DataType, id: &str) -> crate::Result<()>;
+
+    /// Removes all objects and the state of a data type.
+    fn clear(&self, account_id: &str, data_type: &DataType) -> crate::Result<()>;
+
+    /// Replaces all objects of a data type and sets its state, used after a full
+    /// synchronization.
+    ///
+    /// Stores should apply the replacement atomically so that a failure leaves the
+    /// previous objects and state in place. The default implementation does not.
+    fn replace_all(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        objects: Vec<(String, Value)>,
+        state: &str,
+    ) -> crate::Result<()> {
+        self.clear(account_id, data_type)?;
+        for (id, object) in objects {
+            self.insert(account_id, data_type, &id, object)?;
+        }
+        self.set_state(account_id, data_type, state)
+    }
+}
+
+pub struct SyncEngine<S: SyncStore> {
+    store: S,
+    account_id: String,
+    max_changes: usize,
+}
+
+/// Objects that changed during the synchronization of a data type.
+#[derive(Debug, Clone)]
+pub struct SyncChanges {
+    data_type: DataType,
+    created: Vec<String>,
+    updated: Vec<String>,
+    destroyed: Vec<String>,
+    full_sync: bool,
+}
+
+trait SyncObject:
+    ChangesObject<ChangesResponse: DeserializeOwned> + Serialize + DeserializeOwned + Sized
+{
+    const DATA_TYPE: DataType;
+
+    fn changes<'x>(
+        request: &'x mut Request<'_>,
+        account_id: &str,
+        since_state: String,
+    ) -> &'x mut ChangesRequest;
+
+    fn get(
+        request: &mut Request<'_>,
+        account_id: &str,
+        ids: Option<Vec<String>>,
+        properties: Option<Vec<Self::Property>>,
+    );
+
+    fn id(&self) -> Option<&str>;
+
+    fn updated_properties(_response: &ChangesResponse<Self>) -> Option<Vec<Self::Property>> {
+        None
+    }
+}
+
+impl<S: SyncStore> SyncEngine<S> {
+    pub fn new(store: S, account_id: impl Into<String>) -> Self {
+        SyncEngine {
+            store,
+            account_id: account_id.into(),
+            max_changes: DEFAULT_MAX_CHANGES,
+        }
+    }
+
+    /// Set the maximum number of changes requested in each `/changes` call.
+    pub fn max_changes(mut self, max_changes: usize) -> Self {
+        self.max_changes = max_changes;
+        self
+    }
+
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Synchronizes mailboxes, emails, threads and identities.
+    #[maybe_async::maybe_async]
+    pub async fn sync(&self, client: &Client) -> crate::Result<Vec<SyncChanges>> {
+        let mut changes = Vec::with_capacity(4);
+        for data_type in [
+            DataType::Mailbox,
+            DataType::Email,
+            DataType::Thread,
+            DataType::Identity,
+        ] {
+            changes.push(self.sync_data_type(client, data_type).await?);
+        }
+        Ok(changes)
+    }
+
+    #[maybe_async::maybe_async]
+    pub async fn sync_data_type(
+        &self,
+        client: &Client,
+        data_type: DataType,
+    ) -> crate::Result<SyncChanges> {
+        match data_type {
+            DataType::Mailbox => self.sync_object::<Mailbox<Get>>(client).await,
+            DataType::Email => self.sync_object::<Email<Get>>(client).await,
+            DataType::Thread => self.sync_object::<Thread>(client).await,
+            DataType::Identity => self.sync_object::<Identity<Get>>(client).await,
+            _ => Err(Error::Internal(format!(
+                "Synchronization of {} objects is not supported.",
+                data_type
+            ))),
+        }
+    }
+
+    /// Returns the state string a data type was last synchronized at.
+    pub fn state(&self, data_type: &DataType) -> crate::Result<Option<String>> {
+        self.store.state(&self.account_id, data_type)
+    }
+
+    pub fn mailboxes(&self) -> crate::Result<Vec<Mailbox>> {
+        self.objects(&DataType::Mailbox)
+    }
+
+    pub fn mailbox(&self, id: &str) -> crate::Result<Option<Mailbox>> {
+        self.object(&DataType::Mailbox, id)
+    }
+
+    pub fn emails(&self) -> crate::Result<Vec<Email>> {
+        self.objects(&DataType::Email)
+    }
+
+    pub fn email(&self, id: &str) -> crate::Result<Option<Email>> {
+        self.object(&DataType::Email, id)
+    }
+
+    pub fn threads(&self) -> crate::Result<Vec<Thread>> {
+        self.objects(&DataType::Thread)
+    }
+
+    pub fn thread(&self, id: &str) -> crate::Result<Option<Thread>> {
+        self.object(&DataType::Thread, id)
+    }
+
+    pub fn identities(&self) -> crate::Result<Vec<Identity>> {
+        self.objects(&DataType::Identity)
+    }
+
+    pub fn identity(&self, id: &str) -> crate::Result<Option<Identity>> {
+        self.object(&DataType::Identity, id)
+    }
+
+    fn objects<O: DeserializeOwned>(&self, data_type: &DataType) -> crate::Result<Vec<O>> {
+        self.store
+            .get_all(&self.account_id, data_type)?
+            .into_iter()
+            .map(|object| serde_json::from_value(object).map_err(Into::into))
+            .collect()
+    }
+
+    fn object<O: DeserializeOwned>(
+        &self,
+        data_type: &DataType,
+        id: &str,
+    ) -> crate::Result<Option<O>> {
+        match self.store.get(&self.account_id, data_type, id)? {
+            Some(object) => Ok(Some(serde_json::from_value(object)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn sync_object<O: SyncObject>(&self, client: &Client) -> crate::Result<SyncChanges> {
+        let Some(mut state) = self.store.state(&self.account_id, &O::DATA_TYPE)? else {
+            return self.full_sync::<O>(client).await;
+        };
+        let mut changes = SyncChanges::new(O::DATA_TYPE);
+
+        loop {
+            let mut request = client.build();
+            O::changes(&mut request, &self.account_id, state).max_changes(self.max_changes);
+            let mut response = match request.send_single::<ChangesResponse<O>>().await {
+                Ok(response) => response,
+                Err(Error::Method(err))
+                    if err.error() == &MethodErrorType::CannotCalculateChanges =>
+                {
+                    return self.full_sync::<O>(client).await;
+                }
+                Err(err) => return Err(err),
+            };
+
+            let updated_properties = O::updated_properties(&response);
+            let created = response.take_created();
+            let updated = response.take_updated();
+            let destroyed = response.take_destroyed();
+
+            // Objects that were only partially updated are patched in place
+            let (fetch_ids, patch_ids) = match updated_properties {
+                Some(_) => (created.clone(), updated.clone()),
+                None => (
+                    created.iter().chain(updated.iter()).cloned().collect(),
+                    Vec::new(),
+                ),
+            };
+            if !fetch_ids.is_empty() {
+                let mut response = self.get::<O>(client, Some(fetch_ids), None).await?;
+                self.insert_all(response.take_list())?;
+                for id in response.take_not_found() {
+                    self.store.remove(&self.account_id, &O::DATA_TYPE, &id)?;
+                }
+            }
+            if let (false, Some(properties)) = (patch_ids.is_empty(), updated_properties) {
+                let keys = properties
+                    .iter()
+                    .map(|property| property.to_string())
+                    .collect::<Vec<_>>();
+                let mut response = self
+                    .get::<O>(client, Some(patch_ids), Some(properties))
+                    .await?;
+                for object in response.take_list() {
+                    self.patch(object, &keys)?;
+                }
+            }
+            for id in &destroyed {
+                self.store.remove(&self.account_id, &O::DATA_TYPE, id)?;
+            }
+
+            state = response.take_new_state();
+            self.store
+                .set_state(&self.account_id, &O::DATA_TYPE, &state)?;
+            changes.created.extend(created);
+            changes.updated.extend(updated);
+            changes.destroyed.extend(destroyed);
+
+            if !response.has_more_changes() {
+                return Ok(changes);
+            }
+        }
+    }
+
+    #[maybe_async::maybe_async]
+    async fn full_sync<O: SyncObject>(&self, client: &Client) -> crate::Result<SyncChanges> {
+        let (state, objects) = match O::DATA_TYPE {
+            DataType::Email | DataType::Thread => {
+                // Obtain the state before listing the ids so that no changes are missed
+                let state = self
+                    .get::<O>(client, Some(Vec::new()), None)
+                    .await?
+                    .take_state();
+                let ids = if O::DATA_TYPE == DataType::Email {
+                    self.email_ids(client).await?
+                } else {
+                    self.thread_ids()?
+                };
+                let objects = if !ids.is_empty() {
+                    self.get::<O>(client, Some(ids), None).await?.take_list()
+                } else {
+                    Vec::new()
+                };
+                (state, objects)
+            }
+            _ => {
+                let mut response = self.get::<O>(client, None, None).await?;
+                (response.take_state(), response.take_list())
+            }
+        };
+
+        let mut changes = SyncChanges::new(O::DATA_TYPE);
+        changes.full_sync = true;
+        let mut values = Vec::with_capacity(objects.len());
+        for object in objects {
+            if let Some(id) = object.id().map(|id| id.to_string()) {
+                changes.created.push(id.clone());
+                values.push((id, serde_json::to_value(object)?));
+            }
+        }
+        self.store
+            .replace_all(&self.account_id, &O::DATA_TYPE, values, &state)?;
+        Ok(changes)
+    }
+
+    #[maybe_async::maybe_async]
+    async fn get<O: SyncObject>(
+        &self,
+        client: &Client,
+        ids: Option<Vec<String>>,
+        properties: Option<Vec<O::Property>>,
+    ) -> crate::Result<GetResponse<O>> {
+        let mut request = client.build();
+        O::get(&mut request, &self.account_id, ids, properties);
+        request.send_single().await
+    }
+
+    #[maybe_async::maybe_async]
+    async fn email_ids(&self, client: &Client) -> crate::Result<Vec<String>> {
+        let mut ids = Vec::new();
+        loop {
+            let mut request = client.build();
+            request
+                .query_email()
+                .account_id(&self.account_id)
+                .position(ids.len() as i32)
+                .limit(QUERY_PAGE_SIZE)
+                .calculate_total(true);
+            let mut response = request.send_query_email().await?;
+            let page = response.take_ids();
+            // Servers may omit the total and return fewer ids than requested
+            let limit = response.limit().unwrap_or(QUERY_PAGE_SIZE);
+            let is_last_page = page.is_empty() || page.len() < limit;
+            ids.extend(page);
+            if is_last_page || response.total().is_some_and(|total| ids.len() >= total) {
+                return Ok(ids);
+            }
+        }
+    }
+
+    fn thread_ids(&self) -> crate::Result<Vec<String>> {
+        let mut ids = AHashSet::new();
+        for email in self.store.get_all(&self.account_id, &DataType::Email)? {
+            if let Some(thread_id) = email.get("threadId").and_then(|id| id.as_str()) {
+                if !ids.contains(thread_id) {
+                    ids.insert(thread_id.to_string());
+                }
+            }
+        }
+        Ok(ids.into_iter().collect())
+    }
+
+    fn insert_all<O: SyncObject>(&self, objects: Vec<O>) -> crate::Result<()> {
+        for object in objects {
+            if let Some(id) = object.id().map(|id| id.to_string()) {
+                self.store.insert(
+                    &self.account_id,
+                    &O::DATA_TYPE,
+                    &id,
+                    serde_json::to_value(object)?,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn patch<O: SyncObject>(&self, object: O, keys: &[String]) -> crate::Result<()> {
+        let Some(id) = object.id().map(|id| id.to_string()) else {
+            return Ok(());
+        };
+        let mut patch = serde_json::to_value(object)?;
+        let value = match self.store.get(&self.account_id, &O::DATA_TYPE, &id)? {
+            Some(Value::Object(mut stored)) => {
+                for key in keys {
+                    if let Some(value) = patch.get_mut(key.as_str()) {
+                        stored.insert(key.clone(), value.take());
+                    } else {
+                        stored.remove(key.as_str());
+                    }
+                }
+                Value::Object(stored)
+            }
+            _ => patch,
+        };
+        self.store
+            .insert(&self.account_id, &O::DATA_TYPE, &id, value)
+    }
+}
+
+impl SyncChanges {
+    fn new(data_type: DataType) -> Self {
+        SyncChanges {
+            data_type,
+            created: Vec::new(),
+            updated: Vec::new(),
+            destroyed: Vec::new(),
+            full_sync: false,
+        }
+    }
+
+    pub fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    pub fn created(&self) -> &[String] {
+        &self.created
+    }
+
+    pub fn updated(&self) -> &[String] {
+        &self.updated
+    }
+
+    pub fn destroyed(&self) -> &[String] {
+        &self.destroyed
+    }
+
+    /// Whether all objects were fetched again, either because this was the first
+    /// synchronization or because the server could not calculate the changes.
+    pub fn is_full_sync(&self) -> bool {
+        self.full_sync
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.full_sync
+            || !self.created.is_empty()
+            || !self.updated.is_empty()
+            || !self.destroyed.is_empty()
+    }
+}
+
+impl SyncObject for Mailbox<Get> {
+    const DATA_TYPE: DataType = DataType::Mailbox;
+
+    fn changes<'x>(
+        request: &'x mut Request<'_>,
+        account_id: &str,
+        since_state: String,
+    ) -> &'x mut ChangesRequest {
+        request.changes_mailbox(since_state).account_id(account_id)
+    }
+
+    fn get(
+        request: &mut Request<'_>,
+        account_id: &str,
+        ids: Option<Vec<String>>,
+        properties: Option<Vec<mailbox::Property>>,
+    ) {
+        let get_request = request.get_mailbox().account_id(account_id);
+        if let Some(ids) = ids {
+            get_request.ids(ids);
+        }
+        if let Some(mut properties) = properties {
+            properties.push(mailbox::Property::Id);
+            get_request.properties(properties);
+        }
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.id()
+    }
+
+    fn updated_properties(response: &ChangesResponse<Self>) -> Option<Vec<mailbox::Property>> {
+        response
+            .arguments()
+            .updated_properties()
+            .map(|properties| properties.to_vec())
+    }
+}
+
+impl SyncObject for Email<Get> {
+    const DATA_TYPE: DataType = DataType::Email;
+
+    fn changes<'x>(
+        request: &'x mut Request<'_>,
+        account_id: &str,
+        since_state: String,
+    ) -> &'x mut ChangesRequest {
+        request.changes_email(since_state).account_id(account_id)
+    }
+
+    fn get(
+        request: &mut Request<'_>,
+        account_id: &str,
+        ids: Option<Vec<String>>,
+        properties: Option<Vec<email::Property>>,
+    ) {
+        let get_request = request.get_email().account_id(account_id);
+        if let Some(ids) = ids {
+            get_request.ids(ids);
+        }
+        get_request.properties(properties.unwrap_or_else(|| EMAIL_PROPERTIES.to_vec()));
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.id()
+    }
+}
+
+impl SyncObject for Thread {
+    const DATA_TYPE: DataType = DataType::Thread;
+
+    fn changes<'x>(
+        request: &'x mut Request<'_>,
+        account_id: &str,
+        since_state: String,
+    ) -> &'x mut ChangesRequest {
+        request.changes_thread(since_state).account_id(account_id)
+    }
+
+    fn get(
+        request: &mut Request<'_>,
+        account_id: &str,
+        ids: Option<Vec<String>>,
+        _properties: Option<Vec<<Self as Object>::Property>>,
+    ) {
+        let get_request = request.get_thread().account_id(account_id);
+        if let Some(ids) = ids {
+            get_request.ids(ids);
+        }
+    }
+
+    fn id(&self) -> Option<&str> {
+        Some(self.id())
+    }
+}
+
+impl SyncObject for Identity<Get> {
+    const DATA_TYPE: DataType = DataType::Identity;
+
+    fn changes<'x>(
+        request: &'x mut Request<'_>,
+        account_id: &str,
+        since_state: String,
+    ) -> &'x mut ChangesRequest {
+        request.changes_identity(since_state).account_id(account_id)
+    }
+
+    fn get(
+        request: &mut Request<'_>,
+        account_id: &str,
+        ids: Option<Vec<String>>,
+        _properties: Option<Vec<<Self as Object>::Property>>,
+    ) {
+        let get_request = request.get_identity().account_id(account_id);
+        if let Some(ids) = ids {
+            get_request.ids(ids);
+        }
+    }
+
+    fn id(&self) -> Option<&str> {
+        self.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{mailbox::Role, testing::MockServer, DataType};
+
+    use super::{MemoryStore, SyncEngine, SyncStore};
+
+    #[maybe_async::maybe_async]
+    async fn sync_store(store: impl SyncStore) {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let email_id = client
+            .email_import(
+                b"Subject: Lunch\r\n\r\nShall we meet at noon?\r\n".to_vec(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+        client
+            .identity_create("Jane Doe", "jane@example.org")
+            .await
+            .unwrap();
+
+        // First synchronization fetches everything
+        let engine = SyncEngine::new(store, server.account_id()).max_changes(1);
+        let changes = engine.sync(&client).await.unwrap();
+        assert!(changes.iter().all(|changes| changes.is_full_sync()));
+        assert_eq!(engine.mailboxes().unwrap().len(), 1);
+        assert_eq!(engine.identities().unwrap().len(), 1);
+        let email = engine.email(&email_id).unwrap().unwrap();
+        assert_eq!(email.subject(), Some("Lunch"));
+        assert_eq!(email.preview(), Some("Shall we meet at noon?"));
+        let thread = engine.thread(email.thread_id().unwrap()).unwrap().unwrap();
+        assert_eq!(thread.email_ids(), [email_id.as_str()]);
+        assert_eq!(
+            engine.state(&DataType::Email).unwrap(),
+            server.state(DataType::Email)
+        );
+
+        // Later synchronizations only fetch the changes
+        let archive_id = client
+            .mailbox_create("Archive", None::<String>, Role::Archive)
+            .await
+            .unwrap()
+            .take_id();
+        client.mailbox_rename(&inbox_id, "Received").await.unwrap();
+        client
+            .email_set_keyword(&email_id, "$seen", true)
+            .await
+            .unwrap();
+        let changes = engine.sync(&client).await.unwrap();
+        assert!(changes.iter().all(|changes| !changes.is_full_sync()));
+        assert_eq!(changes[0].created(), [archive_id.as_str()]);
+        assert!(changes[0].updated().contains(&inbox_id));
+        assert_eq!(changes[1].updated(), [email_id.as_str()]);
+        assert!(!changes[3].has_changes());
+        let mailbox = engine.mailbox(&inbox_id).unwrap().unwrap();
+        assert_eq!(mailbox.name(), Some("Received"));
+        assert_eq!(mailbox.role(), Role::Inbox);
+        let email = engine.email(&email_id).unwrap().unwrap();
+        assert_eq!(email.keywords(), ["$seen"]);
+
+        client.email_destroy(&email_id).await.unwrap();
+        let changes = engine
+            .sync_data_type(&client, DataType::Email)
+            .await
+            .unwrap();
+        assert_eq!(changes.destroyed(), [email_id.as_str()]);
+        assert!(engine.emails().unwrap().is_empty());
+
+        // Unknown states fall back to a full synchronization
+        engine
+            .store()
+            .set_state(server.account_id(), &DataType::Mailbox, "unknown")
+            .unwrap();
+        let changes = engine
+            .sync_data_type(&client, DataType::Mailbox)
+            .await
+            .unwrap();
+        assert!(changes.is_full_sync());
+        assert_eq!(engine.mailboxes().unwrap().len(), 2);
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn sync_memory() {
+        sync_store(MemoryStore::new()).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn sync_sqlite() {
+        sync_store(super::SqliteStore::open_in_memory().unwrap()).await;
+    }
+}
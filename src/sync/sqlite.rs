@@ -0,0 +1,207 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+
+use crate::{DataType, Error};
+
+use super::SyncStore;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sync_state (
+        account_id TEXT NOT NULL,
+        data_type TEXT NOT NULL,
+        state TEXT NOT NULL,
+        PRIMARY KEY (account_id, data_type)
+    );
+    CREATE TABLE IF NOT EXISTS sync_objects (
+        account_id TEXT NOT NULL,
+        data_type TEXT NOT NULL,
+        id TEXT NOT NULL,
+        object TEXT NOT NULL,
+        PRIMARY KEY (account_id, data_type, id)
+    );
+";
+
+/// [`SyncStore`] that persists the replica in an SQLite database.
+pub struct SqliteStore {
+    conn: parking_lot::Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Connection::open(path)
+            .map_err(sqlite_error)
+            .and_then(Self::from_connection)
+    }
+
+    pub fn open_in_memory() -> crate::Result<Self> {
+        Connection::open_in_memory()
+            .map_err(sqlite_error)
+            .and_then(Self::from_connection)
+    }
+
+    /// Uses an existing connection, creating the sync tables if they do not exist.
+    pub fn from_connection(conn: Connection) -> crate::Result<Self> {
+        conn.execute_batch(SCHEMA).map_err(sqlite_error)?;
+        Ok(SqliteStore { conn: conn.into() })
+    }
+}
+
+impl SyncStore for SqliteStore {
+    fn state(&self, account_id: &str, data_type: &DataType) -> crate::Result<Option<String>> {
+        self.conn
+            .lock()
+            .query_row(
+                "SELECT state FROM sync_state WHERE account_id = ?1 AND data_type = ?2",
+                params![account_id, data_type.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn set_state(&self, account_id: &str, data_type: &DataType, state: &str) -> crate::Result<()> {
+        self.conn
+            .lock()
+            .execute(
+                "INSERT OR REPLACE INTO sync_state (account_id, data_type, state) VALUES (?1, ?2, ?3)",
+                params![account_id, data_type.to_string(), state],
+            )
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn get(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        id: &str,
+    ) -> crate::Result<Option<Value>> {
+        let object: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT object FROM sync_objects WHERE account_id = ?1 AND data_type = ?2 AND id = ?3",
+                params![account_id, data_type.to_string(), id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)?;
+        match object {
+            Some(object) => Ok(Some(serde_json::from_str(&object)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_all(&self, account_id: &str, data_type: &DataType) -> crate::Result<Vec<Value>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn
+            .prepare("SELECT object FROM sync_objects WHERE account_id = ?1 AND data_type = ?2")
+            .map_err(sqlite_error)?;
+        let rows = stmt
+            .query_map(params![account_id, data_type.to_string()], |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(sqlite_error)?;
+
+        let mut objects = Vec::new();
+        for object in rows {
+            objects.push(serde_json::from_str(&object.map_err(sqlite_error)?)?);
+        }
+        Ok(objects)
+    }
+
+    fn insert(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        id: &str,
+        object: Value,
+    ) -> crate::Result<()> {
+        self.conn
+            .lock()
+            .execute(
+                "INSERT OR REPLACE INTO sync_objects (account_id, data_type, id, object) VALUES (?1, ?2, ?3, ?4)",
+                params![account_id, data_type.to_string(), id, object.to_string()],
+            )
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn remove(&self, account_id: &str, data_type: &DataType, id: &str) -> crate::Result<()> {
+        self.conn
+            .lock()
+            .execute(
+                "DELETE FROM sync_objects WHERE account_id = ?1 AND data_type = ?2 AND id = ?3",
+                params![account_id, data_type.to_string(), id],
+            )
+            .map(|_| ())
+            .map_err(sqlite_error)
+    }
+
+    fn clear(&self, account_id: &str, data_type: &DataType) -> crate::Result<()> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        for table in ["sync_objects", "sync_state"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE account_id = ?1 AND data_type = ?2",
+                    table
+                ),
+                params![account_id, data_type.to_string()],
+            )
+            .map_err(sqlite_error)?;
+        }
+        tx.commit().map_err(sqlite_error)
+    }
+
+    fn replace_all(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        objects: Vec<(String, Value)>,
+        state: &str,
+    ) -> crate::Result<()> {
+        let data_type = data_type.to_string();
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction().map_err(sqlite_error)?;
+        tx.execute(
+            "DELETE FROM sync_objects WHERE account_id = ?1 AND data_type = ?2",
+            params![account_id, data_type],
+        )
+        .map_err(sqlite_error)?;
+        {
+            let mut stmt = tx
+                .prepare(
+                    "INSERT OR REPLACE INTO sync_objects (account_id, data_type, id, object) VALUES (?1, ?2, ?3, ?4)",
+                )
+                .map_err(sqlite_error)?;
+            for (id, object) in objects {
+                stmt.execute(params![account_id, data_type, id, object.to_string()])
+                    .map_err(sqlite_error)?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO sync_state (account_id, data_type, state) VALUES (?1, ?2, ?3)",
+            params![account_id, data_type, state],
+        )
+        .map_err(sqlite_error)?;
+        tx.commit().map_err(sqlite_error)
+    }
+}
+
+fn sqlite_error(err: rusqlite::Error) -> Error {
+    Error::Internal(format!("SQLite error: {}", err))
+}
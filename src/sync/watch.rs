@@ -0,0 +1,560 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::pin::Pin;
+
+use ahash::AHashMap;
+use futures_util::{FutureExt, Stream, StreamExt};
+
+use crate::{
+    client::Client,
+    core::{changes::ChangesResponse, error::MethodErrorType, get::GetResponse},
+    email::Email,
+    event_source::PushNotification,
+    identity::Identity,
+    mailbox::Mailbox,
+    thread::Thread,
+    DataType, Error, Get,
+};
+
+use super::SyncObject;
+
+type StateStream<'x> = Pin<Box<dyn Stream<Item = crate::Result<StateChange>> + Send + 'x>>;
+
+enum StateChange {
+    Changed(AHashMap<DataType, String>),
+    /// The push connection was re-established, notifications might have been lost.
+    Reconnected,
+}
+
+/// A change observed by [Client.watch()](crate::client::Client::watch).
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    MailboxCreated(Mailbox),
+    MailboxUpdated(Mailbox),
+    MailboxDestroyed(String),
+    /// Emails created in a mailbox, an email in several mailboxes is reported once per mailbox.
+    EmailsCreated {
+        mailbox_id: String,
+        emails: Vec<Email>,
+    },
+    EmailUpdated(Box<Email>),
+    EmailDestroyed(String),
+    ThreadUpdated(Thread),
+    ThreadDestroyed(String),
+    IdentityCreated(Identity),
+    IdentityUpdated(Identity),
+    IdentityDestroyed(String),
+    /// The server could not calculate the changes of a data type, any local
+    /// state derived from it has to be fetched again.
+    Resync(DataType),
+}
+
+struct ObjectChanges<O> {
+    created: Vec<O>,
+    updated: Vec<O>,
+    destroyed: Vec<String>,
+}
+
+impl Client {
+    /// Returns a stream of typed changes to the objects of an account.
+    ///
+    /// Push notifications are received over WebSocket when the server supports it,
+    /// otherwise an EventSource connection is used. For each notification the
+    /// `/changes` and `/get` calls needed to describe the changes are issued.
+    /// Notifications that arrive while changes are being fetched are coalesced and
+    /// states that were already seen are ignored. EventSource connections are
+    /// re-established using the client's [RetryPolicy](crate::retry::RetryPolicy),
+    /// after which the changes of all the watched types are fetched again.
+    ///
+    /// The supported data types are `Mailbox`, `Email`, `Thread` and `Identity`.
+    /// Emails are fetched with the [EMAIL_PROPERTIES](crate::sync::EMAIL_PROPERTIES).
+    pub async fn watch(
+        &self,
+        account_id: &str,
+        types: impl IntoIterator<Item = DataType>,
+    ) -> crate::Result<impl Stream<Item = crate::Result<WatchEvent>> + Send + '_> {
+        let mut unique_types: Vec<DataType> = Vec::new();
+        for data_type in types {
+            if !matches!(
+                data_type,
+                DataType::Mailbox | DataType::Email | DataType::Thread | DataType::Identity
+            ) {
+                return Err(Error::Internal(format!(
+                    "Watching {} objects is not supported.",
+                    data_type
+                )));
+            } else if !unique_types.contains(&data_type) {
+                unique_types.push(data_type);
+            }
+        }
+        let types = unique_types;
+
+        // Subscribe before reading the states so that no changes are missed
+        let mut push = self.watch_push(account_id, &types).await?;
+        let mut states = AHashMap::with_capacity(types.len());
+        for data_type in &types {
+            states.insert(
+                data_type.clone(),
+                self.watch_state(account_id, data_type).await?,
+            );
+        }
+        let account_id = account_id.to_string();
+
+        Ok(async_stream::stream! {
+            while let Some(result) = push.next().await {
+                let mut changed = match result {
+                    Ok(changed) => changed,
+                    Err(err) => {
+                        yield Err(err);
+                        break;
+                    }
+                };
+
+                // Coalesce notifications that are already waiting
+                while let Some(Some(result)) = push.next().now_or_never() {
+                    match result {
+                        Ok(more_changed) => changed.merge(more_changed),
+                        Err(err) => {
+                            yield Err(err);
+                            return;
+                        }
+                    }
+                }
+
+                for data_type in &types {
+                    if let StateChange::Changed(changed) = &changed {
+                        match changed.get(data_type) {
+                            Some(state) if states.get(data_type) != Some(state) => (),
+                            _ => continue,
+                        }
+                    }
+                    let since_state = states.get(data_type).cloned().unwrap_or_default();
+                    match self.watch_changes(&account_id, data_type, since_state).await {
+                        Ok((events, new_state)) => {
+                            states.insert(data_type.clone(), new_state);
+                            for event in events {
+                                yield Ok(event);
+                            }
+                        }
+                        Err(err) => yield Err(err),
+                    }
+                }
+            }
+        })
+    }
+
+    async fn watch_state(&self, account_id: &str, data_type: &DataType) -> crate::Result<String> {
+        Ok(match data_type {
+            DataType::Mailbox => self
+                .watch_get::<Mailbox<Get>>(account_id, Vec::new())
+                .await?
+                .take_state(),
+            DataType::Email => self
+                .watch_get::<Email<Get>>(account_id, Vec::new())
+                .await?
+                .take_state(),
+            DataType::Thread => self
+                .watch_get::<Thread>(account_id, Vec::new())
+                .await?
+                .take_state(),
+            _ => self
+                .watch_get::<Identity<Get>>(account_id, Vec::new())
+                .await?
+                .take_state(),
+        })
+    }
+
+    async fn watch_changes(
+        &self,
+        account_id: &str,
+        data_type: &DataType,
+        since_state: String,
+    ) -> crate::Result<(Vec<WatchEvent>, String)> {
+        let result = match data_type {
+            DataType::Mailbox => self
+                .object_changes::<Mailbox<Get>>(account_id, since_state)
+                .await
+                .map(|(changes, state)| (changes.mailbox_events(), state)),
+            DataType::Email => self
+                .object_changes::<Email<Get>>(account_id, since_state)
+                .await
+                .map(|(changes, state)| (changes.email_events(), state)),
+            DataType::Thread => self
+                .object_changes::<Thread>(account_id, since_state)
+                .await
+                .map(|(changes, state)| (changes.thread_events(), state)),
+            _ => self
+                .object_changes::<Identity<Get>>(account_id, since_state)
+                .await
+                .map(|(changes, state)| (changes.identity_events(), state)),
+        };
+
+        match result {
+            Err(Error::Method(err)) if err.error() == &MethodErrorType::CannotCalculateChanges => {
+                Ok((
+                    vec![WatchEvent::Resync(data_type.clone())],
+                    self.watch_state(account_id, data_type).await?,
+                ))
+            }
+            result => result,
+        }
+    }
+
+    async fn object_changes<O: SyncObject>(
+        &self,
+        account_id: &str,
+        mut state: String,
+    ) -> crate::Result<(ObjectChanges<O>, String)> {
+        let mut changes = ObjectChanges {
+            created: Vec::new(),
+            updated: Vec::new(),
+            destroyed: Vec::new(),
+        };
+        loop {
+            let mut request = self.build();
+            O::changes(&mut request, account_id, state);
+            let mut response = request.send_single::<ChangesResponse<O>>().await?;
+            let created = response.take_created();
+            let updated = response.take_updated();
+            changes.destroyed.extend(response.take_destroyed());
+
+            if !created.is_empty() || !updated.is_empty() {
+                let objects = self
+                    .watch_get::<O>(
+                        account_id,
+                        created.iter().chain(&updated).cloned().collect(),
+                    )
+                    .await?
+                    .take_list();
+                let (created_objects, updated_objects): (Vec<_>, Vec<_>) =
+                    objects.into_iter().partition(|object| {
+                        object
+                            .id()
+                            .is_some_and(|id| created.iter().any(|c| c == id))
+                    });
+                changes.created.extend(created_objects);
+                changes.updated.extend(updated_objects);
+            }
+
+            state = response.take_new_state();
+            if !response.has_more_changes() {
+                return Ok((changes, state));
+            }
+        }
+    }
+
+    async fn watch_get<O: SyncObject>(
+        &self,
+        account_id: &str,
+        ids: Vec<String>,
+    ) -> crate::Result<GetResponse<O>> {
+        let mut request = self.build();
+        O::get(&mut request, account_id, Some(ids), None);
+        request.send_single().await
+    }
+
+    async fn watch_push(
+        &self,
+        account_id: &str,
+        types: &[DataType],
+    ) -> crate::Result<StateStream<'_>> {
+        #[cfg(feature = "websockets")]
+        if self
+            .session()
+            .websocket_capabilities()
+            .is_some_and(|capabilities| capabilities.supports_push())
+        {
+            return self.watch_push_ws(account_id, types).await;
+        }
+
+        let account_id = account_id.to_string();
+        Ok(Box::pin(
+            self.event_source_reconnect(
+                Some(types.to_vec()),
+                None,
+                None,
+                self.retry_policy().clone(),
+            )
+            .await?
+            .filter_map(move |result| {
+                let result = match result {
+                    Ok(PushNotification::StateChange(mut changes)) => changes
+                        .account_changes(&account_id)
+                        .map(|changes| Ok(StateChange::Changed(changes))),
+                    Ok(PushNotification::Reconnected) => Some(Ok(StateChange::Reconnected)),
+                    Ok(PushNotification::CalendarAlert(_)) => None,
+                    Err(err) => Some(Err(err)),
+                };
+                async move { result }
+            }),
+        ))
+    }
+
+    #[cfg(feature = "websockets")]
+    async fn watch_push_ws(
+        &self,
+        account_id: &str,
+        types: &[DataType],
+    ) -> crate::Result<StateStream<'_>> {
+        use crate::PushObject;
+
+        fn account_changes(
+            push: PushObject,
+            account_id: &str,
+            changes: &mut AHashMap<DataType, String>,
+        ) {
+            match push {
+                PushObject::StateChange { mut changed } => {
+                    changes.extend(changed.remove(account_id).unwrap_or_default())
+                }
+                PushObject::Group { entries } => {
+                    for push in entries {
+                        account_changes(push, account_id, changes);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut push_rx = self.ws_subscribe_push(types).await?;
+        let account_id = account_id.to_string();
+        Ok(Box::pin(async_stream::stream! {
+            while let Some(result) = push_rx.recv().await {
+                match result {
+                    Ok(push) => {
+                        let mut changes = AHashMap::new();
+                        account_changes(push, &account_id, &mut changes);
+                        if !changes.is_empty() {
+                            yield Ok(StateChange::Changed(changes));
+                        }
+                    }
+                    Err(err) => {
+                        yield Err(err);
+                        return;
+                    }
+                }
+            }
+            yield Err(Error::Internal("WebSocket connection closed.".to_string()));
+        }))
+    }
+}
+
+impl StateChange {
+    fn merge(&mut self, other: StateChange) {
+        match (&mut *self, other) {
+            (StateChange::Changed(changed), StateChange::Changed(other)) => changed.extend(other),
+            (_, StateChange::Reconnected) => *self = StateChange::Reconnected,
+            (StateChange::Reconnected, StateChange::Changed(_)) => (),
+        }
+    }
+}
+
+impl ObjectChanges<Mailbox<Get>> {
+    fn mailbox_events(self) -> Vec<WatchEvent> {
+        self.created
+            .into_iter()
+            .map(WatchEvent::MailboxCreated)
+            .chain(self.updated.into_iter().map(WatchEvent::MailboxUpdated))
+            .chain(self.destroyed.into_iter().map(WatchEvent::MailboxDestroyed))
+            .collect()
+    }
+}
+
+impl ObjectChanges<Email<Get>> {
+    fn email_events(self) -> Vec<WatchEvent> {
+        let mut created: Vec<(String, Vec<Email>)> = Vec::new();
+        for email in self.created {
+            for mailbox_id in email.mailbox_ids() {
+                match created.iter_mut().find(|(id, _)| id == mailbox_id) {
+                    Some((_, emails)) => emails.push(email.clone()),
+                    None => created.push((mailbox_id.to_string(), vec![email.clone()])),
+                }
+            }
+        }
+
+        created
+            .into_iter()
+            .map(|(mailbox_id, emails)| WatchEvent::EmailsCreated { mailbox_id, emails })
+            .chain(
+                self.updated
+                    .into_iter()
+                    .map(|email| WatchEvent::EmailUpdated(Box::new(email))),
+            )
+            .chain(self.destroyed.into_iter().map(WatchEvent::EmailDestroyed))
+            .collect()
+    }
+}
+
+impl ObjectChanges<Thread> {
+    fn thread_events(self) -> Vec<WatchEvent> {
+        self.created
+            .into_iter()
+            .chain(self.updated)
+            .map(WatchEvent::ThreadUpdated)
+            .chain(self.destroyed.into_iter().map(WatchEvent::ThreadDestroyed))
+            .collect()
+    }
+}
+
+impl ObjectChanges<Identity<Get>> {
+    fn identity_events(self) -> Vec<WatchEvent> {
+        self.created
+            .into_iter()
+            .map(WatchEvent::IdentityCreated)
+            .chain(self.updated.into_iter().map(WatchEvent::IdentityUpdated))
+            .chain(
+                self.destroyed
+                    .into_iter()
+                    .map(WatchEvent::IdentityDestroyed),
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+
+    use crate::{mailbox::Role, testing::MockServer, DataType};
+
+    use super::WatchEvent;
+
+    async fn next(
+        stream: &mut (impl futures_util::Stream<Item = crate::Result<WatchEvent>> + Unpin),
+    ) -> WatchEvent {
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let mut stream = Box::pin(
+            client
+                .watch(server.account_id(), [DataType::Mailbox, DataType::Email])
+                .await
+                .unwrap(),
+        );
+
+        let inbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let email_id = client
+            .email_import(
+                b"Subject: Lunch\r\n\r\nShall we meet at noon?\r\n".to_vec(),
+                [&inbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+
+        // Both notifications are coalesced
+        match next(&mut stream).await {
+            WatchEvent::MailboxCreated(mailbox) => {
+                assert_eq!(mailbox.id(), Some(inbox_id.as_str()));
+                assert_eq!(mailbox.total_emails(), 1);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match next(&mut stream).await {
+            WatchEvent::EmailsCreated { mailbox_id, emails } => {
+                assert_eq!(mailbox_id, inbox_id);
+                assert_eq!(emails.len(), 1);
+                assert_eq!(emails[0].id(), Some(email_id.as_str()));
+                assert_eq!(emails[0].subject(), Some("Lunch"));
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        client.mailbox_rename(&inbox_id, "Received").await.unwrap();
+        match next(&mut stream).await {
+            WatchEvent::MailboxUpdated(mailbox) => assert_eq!(mailbox.name(), Some("Received")),
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        // Changes made while disconnected are fetched after reconnecting
+        server.close_event_sources();
+        client.mailbox_rename(&inbox_id, "Inbox").await.unwrap();
+        match next(&mut stream).await {
+            WatchEvent::MailboxUpdated(mailbox) => assert_eq!(mailbox.name(), Some("Inbox")),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[cfg(feature = "websockets")]
+    #[tokio::test]
+    async fn watch_ws() {
+        use crate::{client_ws::WebSocketMessage, core::set::SetObject, PushObject};
+
+        let server = MockServer::new();
+        server.listen_ws().await.unwrap();
+        let client = server.connect().await.unwrap();
+        let mut push_stream = client.connect_ws().await.unwrap();
+        client
+            .enable_push_ws(Some([DataType::Email]), None::<&str>)
+            .await
+            .unwrap();
+
+        // The existing connection and its push subscription are reused
+        let mut stream = Box::pin(
+            client
+                .watch(server.account_id(), [DataType::Mailbox])
+                .await
+                .unwrap(),
+        );
+        let mut request = client.build();
+        let create_id = request
+            .set_mailbox()
+            .create()
+            .name("Inbox")
+            .create_id()
+            .unwrap();
+        let mailbox_id = request
+            .send_ws()
+            .await
+            .unwrap()
+            .pop_method_response()
+            .unwrap()
+            .unwrap_set_mailbox()
+            .unwrap()
+            .created(&create_id)
+            .unwrap()
+            .take_id();
+        match next(&mut stream).await {
+            WatchEvent::MailboxCreated(mailbox) => {
+                assert_eq!(mailbox.id(), Some(mailbox_id.as_str()))
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        let message = tokio::time::timeout(Duration::from_secs(5), push_stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        match message {
+            WebSocketMessage::PushNotification(PushObject::StateChange { changed }) => {
+                assert!(changed[server.account_id()].contains_key(&DataType::Mailbox));
+            }
+            message => panic!("Unexpected message: {message:?}"),
+        }
+    }
+}
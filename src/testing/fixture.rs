@@ -0,0 +1,500 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ahash::AHashMap;
+use base64::{engine::general_purpose, Engine};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+
+#[derive(Debug, Clone)]
+pub(crate) enum FixtureMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// A recorded request/response pair. Call ids in JMAP API requests and
+/// responses are stored normalized to their position in `methodCalls`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Body::is_empty")]
+    request: Body,
+    status: u16,
+    #[serde(rename = "contentType")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Body::is_empty")]
+    response: Body,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Body {
+    #[default]
+    Empty,
+    Json(Value),
+    Base64(String),
+}
+
+/// [`Transport`] that forwards requests to another transport and writes every
+/// request/response pair to a fixture file that can be served by a [`ReplayTransport`].
+///
+/// Each exchange is appended to the file as a line of JSON. Streamed responses are
+/// recorded once their body has been read to the end, so streams that are dropped
+/// early such as event source connections are not recorded. `Authorization` headers
+/// are never recorded.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    recorder: Arc<Recorder>,
+}
+
+struct Recorder {
+    path: PathBuf,
+    file: parking_lot::Mutex<Option<File>>,
+}
+
+/// [`Transport`] that serves the responses stored in a fixture file written by
+/// a [`RecordingTransport`].
+///
+/// Requests are matched on their method, URL and body, with the call ids of JMAP
+/// API requests normalized so that requests built with different call ids match.
+/// Identical requests are served in the order they were recorded, once all of
+/// them have been used the last one is served again.
+pub struct ReplayTransport {
+    exchanges: Vec<Exchange>,
+    used: parking_lot::Mutex<Vec<bool>>,
+}
+
+impl FixtureMode {
+    pub(crate) fn into_transport(
+        self,
+        transport: Arc<dyn Transport>,
+    ) -> crate::Result<Arc<dyn Transport>> {
+        Ok(match self {
+            FixtureMode::Record(path) => Arc::new(RecordingTransport::new(transport, path)),
+            FixtureMode::Replay(path) => Arc::new(ReplayTransport::open(path)?),
+        })
+    }
+}
+
+impl RecordingTransport {
+    pub fn new(inner: impl Transport + 'static, path: impl Into<PathBuf>) -> Self {
+        RecordingTransport {
+            inner: Arc::new(inner),
+            recorder: Arc::new(Recorder {
+                path: path.into(),
+                file: Default::default(),
+            }),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.recorder.path
+    }
+}
+
+impl Recorder {
+    fn record(&self, request: &HttpRequest, response: &HttpResponse) -> crate::Result<()> {
+        let mut request_body = Body::new(
+            request.body.as_deref().unwrap_or_default(),
+            content_type(&request.headers),
+        );
+        let call_ids = request_body.normalize_request();
+        let mut response_body = Body::new(&response.body, response.content_type());
+        response_body.map_call_ids(&call_ids.into_iter().zip(normalized_ids()).collect());
+
+        let mut line = serde_json::to_vec(&Exchange {
+            method: request.method.to_string(),
+            url: request.url.clone(),
+            request: request_body,
+            status: response.status.as_u16(),
+            content_type: response.content_type().map(Into::into),
+            response: response_body,
+        })?;
+        line.push(b'\n');
+
+        // The file is truncated when the first exchange is recorded
+        let mut file = self.file.lock();
+        let result = match &mut *file {
+            Some(file) => file.write_all(&line),
+            None => {
+                File::create(&self.path).and_then(|new_file| file.insert(new_file).write_all(&line))
+            }
+        };
+        result.map_err(|err| {
+            crate::Error::Internal(format!(
+                "Failed to write fixture {}: {}",
+                self.path.display(),
+                err
+            ))
+        })
+    }
+}
+
+impl ReplayTransport {
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        std::fs::read(path)
+            .map_err(|err| {
+                crate::Error::Internal(format!(
+                    "Failed to read fixture {}: {}",
+                    path.display(),
+                    err
+                ))
+            })
+            .and_then(|bytes| Self::from_slice(&bytes))
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> crate::Result<Self> {
+        let exchanges = serde_json::Deserializer::from_slice(bytes)
+            .into_iter::<Exchange>()
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ReplayTransport {
+            used: vec![false; exchanges.len()].into(),
+            exchanges,
+        })
+    }
+
+    fn replay(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let mut request_body = Body::new(
+            request.body.as_deref().unwrap_or_default(),
+            content_type(&request.headers),
+        );
+        let call_ids = request_body.normalize_request();
+        let method = request.method.as_str();
+        let matches = self
+            .exchanges
+            .iter()
+            .enumerate()
+            .filter(|(_, exchange)| {
+                exchange.method == method
+                    && exchange.url == request.url
+                    && exchange.request == request_body
+            })
+            .map(|(pos, _)| pos)
+            .collect::<Vec<_>>();
+
+        let mut used = self.used.lock();
+        let pos = matches
+            .iter()
+            .find(|pos| !used[**pos])
+            .or(matches.last())
+            .copied()?;
+        used[pos] = true;
+        drop(used);
+
+        let exchange = &self.exchanges[pos];
+        let mut headers = HeaderMap::new();
+        if let Some(value) = exchange
+            .content_type
+            .as_deref()
+            .and_then(|value| HeaderValue::from_str(value).ok())
+        {
+            headers.insert(CONTENT_TYPE, value);
+        }
+        let mut response_body = exchange.response.clone();
+        response_body.map_call_ids(&normalized_ids().zip(call_ids).collect());
+
+        Some(HttpResponse::new(
+            StatusCode::from_u16(exchange.status).ok()?,
+            headers,
+            response_body.into_bytes(),
+        ))
+    }
+}
+
+#[maybe_async::maybe_async]
+impl Transport for RecordingTransport {
+    async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        let response = self.inner.send(request.clone()).await?;
+        self.recorder.record(&request, &response)?;
+        Ok(response)
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_stream(
+        &self,
+        request: HttpRequest,
+    ) -> crate::Result<HttpResponse<crate::transport::ByteStream>> {
+        use futures_util::StreamExt;
+
+        let response = self.inner.send_stream(request.clone()).await?;
+        let recorder = self.recorder.clone();
+        let status = response.status;
+        let headers = response.headers.clone();
+        let mut stream = response.body;
+
+        Ok(HttpResponse::new(
+            response.status,
+            response.headers,
+            Box::pin(async_stream::stream! {
+                let mut body = Vec::new();
+                while let Some(chunk) = stream.next().await {
+                    match chunk {
+                        Ok(bytes) => {
+                            body.extend_from_slice(&bytes);
+                            yield Ok(bytes);
+                        }
+                        Err(err) => {
+                            yield Err(err);
+                            return;
+                        }
+                    }
+                }
+                if let Err(err) = recorder.record(&request, &HttpResponse::new(status, headers, body)) {
+                    yield Err(err);
+                }
+            }),
+        ))
+    }
+}
+
+#[maybe_async::maybe_async]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        self.replay(&request).ok_or_else(|| {
+            crate::Error::Internal(format!(
+                "No recorded response for {} {}",
+                request.method, request.url
+            ))
+        })
+    }
+}
+
+impl Body {
+    fn new(bytes: &[u8], content_type: Option<&str>) -> Self {
+        if bytes.is_empty() {
+            Body::Empty
+        } else if let Some(value) = content_type
+            .filter(|content_type| content_type.starts_with("application/json"))
+            .and_then(|_| serde_json::from_slice(bytes).ok())
+        {
+            Body::Json(value)
+        } else {
+            Body::Base64(general_purpose::STANDARD.encode(bytes))
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, Body::Empty)
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Body::Empty => Vec::new(),
+            Body::Json(value) => serde_json::to_vec(&value).unwrap_or_default(),
+            Body::Base64(value) => general_purpose::STANDARD.decode(value).unwrap_or_default(),
+        }
+    }
+
+    /// Replaces the call ids of a JMAP API request with their position, returning
+    /// the original call ids in order.
+    fn normalize_request(&mut self) -> Vec<String> {
+        let Body::Json(Value::Object(request)) = self else {
+            return Vec::new();
+        };
+        let Some(Value::Array(method_calls)) = request.get_mut("methodCalls") else {
+            return Vec::new();
+        };
+
+        let mut call_ids = Vec::with_capacity(method_calls.len());
+        let mut id_map = AHashMap::new();
+        for (call, normalized_id) in method_calls.iter_mut().zip(normalized_ids()) {
+            if let Some(Value::String(call_id)) = call.get_mut(2) {
+                let call_id = std::mem::replace(call_id, normalized_id.clone());
+                id_map.insert(call_id.clone(), normalized_id);
+                call_ids.push(call_id);
+            }
+            if let Some(arguments) = call.get_mut(1) {
+                map_result_references(arguments, &id_map);
+            }
+        }
+        call_ids
+    }
+
+    /// Maps the call ids of a JMAP API response.
+    fn map_call_ids(&mut self, id_map: &AHashMap<String, String>) {
+        if let Body::Json(Value::Object(response)) = self {
+            if let Some(Value::Array(method_responses)) = response.get_mut("methodResponses") {
+                for method_response in method_responses {
+                    if let Some(Value::String(call_id)) = method_response.get_mut(2) {
+                        if let Some(mapped_id) = id_map.get(call_id.as_str()) {
+                            *call_id = mapped_id.clone();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn map_result_references(value: &mut Value, id_map: &AHashMap<String, String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(result_of)) = object.get_mut("resultOf") {
+                if let Some(mapped_id) = id_map.get(result_of.as_str()) {
+                    *result_of = mapped_id.clone();
+                }
+            }
+            for value in object.values_mut() {
+                map_result_references(value, id_map);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                map_result_references(value, id_map);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn normalized_ids() -> impl Iterator<Item = String> {
+    (0..).map(|pos| format!("#{}", pos))
+}
+
+fn content_type(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        mailbox::Role,
+        testing::{MockServer, ReplayTransport},
+        transport::{HttpRequest, Transport},
+    };
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn record_replay() {
+        let path = std::env::temp_dir().join(format!("jmap-fixture-{}.json", std::process::id()));
+        let server = MockServer::new();
+        let client = crate::client::Client::new()
+            .credentials("secret")
+            .transport(server.clone())
+            .record(&path)
+            .connect(server.url())
+            .await
+            .unwrap();
+        let mailbox_id = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        let blob_id = client
+            .upload(None, b"hello world".to_vec(), None)
+            .await
+            .unwrap()
+            .take_blob_id();
+
+        let client = crate::client::Client::new()
+            .credentials("secret")
+            .replay(&path)
+            .connect(server.url())
+            .await
+            .unwrap();
+        let result = client.mailbox_get(&mailbox_id, None::<Vec<_>>).await;
+        assert!(matches!(result, Err(crate::Error::Internal(_))));
+        let result = client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap()
+            .take_id();
+        assert_eq!(result, mailbox_id);
+        let result = client
+            .upload(None, b"hello world".to_vec(), None)
+            .await
+            .unwrap()
+            .take_blob_id();
+        assert_eq!(result, blob_id);
+
+        // Call ids are normalized when matching and mapped back in the response
+        let transport = ReplayTransport::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let request = json!({
+            "using": ["urn:ietf:params:jmap:core", "urn:ietf:params:jmap:mail"],
+            "methodCalls": [["Mailbox/set", {
+                "accountId": server.account_id(),
+                "create": {"c0": {"name": "Inbox", "parentId": null, "role": "inbox"}}
+            }, "my-call"]]
+        });
+        let response = transport
+            .send(
+                HttpRequest::post(format!("{}/api/", server.url()))
+                    .with_headers(client.headers().clone())
+                    .with_body(serde_json::to_vec(&request).unwrap()),
+            )
+            .await
+            .unwrap();
+        let response: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(response["methodResponses"][0][2], "my-call");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn record_replay_stream() {
+        use futures_util::StreamExt;
+
+        let path =
+            std::env::temp_dir().join(format!("jmap-fixture-stream-{}.json", std::process::id()));
+        let server = MockServer::new();
+        let client = crate::client::Client::new()
+            .credentials("secret")
+            .transport(server.clone())
+            .record(&path)
+            .connect(server.url())
+            .await
+            .unwrap();
+        let blob_id = client
+            .upload(None, b"hello world".to_vec(), None)
+            .await
+            .unwrap()
+            .take_blob_id();
+        let mut stream = client.download_stream(&blob_id, 0).await.unwrap();
+        let mut contents = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            contents.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(contents, b"hello world");
+
+        let client = crate::client::Client::new()
+            .credentials("secret")
+            .replay(&path)
+            .connect(server.url())
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut stream = client.download_stream(&blob_id, 0).await.unwrap();
+        let mut contents = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            contents.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(contents, b"hello world");
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+const PREVIEW_LENGTH: usize = 256;
+
+/// Extracts the Email properties supported by the mock server from a raw message.
+/// Only top-level headers and a plain text body are understood.
+pub(crate) fn parse_message(raw: &[u8]) -> Option<Map<String, Value>> {
+    let raw = std::str::from_utf8(raw).ok()?;
+    let (headers, body) = split_message(raw);
+
+    let mut email = Map::new();
+    for (name, value) in parse_headers(headers)? {
+        let name = name.to_ascii_lowercase();
+        let (property, value) = match name.as_str() {
+            "subject" => ("subject", Value::String(value)),
+            "from" | "to" | "cc" | "bcc" | "sender" => (name.as_str(), parse_addresses(&value)),
+            "reply-to" => ("replyTo", parse_addresses(&value)),
+            "message-id" => ("messageId", parse_ids(&value)),
+            "in-reply-to" => ("inReplyTo", parse_ids(&value)),
+            "references" => ("references", parse_ids(&value)),
+            "date" => match DateTime::parse_from_rfc2822(&value) {
+                Ok(date) => (
+                    "sentAt",
+                    Value::String(
+                        date.with_timezone(&Utc)
+                            .to_rfc3339_opts(SecondsFormat::Secs, true),
+                    ),
+                ),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        email.insert(property.to_string(), value);
+    }
+    email.insert("preview".to_string(), Value::String(preview(body)));
+    email.insert("hasAttachment".to_string(), Value::Bool(false));
+    email.insert("size".to_string(), Value::from(raw.len()));
+    Some(email)
+}
+
+/// Extracts the MDN properties from a multipart/report message. Only the subject,
+/// the first text part and the disposition notification fields are understood.
+pub(crate) fn parse_mdn(raw: &[u8]) -> Option<Map<String, Value>> {
+    let raw = std::str::from_utf8(raw).ok()?;
+    let (headers, body) = split_message(raw);
+    let headers = parse_headers(headers)?;
+    let boundary = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .and_then(|(_, value)| {
+            value
+                .split(';')
+                .find_map(|param| param.trim().strip_prefix("boundary="))
+        })?
+        .trim_matches('"');
+
+    let mut mdn = Map::new();
+    if let Some((_, subject)) = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("subject"))
+    {
+        mdn.insert("subject".to_string(), subject.as_str().into());
+    }
+
+    let mut has_report = false;
+    let delimiter = format!("--{}", boundary);
+    for part in body.split(delimiter.as_str()).skip(1) {
+        if part.starts_with("--") {
+            break;
+        }
+        let (headers, body) = split_message(part.trim_start_matches(['\r', '\n']));
+        let content_type = parse_headers(headers)?
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.to_ascii_lowercase())
+            .unwrap_or_else(|| "text/plain".to_string());
+
+        if content_type.starts_with("message/disposition-notification") {
+            let mut errors = Vec::new();
+            let mut extension_fields = Map::new();
+            for (name, value) in parse_headers(body.trim())? {
+                let property = match name.to_ascii_lowercase().as_str() {
+                    "reporting-ua" => "reportingUA",
+                    "mdn-gateway" => "mdnGateway",
+                    "original-recipient" => "originalRecipient",
+                    "final-recipient" => "finalRecipient",
+                    "original-message-id" => "originalMessageId",
+                    "disposition" => {
+                        mdn.insert("disposition".to_string(), parse_disposition(&value)?);
+                        continue;
+                    }
+                    "error" => {
+                        errors.push(Value::String(value));
+                        continue;
+                    }
+                    _ => {
+                        extension_fields.insert(name, Value::String(value));
+                        continue;
+                    }
+                };
+                mdn.insert(property.to_string(), Value::String(value));
+            }
+            if !errors.is_empty() {
+                mdn.insert("error".to_string(), Value::Array(errors));
+            }
+            if !extension_fields.is_empty() {
+                mdn.insert(
+                    "extensionFields".to_string(),
+                    Value::Object(extension_fields),
+                );
+            }
+            has_report = true;
+        } else if content_type.starts_with("message/rfc822")
+            || content_type.starts_with("text/rfc822-headers")
+        {
+            mdn.insert("includeOriginalMessage".to_string(), true.into());
+        } else if content_type.starts_with("text/plain") && !mdn.contains_key("textBody") {
+            mdn.insert("textBody".to_string(), body.trim_end().into());
+        }
+    }
+
+    has_report.then_some(mdn)
+}
+
+/// Builds a raw message out of the properties of an Email/set creation.
+pub(crate) fn build_message(email: &Map<String, Value>) -> Vec<u8> {
+    let mut message = String::new();
+    for (property, header) in [
+        ("from", "From"),
+        ("sender", "Sender"),
+        ("to", "To"),
+        ("cc", "Cc"),
+        ("bcc", "Bcc"),
+        ("replyTo", "Reply-To"),
+    ] {
+        if let Some(addresses) = email.get(property).and_then(|v| v.as_array()) {
+            let addresses = addresses
+                .iter()
+                .filter_map(|address| {
+                    let email = address.get("email")?.as_str()?;
+                    Some(match address.get("name").and_then(|v| v.as_str()) {
+                        Some(name) => format!("\"{}\" <{}>", name, email),
+                        None => format!("<{}>", email),
+                    })
+                })
+                .collect::<Vec<_>>();
+            message.push_str(&format!("{}: {}\r\n", header, addresses.join(", ")));
+        }
+    }
+    if let Some(subject) = email.get("subject").and_then(|v| v.as_str()) {
+        message.push_str(&format!("Subject: {}\r\n", subject));
+    }
+    message.push_str("\r\n");
+    message.push_str(&body_text(email));
+    message.into_bytes()
+}
+
+/// Returns the text of the first body part with an inline value.
+pub(crate) fn body_text(email: &Map<String, Value>) -> String {
+    let body_values = email.get("bodyValues").and_then(|v| v.as_object());
+    ["textBody", "htmlBody", "bodyStructure"]
+        .iter()
+        .filter_map(|property| email.get(*property))
+        .flat_map(|parts| match parts {
+            Value::Array(parts) => parts.iter().collect::<Vec<_>>(),
+            part => vec![part],
+        })
+        .find_map(|part| {
+            let part_id = part.get("partId")?.as_str()?;
+            body_values?.get(part_id)?.get("value")?.as_str()
+        })
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub(crate) fn preview(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(PREVIEW_LENGTH)
+        .collect()
+}
+
+fn split_message(raw: &str) -> (&str, &str) {
+    raw.split_once("\r\n\r\n")
+        .or_else(|| raw.split_once("\n\n"))
+        .unwrap_or((raw, ""))
+}
+
+/// Unfolds a header block, or returns `None` if a line is not a valid header.
+fn parse_headers(headers: &str) -> Option<Vec<(String, String)>> {
+    let mut unfolded: Vec<(String, String)> = Vec::new();
+    for line in headers.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = unfolded.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            unfolded.push((name.trim().to_string(), value.trim().to_string()));
+        } else {
+            return None;
+        }
+    }
+    Some(unfolded)
+}
+
+/// Parses a disposition such as `manual-action/MDN-sent-manually; displayed`.
+fn parse_disposition(value: &str) -> Option<Value> {
+    let (modes, type_) = value.split_once(';')?;
+    let (action_mode, sending_mode) = modes.trim().split_once('/')?;
+    let type_ = type_.trim().split('/').next()?;
+    Some(json!({
+        "actionMode": action_mode.trim().to_ascii_lowercase(),
+        "sendingMode": sending_mode.trim().to_ascii_lowercase(),
+        "type": type_.trim().to_ascii_lowercase(),
+    }))
+}
+
+fn parse_addresses(value: &str) -> Value {
+    Value::Array(
+        value
+            .split(',')
+            .filter_map(|address| {
+                let address = address.trim();
+                match address.rsplit_once('<') {
+                    Some((name, email)) => {
+                        let name = name.trim().trim_matches('"').trim();
+                        Some(json!({
+                            "name": (!name.is_empty()).then_some(name),
+                            "email": email.trim_end_matches('>').trim(),
+                        }))
+                    }
+                    None if !address.is_empty() => Some(json!({
+                        "name": null,
+                        "email": address,
+                    })),
+                    None => None,
+                }
+            })
+            .collect(),
+    )
+}
+
+fn parse_ids(value: &str) -> Value {
+    Value::Array(
+        value
+            .split_whitespace()
+            .map(|id| Value::String(id.trim_matches(['<', '>']).to_string()))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn parse_message() {
+        let email = super::parse_message(
+            concat!(
+                "From: \"Jane Doe\" <jane@example.org>\r\n",
+                "To: john@example.org, Bill <bill@example.org>\r\n",
+                "Subject: Lunch\r\n tomorrow?\r\n",
+                "Message-ID: <1234@example.org>\r\n",
+                "Date: Sat, 20 Nov 2021 14:22:01 -0800\r\n",
+                "\r\n",
+                "Shall we meet\r\nat noon?\r\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(email["subject"], "Lunch tomorrow?");
+        assert_eq!(
+            email["from"],
+            json!([{"name": "Jane Doe", "email": "jane@example.org"}])
+        );
+        assert_eq!(
+            email["to"],
+            json!([{"name": null, "email": "john@example.org"},
+                   {"name": "Bill", "email": "bill@example.org"}])
+        );
+        assert_eq!(email["messageId"], json!(["1234@example.org"]));
+        assert_eq!(email["sentAt"], "2021-11-20T22:22:01Z");
+        assert_eq!(email["preview"], "Shall we meet at noon?");
+
+        let raw = super::build_message(&email);
+        assert_eq!(
+            super::parse_message(&raw).unwrap()["from"],
+            json!([{"name": "Jane Doe", "email": "jane@example.org"}])
+        );
+    }
+}
@@ -0,0 +1,1687 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::cmp::Ordering;
+
+use ahash::{AHashMap, AHashSet};
+use base64::{engine::general_purpose, Engine};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use crate::{DataType, URI};
+
+use super::{
+    message,
+    store::{apply_patch, Store},
+    ACCOUNT_ID,
+};
+
+type MethodResult = Result<Vec<(&'static str, Value)>, Value>;
+
+const SUPPORTED_CAPABILITIES: [URI; 6] = [
+    URI::Core,
+    URI::Mail,
+    URI::Submission,
+    URI::Blob,
+    URI::Quota,
+    URI::Mdn,
+];
+
+/// Processes a single JMAP API request against the store.
+pub(crate) struct RequestHandler<'x> {
+    store: &'x mut Store,
+    created_ids: AHashMap<String, String>,
+    responses: Vec<(&'static str, Value, String)>,
+}
+
+impl<'x> RequestHandler<'x> {
+    pub fn new(store: &'x mut Store) -> Self {
+        RequestHandler {
+            store,
+            created_ids: AHashMap::new(),
+            responses: Vec::new(),
+        }
+    }
+
+    /// Returns the method responses, or the problem details of a request level error.
+    pub fn handle(mut self, request: &[u8], session_state: &str) -> Result<Value, Value> {
+        let request: Value = serde_json::from_slice(request).map_err(|_| {
+            problem(
+                "urn:ietf:params:jmap:error:notJSON",
+                "Failed to parse request.",
+            )
+        })?;
+        let using = request
+            .get("using")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| problem("urn:ietf:params:jmap:error:notRequest", "Missing 'using'."))?;
+        let method_calls = request
+            .get("methodCalls")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| {
+                problem(
+                    "urn:ietf:params:jmap:error:notRequest",
+                    "Missing 'methodCalls'.",
+                )
+            })?;
+        for capability in using {
+            if !SUPPORTED_CAPABILITIES
+                .iter()
+                .any(|uri| capability.as_str() == Some(uri.as_ref()))
+            {
+                return Err(problem(
+                    "urn:ietf:params:jmap:error:unknownCapability",
+                    &format!("Unsupported capability {}.", capability),
+                ));
+            }
+        }
+        if let Some(created_ids) = request.get("createdIds").and_then(|v| v.as_object()) {
+            for (create_id, id) in created_ids {
+                if let Some(id) = id.as_str() {
+                    self.created_ids.insert(create_id.clone(), id.to_string());
+                }
+            }
+        }
+
+        for method_call in method_calls {
+            let (name, mut arguments, call_id) = match method_call.as_array().map(|v| v.as_slice())
+            {
+                Some([Value::String(name), arguments @ Value::Object(_), Value::String(id)]) => {
+                    (name.as_str(), arguments.clone(), id.clone())
+                }
+                _ => {
+                    return Err(problem(
+                        "urn:ietf:params:jmap:error:notRequest",
+                        "Invalid method call.",
+                    ))
+                }
+            };
+
+            let result = self.resolve_references(&mut arguments).and_then(|_| {
+                if name != "Core/echo"
+                    && arguments.get("accountId").and_then(|v| v.as_str()) != Some(ACCOUNT_ID)
+                {
+                    return Err(method_error("accountNotFound"));
+                }
+                self.call(name, arguments)
+            });
+            match result {
+                Ok(responses) => {
+                    for (name, response) in responses {
+                        self.responses.push((name, response, call_id.clone()));
+                    }
+                }
+                Err(error) => self.responses.push(("error", error, call_id)),
+            }
+        }
+
+        let mut response = json!({
+            "methodResponses": self
+                .responses
+                .into_iter()
+                .map(|(name, response, call_id)| json!([name, response, call_id]))
+                .collect::<Vec<_>>(),
+            "sessionState": session_state,
+        });
+        if request.get("createdIds").is_some() {
+            response["createdIds"] = json!(self.created_ids);
+        }
+        Ok(response)
+    }
+
+    fn call(&mut self, name: &str, arguments: Value) -> MethodResult {
+        let result = match name {
+            "Core/echo" => ("Core/echo", arguments),
+            "Blob/upload" => ("Blob/upload", self.blob_upload(&arguments)?),
+            "Blob/get" => ("Blob/get", self.blob_get(&arguments)?),
+            "Blob/lookup" => ("Blob/lookup", self.blob_lookup(&arguments)?),
+            "Mailbox/get" => ("Mailbox/get", self.get(DataType::Mailbox, &arguments)?),
+            "Mailbox/changes" => (
+                "Mailbox/changes",
+                self.changes(DataType::Mailbox, &arguments)?,
+            ),
+            "Mailbox/query" => ("Mailbox/query", self.query(DataType::Mailbox, &arguments)?),
+            "Mailbox/set" => ("Mailbox/set", self.set(DataType::Mailbox, &arguments)?),
+            "Thread/get" => ("Thread/get", self.get(DataType::Thread, &arguments)?),
+            "Thread/changes" => (
+                "Thread/changes",
+                self.changes(DataType::Thread, &arguments)?,
+            ),
+            "Email/get" => ("Email/get", self.get(DataType::Email, &arguments)?),
+            "Email/changes" => ("Email/changes", self.changes(DataType::Email, &arguments)?),
+            "Email/query" => ("Email/query", self.query(DataType::Email, &arguments)?),
+            "Email/set" => ("Email/set", self.set(DataType::Email, &arguments)?),
+            "Email/import" => ("Email/import", self.email_import(&arguments)?),
+            "Email/parse" => ("Email/parse", self.email_parse(&arguments)?),
+            "Identity/get" => ("Identity/get", self.get(DataType::Identity, &arguments)?),
+            "Identity/changes" => (
+                "Identity/changes",
+                self.changes(DataType::Identity, &arguments)?,
+            ),
+            "Identity/set" => ("Identity/set", self.set(DataType::Identity, &arguments)?),
+            "EmailSubmission/get" => (
+                "EmailSubmission/get",
+                self.get(DataType::EmailSubmission, &arguments)?,
+            ),
+            "EmailSubmission/changes" => (
+                "EmailSubmission/changes",
+                self.changes(DataType::EmailSubmission, &arguments)?,
+            ),
+            "EmailSubmission/query" => (
+                "EmailSubmission/query",
+                self.query(DataType::EmailSubmission, &arguments)?,
+            ),
+            "EmailSubmission/set" => return self.email_submission_set(&arguments),
+            "Quota/get" => ("Quota/get", self.get(DataType::Quota, &arguments)?),
+            "Quota/changes" => ("Quota/changes", self.changes(DataType::Quota, &arguments)?),
+            "Quota/query" => ("Quota/query", self.query(DataType::Quota, &arguments)?),
+            "MDN/send" => return self.mdn_send(&arguments),
+            "MDN/parse" => ("MDN/parse", self.mdn_parse(&arguments)?),
+            "Mailbox/queryChanges"
+            | "Email/queryChanges"
+            | "EmailSubmission/queryChanges"
+            | "Quota/queryChanges" => return Err(method_error("cannotCalculateChanges")),
+            _ => return Err(method_error("unknownMethod")),
+        };
+        Ok(vec![result])
+    }
+
+    fn get(&self, data_type: DataType, arguments: &Value) -> Result<Value, Value> {
+        let collection = self.store.collection(&data_type).unwrap();
+        let ids = match arguments.get("ids") {
+            None | Some(Value::Null) => collection.ids(),
+            Some(Value::Array(ids)) => ids
+                .iter()
+                .map(|id| id.as_str().map(|id| self.resolve_id(id)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| method_error("invalidArguments"))?,
+            _ => return Err(method_error("invalidArguments")),
+        };
+        let properties = arguments
+            .get("properties")
+            .and_then(|v| v.as_array())
+            .map(|properties| {
+                properties
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .collect::<AHashSet<_>>()
+            });
+        let fetch_body_values = [
+            "fetchTextBodyValues",
+            "fetchHTMLBodyValues",
+            "fetchAllBodyValues",
+        ]
+        .iter()
+        .any(|argument| arguments.get(*argument) == Some(&Value::Bool(true)));
+
+        let mut list = Vec::with_capacity(ids.len());
+        let mut not_found = Vec::new();
+        for id in ids {
+            match self.object(&data_type, &id) {
+                Some(mut object) => {
+                    if let Some(properties) = &properties {
+                        object.retain(|property, _| {
+                            property == "id" || properties.contains(property.as_str())
+                        });
+                    }
+                    if !fetch_body_values {
+                        object.remove("bodyValues");
+                    }
+                    list.push(Value::Object(object));
+                }
+                None => not_found.push(Value::String(id)),
+            }
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "state": collection.state(),
+            "list": list,
+            "notFound": not_found,
+        }))
+    }
+
+    fn changes(&self, data_type: DataType, arguments: &Value) -> Result<Value, Value> {
+        let since_state = arguments
+            .get("sinceState")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| method_error("invalidArguments"))?;
+        let max_changes = match arguments.get("maxChanges") {
+            None | Some(Value::Null) => None,
+            Some(max_changes) => Some(
+                max_changes
+                    .as_u64()
+                    .filter(|max_changes| *max_changes > 0)
+                    .ok_or_else(|| method_error("invalidArguments"))? as usize,
+            ),
+        };
+        let changes = self
+            .store
+            .collection(&data_type)
+            .unwrap()
+            .changes(since_state, max_changes)
+            .ok_or_else(|| method_error("cannotCalculateChanges"))?;
+
+        let mut response = json!({
+            "accountId": ACCOUNT_ID,
+            "oldState": since_state,
+            "newState": changes.new_state,
+            "hasMoreChanges": changes.has_more_changes,
+            "created": changes.created,
+            "updated": changes.updated,
+            "destroyed": changes.destroyed,
+        });
+        match data_type {
+            DataType::Mailbox => response["updatedProperties"] = Value::Null,
+            DataType::Quota => response["updatedProperties"] = json!(["used"]),
+            _ => (),
+        }
+        Ok(response)
+    }
+
+    fn query(&self, data_type: DataType, arguments: &Value) -> Result<Value, Value> {
+        let collection = self.store.collection(&data_type).unwrap();
+        let mut ids = Vec::new();
+        for id in collection.ids() {
+            let object = Value::Object(self.object(&data_type, &id).unwrap());
+            match arguments.get("filter") {
+                Some(filter @ Value::Object(_)) => {
+                    if self.matches(&data_type, &object, filter)? {
+                        ids.push((id, object));
+                    }
+                }
+                None | Some(Value::Null) => ids.push((id, object)),
+                _ => return Err(method_error("invalidArguments")),
+            }
+        }
+
+        if let Some(comparators) = arguments.get("sort").and_then(|v| v.as_array()) {
+            let mut sorted = Vec::with_capacity(ids.len());
+            for (id, object) in ids {
+                let keys = comparators
+                    .iter()
+                    .map(|comparator| {
+                        let is_ascending = comparator
+                            .get("isAscending")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(true);
+                        self.sort_key(&data_type, &object, comparator)
+                            .map(|key| (key, is_ascending))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                sorted.push((id, object, keys));
+            }
+            sorted.sort_by(|(_, _, a), (_, _, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .map(|((a, is_ascending), (b, _))| {
+                        let ordering = compare_values(a, b);
+                        if *is_ascending {
+                            ordering
+                        } else {
+                            ordering.reverse()
+                        }
+                    })
+                    .find(|ordering| *ordering != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+            ids = sorted
+                .into_iter()
+                .map(|(id, object, _)| (id, object))
+                .collect();
+        }
+
+        if data_type == DataType::Email
+            && arguments.get("collapseThreads") == Some(&Value::Bool(true))
+        {
+            let mut seen_threads = AHashSet::new();
+            ids.retain(|(_, object)| seen_threads.insert(object["threadId"].clone()));
+        }
+
+        let ids = ids.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        let total = ids.len();
+        let position = if let Some(anchor) = arguments.get("anchor").and_then(|v| v.as_str()) {
+            let anchor = self.resolve_id(anchor);
+            let index = ids
+                .iter()
+                .position(|id| *id == anchor)
+                .ok_or_else(|| method_error("anchorNotFound"))?;
+            let offset = arguments
+                .get("anchorOffset")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0);
+            (index as i64 + offset).max(0) as usize
+        } else {
+            match arguments.get("position").and_then(|v| v.as_i64()) {
+                Some(position) if position < 0 => (total as i64 + position).max(0) as usize,
+                Some(position) => position as usize,
+                None => 0,
+            }
+            .min(total)
+        };
+        let limit = arguments
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|limit| limit as usize);
+        let ids = ids
+            .into_iter()
+            .skip(position)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect::<Vec<_>>();
+
+        let mut response = json!({
+            "accountId": ACCOUNT_ID,
+            "queryState": collection.state(),
+            "canCalculateChanges": false,
+            "position": position,
+            "ids": ids,
+        });
+        if arguments.get("calculateTotal") == Some(&Value::Bool(true)) {
+            response["total"] = total.into();
+        }
+        if let Some(limit) = limit {
+            response["limit"] = limit.into();
+        }
+        Ok(response)
+    }
+
+    fn set(&mut self, data_type: DataType, arguments: &Value) -> Result<Value, Value> {
+        let old_state = self.store.collection(&data_type).unwrap().state();
+        if let Some(if_in_state) = arguments.get("ifInState").and_then(|v| v.as_str()) {
+            if if_in_state != old_state {
+                return Err(method_error("stateMismatch"));
+            }
+        }
+
+        let mut created = Map::new();
+        let mut not_created = Map::new();
+        if let Some(create) = arguments.get("create").and_then(|v| v.as_object()) {
+            for (create_id, object) in create {
+                let result = match self.resolve_creation_ids(object.clone()) {
+                    Value::Object(object) => self.create(&data_type, object),
+                    _ => Err(set_error("invalidProperties", &[])),
+                };
+                match result {
+                    Ok((id, object)) => {
+                        self.created_ids.insert(create_id.clone(), id);
+                        created.insert(create_id.clone(), object);
+                    }
+                    Err(error) => {
+                        not_created.insert(create_id.clone(), error);
+                    }
+                }
+            }
+        }
+
+        let mut updated = Map::new();
+        let mut not_updated = Map::new();
+        if let Some(update) = arguments.get("update").and_then(|v| v.as_object()) {
+            for (id, patch) in update {
+                let id = self.resolve_id(id);
+                let result = match patch {
+                    Value::Object(patch) => self.update(&data_type, &id, patch.clone()),
+                    _ => Err(set_error("invalidPatch", &[])),
+                };
+                match result {
+                    Ok(()) => {
+                        updated.insert(id, Value::Null);
+                    }
+                    Err(error) => {
+                        not_updated.insert(id, error);
+                    }
+                }
+            }
+        }
+
+        let mut destroyed = Vec::new();
+        let mut not_destroyed = Map::new();
+        if let Some(destroy) = arguments.get("destroy").and_then(|v| v.as_array()) {
+            for id in destroy.iter().filter_map(|id| id.as_str()) {
+                let id = self.resolve_id(id);
+                match self.destroy(&data_type, &id, arguments) {
+                    Ok(()) => destroyed.push(Value::String(id)),
+                    Err(error) => {
+                        not_destroyed.insert(id, error);
+                    }
+                }
+            }
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "oldState": old_state,
+            "newState": self.store.collection(&data_type).unwrap().state(),
+            "created": non_empty(created),
+            "updated": non_empty(updated),
+            "destroyed": (!destroyed.is_empty()).then_some(destroyed),
+            "notCreated": non_empty(not_created),
+            "notUpdated": non_empty(not_updated),
+            "notDestroyed": non_empty(not_destroyed),
+        }))
+    }
+
+    fn create(
+        &mut self,
+        data_type: &DataType,
+        mut object: Map<String, Value>,
+    ) -> Result<(String, Value), Value> {
+        let client_properties = object.keys().cloned().collect::<Vec<_>>();
+        let id = match data_type {
+            DataType::Mailbox => {
+                set_default(&mut object, "parentId", Value::Null);
+                set_default(&mut object, "role", Value::Null);
+                set_default(&mut object, "sortOrder", 0.into());
+                set_default(&mut object, "isSubscribed", false.into());
+                set_default(&mut object, "shareWith", Value::Null);
+                self.validate_mailbox(None, &object)?;
+                self.store.mailboxes.insert(object)
+            }
+            DataType::Email => {
+                self.validate_mailbox_ids(&object)?;
+                let raw = message::build_message(&object);
+                let preview = message::preview(&message::body_text(&object));
+                set_default(&mut object, "size", raw.len().into());
+                set_default(&mut object, "preview", preview.into());
+                set_default(&mut object, "hasAttachment", false.into());
+                let blob_id = self.store.add_blob("message/rfc822", raw);
+                object.insert("blobId".to_string(), blob_id.into());
+                self.add_email(object)
+            }
+            DataType::Identity => {
+                if !object.get("email").is_some_and(|v| v.is_string()) {
+                    return Err(set_error("invalidProperties", &["email"]));
+                }
+                set_default(&mut object, "name", "".into());
+                set_default(&mut object, "replyTo", Value::Null);
+                set_default(&mut object, "bcc", Value::Null);
+                set_default(&mut object, "textSignature", "".into());
+                set_default(&mut object, "htmlSignature", "".into());
+                object.insert("mayDelete".to_string(), true.into());
+                self.store.identities.insert(object)
+            }
+            DataType::EmailSubmission => {
+                let identity = object
+                    .get("identityId")
+                    .and_then(|v| v.as_str())
+                    .and_then(|id| self.store.identities.get(id))
+                    .ok_or_else(|| set_error("invalidProperties", &["identityId"]))?;
+                let email = object
+                    .get("emailId")
+                    .and_then(|v| v.as_str())
+                    .and_then(|id| self.store.emails.get(id))
+                    .ok_or_else(|| set_error("invalidProperties", &["emailId"]))?;
+                let envelope = match object.get("envelope") {
+                    Some(Value::Object(envelope)) => Value::Object(envelope.clone()),
+                    _ => {
+                        let rcpt_to = ["to", "cc", "bcc"]
+                            .iter()
+                            .filter_map(|property| email.get(*property)?.as_array())
+                            .flatten()
+                            .filter_map(|address| address.get("email"))
+                            .map(|email| json!({"email": email, "parameters": null}))
+                            .collect::<Vec<_>>();
+                        json!({
+                            "mailFrom": {"email": identity["email"], "parameters": null},
+                            "rcptTo": rcpt_to,
+                        })
+                    }
+                };
+                let recipients = envelope["rcptTo"]
+                    .as_array()
+                    .map(|rcpt_to| {
+                        rcpt_to
+                            .iter()
+                            .filter_map(|rcpt| rcpt.get("email")?.as_str())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                if recipients.is_empty() {
+                    return Err(set_error("noRecipients", &[]));
+                }
+                let delivery_status = recipients
+                    .into_iter()
+                    .map(|rcpt| {
+                        (
+                            rcpt.to_string(),
+                            json!({
+                                "smtpReply": "250 2.1.5 Queued",
+                                "delivered": "queued",
+                                "displayed": "unknown",
+                            }),
+                        )
+                    })
+                    .collect::<Map<_, _>>();
+
+                object.insert("threadId".to_string(), email["threadId"].clone());
+                object.insert("envelope".to_string(), envelope);
+                object.insert("sendAt".to_string(), now().into());
+                object.insert("undoStatus".to_string(), "pending".into());
+                object.insert("deliveryStatus".to_string(), delivery_status.into());
+                object.insert("dsnBlobIds".to_string(), json!([]));
+                object.insert("mdnBlobIds".to_string(), json!([]));
+                self.store.submissions.insert(object)
+            }
+            _ => return Err(set_error("forbidden", &[])),
+        };
+
+        let mut object = self.object(data_type, &id).unwrap();
+        object.retain(|property, _| {
+            property == "id" || !client_properties.iter().any(|p| p == property)
+        });
+        Ok((id, Value::Object(object)))
+    }
+
+    fn update(
+        &mut self,
+        data_type: &DataType,
+        id: &str,
+        patch: Map<String, Value>,
+    ) -> Result<(), Value> {
+        let current = self
+            .store
+            .collection(data_type)
+            .unwrap()
+            .get(id)
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| set_error("notFound", &[]))?
+            .clone();
+        let server_set: &[&str] = match data_type {
+            DataType::Mailbox => &[
+                "id",
+                "totalEmails",
+                "unreadEmails",
+                "totalThreads",
+                "unreadThreads",
+                "myRights",
+            ],
+            DataType::Email => &["id", "blobId", "threadId", "size"],
+            DataType::Identity => &["id", "email", "mayDelete"],
+            DataType::EmailSubmission => &[
+                "id",
+                "identityId",
+                "emailId",
+                "threadId",
+                "envelope",
+                "sendAt",
+                "deliveryStatus",
+                "dsnBlobIds",
+                "mdnBlobIds",
+            ],
+            _ => return Err(set_error("forbidden", &[])),
+        };
+
+        let mut object = current.clone();
+        for (path, value) in patch {
+            let path = self.resolve_path(&path);
+            let property = path.split('/').next().unwrap_or_default();
+            if server_set.contains(&property) {
+                return Err(set_error("invalidProperties", &[property]));
+            }
+            let value = self.resolve_creation_ids(value);
+            if !apply_patch(&mut object, &path, value) {
+                return Err(set_error("invalidPatch", &[]));
+            }
+        }
+
+        match data_type {
+            DataType::Mailbox => self.validate_mailbox(Some(id), &object)?,
+            DataType::Email => {
+                if let Some(property) = object.keys().chain(current.keys()).find(|property| {
+                    !matches!(property.as_str(), "mailboxIds" | "keywords")
+                        && object.get(*property) != current.get(*property)
+                }) {
+                    return Err(set_error("invalidProperties", &[property.as_str()]));
+                }
+                self.validate_mailbox_ids(&object)?;
+                for mailbox_id in mailbox_ids(current.get("mailboxIds"))
+                    .chain(mailbox_ids(object.get("mailboxIds")))
+                {
+                    self.store.mailboxes.touch(mailbox_id);
+                }
+            }
+            DataType::EmailSubmission
+                if object.get("undoStatus") != current.get("undoStatus")
+                    && (current.get("undoStatus") != Some(&Value::from("pending"))
+                        || object.get("undoStatus") != Some(&Value::from("canceled"))) =>
+            {
+                return Err(set_error("cannotUnsend", &[]));
+            }
+            _ => (),
+        }
+
+        self.store
+            .collection_mut(data_type)
+            .unwrap()
+            .replace(id, Value::Object(object));
+        Ok(())
+    }
+
+    fn destroy(&mut self, data_type: &DataType, id: &str, arguments: &Value) -> Result<(), Value> {
+        if !self.store.collection(data_type).unwrap().contains(id) {
+            return Err(set_error("notFound", &[]));
+        }
+
+        match data_type {
+            DataType::Mailbox => {
+                if self
+                    .store
+                    .mailboxes
+                    .values()
+                    .any(|mailbox| mailbox["parentId"].as_str() == Some(id))
+                {
+                    return Err(set_error("mailboxHasChild", &[]));
+                }
+                let email_ids = self
+                    .store
+                    .emails
+                    .values()
+                    .filter(|email| mailbox_ids(email.get("mailboxIds")).any(|m| m == id))
+                    .map(|email| email["id"].as_str().unwrap().to_string())
+                    .collect::<Vec<_>>();
+                if !email_ids.is_empty() {
+                    if arguments.get("onDestroyRemoveEmails") != Some(&Value::Bool(true)) {
+                        return Err(set_error("mailboxHasEmail", &[]));
+                    }
+                    for email_id in email_ids {
+                        let mut email = self.store.emails.get(&email_id).unwrap().clone();
+                        let mailbox_ids = email["mailboxIds"].as_object_mut().unwrap();
+                        mailbox_ids.remove(id);
+                        if mailbox_ids.is_empty() {
+                            self.destroy_email(&email_id);
+                        } else {
+                            self.store.emails.replace(&email_id, email);
+                        }
+                    }
+                }
+                self.store.mailboxes.remove(id);
+            }
+            DataType::Email => self.destroy_email(id),
+            DataType::Identity | DataType::EmailSubmission => {
+                self.store.collection_mut(data_type).unwrap().remove(id);
+            }
+            _ => return Err(set_error("forbidden", &[])),
+        }
+        Ok(())
+    }
+
+    fn blob_upload(&mut self, arguments: &Value) -> Result<Value, Value> {
+        let mut created = Map::new();
+        let mut not_created = Map::new();
+        for (create_id, upload) in arguments
+            .get("create")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| method_error("invalidArguments"))?
+        {
+            let upload = self.resolve_creation_ids(upload.clone());
+            let result = upload
+                .get("data")
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| set_error("invalidProperties", &["data"]))
+                .and_then(|sources| {
+                    let mut data = Vec::new();
+                    for source in sources {
+                        data.extend(self.blob_data_source(source)?);
+                    }
+                    Ok(data)
+                });
+
+            match result {
+                Ok(data) => {
+                    let content_type = upload
+                        .get("type")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("application/octet-stream");
+                    let size = data.len();
+                    let id = self.store.add_blob(content_type, data);
+                    created.insert(
+                        create_id.clone(),
+                        json!({"id": id, "type": content_type, "size": size}),
+                    );
+                    self.created_ids.insert(create_id.clone(), id);
+                }
+                Err(error) => {
+                    not_created.insert(create_id.clone(), error);
+                }
+            }
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "created": non_empty(created),
+            "notCreated": non_empty(not_created),
+        }))
+    }
+
+    fn blob_data_source(&self, source: &Value) -> Result<Vec<u8>, Value> {
+        if let Some(text) = source.get("data:asText").and_then(|v| v.as_str()) {
+            Ok(text.as_bytes().to_vec())
+        } else if let Some(data) = source.get("data:asBase64").and_then(|v| v.as_str()) {
+            general_purpose::STANDARD
+                .decode(data)
+                .map_err(|_| set_error("invalidProperties", &["data"]))
+        } else if let Some(blob_id) = source.get("blobId").and_then(|v| v.as_str()) {
+            let blob = self
+                .store
+                .blobs
+                .get(blob_id)
+                .ok_or_else(|| set_error("blobNotFound", &[]))?;
+            let offset = source.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+            let end = source
+                .get("length")
+                .and_then(|v| v.as_u64())
+                .map_or(blob.data.len(), |length| offset + length as usize);
+            blob.data
+                .get(offset..end)
+                .map(|data| data.to_vec())
+                .ok_or_else(|| set_error("invalidProperties", &["data"]))
+        } else {
+            Err(set_error("invalidProperties", &["data"]))
+        }
+    }
+
+    fn blob_get(&self, arguments: &Value) -> Result<Value, Value> {
+        let ids = arguments
+            .get("ids")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| method_error("requestTooLarge"))?;
+        let properties = match arguments.get("properties").and_then(|v| v.as_array()) {
+            Some(properties) => properties.iter().filter_map(|v| v.as_str()).collect(),
+            None => vec!["data", "size"],
+        };
+        let offset = arguments
+            .get("offset")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize;
+        let length = arguments
+            .get("length")
+            .and_then(|v| v.as_u64())
+            .map(|length| length as usize);
+
+        let mut list = Vec::new();
+        let mut not_found = Vec::new();
+        for id in ids.iter().filter_map(|id| id.as_str()) {
+            let id = self.resolve_id(id);
+            let Some(blob) = self.store.blobs.get(&id) else {
+                not_found.push(Value::String(id));
+                continue;
+            };
+            let start = offset.min(blob.data.len());
+            let end = length.map_or(blob.data.len(), |length| {
+                (start + length).min(blob.data.len())
+            });
+            let data = &blob.data[start..end];
+            let text = std::str::from_utf8(data).ok();
+
+            let mut object = Map::new();
+            object.insert("id".to_string(), Value::String(id.clone()));
+            for property in &properties {
+                match *property {
+                    "size" => {
+                        object.insert("size".to_string(), blob.data.len().into());
+                    }
+                    "data:asText" => {
+                        object.insert("data:asText".to_string(), text.into());
+                    }
+                    "data:asBase64" => {
+                        object.insert(
+                            "data:asBase64".to_string(),
+                            general_purpose::STANDARD.encode(data).into(),
+                        );
+                    }
+                    "data" => match text {
+                        Some(text) => {
+                            object.insert("data:asText".to_string(), text.into());
+                        }
+                        None => {
+                            object.insert(
+                                "data:asBase64".to_string(),
+                                general_purpose::STANDARD.encode(data).into(),
+                            );
+                        }
+                    },
+                    _ => return Err(method_error("invalidArguments")),
+                }
+            }
+            if properties
+                .iter()
+                .any(|property| property.starts_with("data"))
+            {
+                object.insert(
+                    "isEncodingProblem".to_string(),
+                    (text.is_none() && !properties.contains(&"data:asBase64")).into(),
+                );
+                object.insert(
+                    "isTruncated".to_string(),
+                    (offset > blob.data.len()
+                        || length.is_some_and(|length| offset + length > blob.data.len()))
+                    .into(),
+                );
+            }
+            list.push(Value::Object(object));
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "list": list,
+            "notFound": not_found,
+        }))
+    }
+
+    fn blob_lookup(&self, arguments: &Value) -> Result<Value, Value> {
+        let type_names = arguments
+            .get("typeNames")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| method_error("invalidArguments"))?
+            .iter()
+            .map(|type_name| match type_name.as_str() {
+                Some(type_name @ ("Email" | "Mailbox" | "Thread")) => Ok(type_name),
+                _ => Err(method_error("unknownDataType")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut list = Vec::new();
+        let mut not_found = Vec::new();
+        for id in arguments
+            .get("ids")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| method_error("invalidArguments"))?
+            .iter()
+            .filter_map(|id| id.as_str())
+        {
+            let id = self.resolve_id(id);
+            if !self.store.blobs.contains_key(&id) {
+                not_found.push(Value::String(id));
+                continue;
+            }
+            let emails = self
+                .store
+                .emails
+                .values()
+                .filter(|email| email["blobId"].as_str() == Some(id.as_str()))
+                .collect::<Vec<_>>();
+            let mut matched_ids = Map::new();
+            for type_name in &type_names {
+                let mut ids = Vec::new();
+                for email in &emails {
+                    let email_ids = match *type_name {
+                        "Email" => vec![email["id"].as_str().unwrap_or_default()],
+                        "Mailbox" => mailbox_ids(email.get("mailboxIds")).collect(),
+                        _ => vec![email["threadId"].as_str().unwrap_or_default()],
+                    };
+                    for email_id in email_ids {
+                        if !ids.contains(&email_id) {
+                            ids.push(email_id);
+                        }
+                    }
+                }
+                matched_ids.insert(type_name.to_string(), json!(ids));
+            }
+            list.push(json!({"id": id, "matchedIds": matched_ids}));
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "list": list,
+            "notFound": not_found,
+        }))
+    }
+
+    fn email_import(&mut self, arguments: &Value) -> Result<Value, Value> {
+        let old_state = self.store.emails.state();
+        if let Some(if_in_state) = arguments.get("ifInState").and_then(|v| v.as_str()) {
+            if if_in_state != old_state {
+                return Err(method_error("stateMismatch"));
+            }
+        }
+
+        let mut created = Map::new();
+        let mut not_created = Map::new();
+        for (create_id, import) in arguments
+            .get("emails")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| method_error("invalidArguments"))?
+        {
+            let import = self.resolve_creation_ids(import.clone());
+            let blob_id = import["blobId"].as_str().unwrap_or_default();
+            let result = match self.store.blobs.get(blob_id) {
+                Some(blob) => match message::parse_message(&blob.data) {
+                    Some(mut email) => {
+                        email.insert("blobId".to_string(), blob_id.into());
+                        for property in ["mailboxIds", "keywords", "receivedAt"] {
+                            if let Some(value) = import.get(property).filter(|v| !v.is_null()) {
+                                email.insert(property.to_string(), value.clone());
+                            }
+                        }
+                        self.validate_mailbox_ids(&email)
+                            .map(|_| self.add_email(email))
+                    }
+                    None => Err(set_error("invalidEmail", &[])),
+                },
+                None => Err(set_error("blobNotFound", &[])),
+            };
+
+            match result {
+                Ok(id) => {
+                    let email = self.store.emails.get(&id).unwrap();
+                    created.insert(
+                        create_id.clone(),
+                        json!({
+                            "id": id,
+                            "blobId": email["blobId"],
+                            "threadId": email["threadId"],
+                            "size": email["size"],
+                        }),
+                    );
+                    self.created_ids.insert(create_id.clone(), id);
+                }
+                Err(error) => {
+                    not_created.insert(create_id.clone(), error);
+                }
+            }
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "oldState": old_state,
+            "newState": self.store.emails.state(),
+            "created": non_empty(created),
+            "notCreated": non_empty(not_created),
+        }))
+    }
+
+    fn email_parse(&self, arguments: &Value) -> Result<Value, Value> {
+        let mut parsed = Map::new();
+        let mut not_parsable = Vec::new();
+        let mut not_found = Vec::new();
+        for blob_id in arguments
+            .get("blobIds")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| method_error("invalidArguments"))?
+            .iter()
+            .filter_map(|v| v.as_str())
+        {
+            match self.store.blobs.get(blob_id) {
+                Some(blob) => match message::parse_message(&blob.data) {
+                    Some(email) => {
+                        parsed.insert(blob_id.to_string(), email.into());
+                    }
+                    None => not_parsable.push(blob_id),
+                },
+                None => not_found.push(blob_id),
+            }
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "parsed": non_empty(parsed),
+            "notParsable": not_parsable,
+            "notFound": not_found,
+        }))
+    }
+
+    fn email_submission_set(&mut self, arguments: &Value) -> MethodResult {
+        let response = self.set(DataType::EmailSubmission, arguments)?;
+        let mut responses = vec![("EmailSubmission/set", response)];
+
+        // Apply onSuccessUpdateEmail and onSuccessDestroyEmail to the emails of
+        // the submissions that were created or updated successfully
+        let succeeded = |id: &str| -> Option<String> {
+            let response = &responses[0].1;
+            let id = match id.strip_prefix('#') {
+                Some(create_id) => response["created"].get(create_id)?["id"].as_str()?,
+                None if response["updated"].get(id).is_some() => id,
+                None => return None,
+            };
+            self.store.submissions.get(id)?["emailId"]
+                .as_str()
+                .map(|id| id.to_string())
+        };
+        let mut update = Map::new();
+        let mut destroy = Vec::new();
+        if let Some(on_success) = arguments
+            .get("onSuccessUpdateEmail")
+            .and_then(|v| v.as_object())
+        {
+            for (id, patch) in on_success {
+                if let Some(email_id) = succeeded(id) {
+                    update.insert(email_id, patch.clone());
+                }
+            }
+        }
+        if let Some(on_success) = arguments
+            .get("onSuccessDestroyEmail")
+            .and_then(|v| v.as_array())
+        {
+            for id in on_success.iter().filter_map(|id| id.as_str()) {
+                if let Some(email_id) = succeeded(id) {
+                    destroy.push(email_id);
+                }
+            }
+        }
+
+        if !update.is_empty() || !destroy.is_empty() {
+            let response = self.set(
+                DataType::Email,
+                &json!({"update": update, "destroy": destroy}),
+            )?;
+            responses.push(("Email/set", response));
+        }
+        Ok(responses)
+    }
+
+    fn mdn_send(&mut self, arguments: &Value) -> MethodResult {
+        let identity_email = arguments
+            .get("identityId")
+            .and_then(|v| v.as_str())
+            .and_then(|id| self.store.identities.get(&self.resolve_id(id)))
+            .and_then(|identity| identity["email"].as_str())
+            .map(|email| email.to_string())
+            .ok_or_else(|| method_error("invalidArguments"))?;
+
+        let mut sent = Map::new();
+        let mut not_sent = Map::new();
+        let mut sent_email_ids = AHashMap::new();
+        for (create_id, mdn) in arguments
+            .get("send")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| method_error("invalidArguments"))?
+        {
+            let email_id = mdn
+                .get("forEmailId")
+                .and_then(|v| v.as_str())
+                .map(|id| self.resolve_id(id));
+            let result = match (email_id, mdn.get("disposition")) {
+                (Some(email_id), Some(Value::Object(_))) => {
+                    match self.store.emails.get(&email_id) {
+                        Some(email) if has_keyword(email.get("keywords"), "$mdnsent") => {
+                            Err(set_error("mdnAlreadySent", &[]))
+                        }
+                        Some(email) => {
+                            let mut result = json!({
+                                "finalRecipient": format!("rfc822; {}", identity_email),
+                            });
+                            if let Some(message_id) = email["messageId"][0].as_str() {
+                                result["originalMessageId"] = format!("<{}>", message_id).into();
+                            }
+                            Ok((email_id, result))
+                        }
+                        None => Err(set_error("notFound", &["forEmailId"])),
+                    }
+                }
+                _ => Err(set_error(
+                    "invalidProperties",
+                    &["forEmailId", "disposition"],
+                )),
+            };
+
+            match result {
+                Ok((email_id, result)) => {
+                    sent.insert(create_id.clone(), result);
+                    sent_email_ids.insert(format!("#{}", create_id), email_id);
+                }
+                Err(error) => {
+                    not_sent.insert(create_id.clone(), error);
+                }
+            }
+        }
+
+        let mut responses = vec![(
+            "MDN/send",
+            json!({
+                "accountId": ACCOUNT_ID,
+                "sent": non_empty(sent),
+                "notSent": non_empty(not_sent),
+            }),
+        )];
+
+        // Apply onSuccessUpdateEmail to the emails of the MDNs that were sent
+        let mut update = Map::new();
+        if let Some(on_success) = arguments
+            .get("onSuccessUpdateEmail")
+            .and_then(|v| v.as_object())
+        {
+            for (id, patch) in on_success {
+                if let Some(email_id) = sent_email_ids.get(id) {
+                    update.insert(email_id.clone(), patch.clone());
+                }
+            }
+        }
+        if !update.is_empty() {
+            let response = self.set(DataType::Email, &json!({ "update": update }))?;
+            responses.push(("Email/set", response));
+        }
+        Ok(responses)
+    }
+
+    fn mdn_parse(&self, arguments: &Value) -> Result<Value, Value> {
+        let mut parsed = Map::new();
+        let mut not_parsable = Vec::new();
+        let mut not_found = Vec::new();
+        for blob_id in arguments
+            .get("blobIds")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| method_error("invalidArguments"))?
+            .iter()
+            .filter_map(|v| v.as_str())
+        {
+            match self.store.blobs.get(blob_id) {
+                Some(blob) => match message::parse_mdn(&blob.data) {
+                    Some(mdn) => {
+                        parsed.insert(blob_id.to_string(), mdn.into());
+                    }
+                    None => not_parsable.push(blob_id),
+                },
+                None => not_found.push(blob_id),
+            }
+        }
+
+        Ok(json!({
+            "accountId": ACCOUNT_ID,
+            "parsed": non_empty(parsed),
+            "notParsable": not_parsable,
+            "notFound": not_found,
+        }))
+    }
+
+    /// Returns an object including its computed properties.
+    fn object(&self, data_type: &DataType, id: &str) -> Option<Map<String, Value>> {
+        let mut object = self
+            .store
+            .collection(data_type)?
+            .get(id)?
+            .as_object()?
+            .clone();
+        match data_type {
+            DataType::Mailbox => {
+                let mut total_emails = 0;
+                let mut unread_emails = 0;
+                let mut threads = AHashSet::new();
+                let mut unread_threads = AHashSet::new();
+                for email in self.store.emails.values() {
+                    if mailbox_ids(email.get("mailboxIds")).any(|m| m == id) {
+                        total_emails += 1;
+                        threads.insert(&email["threadId"]);
+                        if !has_keyword(email.get("keywords"), "$seen") {
+                            unread_emails += 1;
+                            unread_threads.insert(&email["threadId"]);
+                        }
+                    }
+                }
+                object.insert("totalEmails".to_string(), total_emails.into());
+                object.insert("unreadEmails".to_string(), unread_emails.into());
+                object.insert("totalThreads".to_string(), threads.len().into());
+                object.insert("unreadThreads".to_string(), unread_threads.len().into());
+                object.insert(
+                    "myRights".to_string(),
+                    json!({
+                        "mayReadItems": true,
+                        "mayAddItems": true,
+                        "mayRemoveItems": true,
+                        "maySetSeen": true,
+                        "maySetKeywords": true,
+                        "mayCreateChild": true,
+                        "mayRename": true,
+                        "mayDelete": true,
+                        "maySubmit": true,
+                    }),
+                );
+            }
+            DataType::Thread => {
+                object.insert("emailIds".to_string(), self.thread_email_ids(id).into());
+            }
+            DataType::Quota => {
+                let used = match object["resourceType"].as_str() {
+                    Some("octets") => self
+                        .store
+                        .emails
+                        .values()
+                        .filter_map(|email| email["size"].as_u64())
+                        .sum(),
+                    _ => self.store.emails.values().count() as u64,
+                };
+                object.insert("used".to_string(), used.into());
+            }
+            _ => (),
+        }
+        Some(object)
+    }
+
+    fn matches(&self, data_type: &DataType, object: &Value, filter: &Value) -> Result<bool, Value> {
+        if let Some(operator) = filter.get("operator") {
+            let conditions = filter
+                .get("conditions")
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| method_error("invalidArguments"))?;
+            let mut matches = Vec::with_capacity(conditions.len());
+            for condition in conditions {
+                matches.push(self.matches(data_type, object, condition)?);
+            }
+            return match operator.as_str() {
+                Some("AND") => Ok(matches.iter().all(|m| *m)),
+                Some("OR") => Ok(matches.iter().any(|m| *m)),
+                Some("NOT") => Ok(!matches.iter().any(|m| *m)),
+                _ => Err(method_error("unsupportedFilter")),
+            };
+        }
+
+        for (condition, value) in filter.as_object().into_iter().flatten() {
+            let matches = match (data_type, condition.as_str()) {
+                (DataType::Mailbox, "parentId" | "role") => object.get(condition) == Some(value),
+                (DataType::Mailbox, "name") => contains(&object["name"], value),
+                (DataType::Mailbox, "hasAnyRole") => {
+                    Some(!object["role"].is_null()) == value.as_bool()
+                }
+                (DataType::Mailbox, "isSubscribed") => object.get(condition) == Some(value),
+                (DataType::Email, "inMailbox") => {
+                    mailbox_ids(object.get("mailboxIds")).any(|id| Some(id) == value.as_str())
+                }
+                (DataType::Email, "inMailboxOtherThan") => {
+                    let other = value.as_array().cloned().unwrap_or_default();
+                    mailbox_ids(object.get("mailboxIds"))
+                        .any(|id| !other.iter().any(|v| v.as_str() == Some(id)))
+                }
+                (DataType::Email, "before") => {
+                    compare_dates(&object["receivedAt"], value) == Ordering::Less
+                }
+                (DataType::Email, "after") => {
+                    compare_dates(&object["receivedAt"], value) != Ordering::Less
+                }
+                (DataType::Email, "minSize") => object["size"].as_u64() >= value.as_u64(),
+                (DataType::Email, "maxSize") => object["size"].as_u64() < value.as_u64(),
+                (DataType::Email, "hasKeyword") => {
+                    has_keyword(object.get("keywords"), value.as_str().unwrap_or_default())
+                }
+                (DataType::Email, "notKeyword") => {
+                    !has_keyword(object.get("keywords"), value.as_str().unwrap_or_default())
+                }
+                (
+                    DataType::Email,
+                    "allInThreadHaveKeyword"
+                    | "someInThreadHaveKeyword"
+                    | "noneInThreadHaveKeyword",
+                ) => {
+                    let keyword = value.as_str().unwrap_or_default();
+                    let mut thread = self
+                        .thread_email_ids(object["threadId"].as_str().unwrap_or_default())
+                        .into_iter()
+                        .filter_map(|id| self.store.emails.get(&id))
+                        .map(|email| has_keyword(email.get("keywords"), keyword));
+                    match condition.as_str() {
+                        "allInThreadHaveKeyword" => thread.all(|k| k),
+                        "someInThreadHaveKeyword" => thread.any(|k| k),
+                        _ => !thread.any(|k| k),
+                    }
+                }
+                (DataType::Email, "hasAttachment") => object.get(condition) == Some(value),
+                (DataType::Email, "id") => value
+                    .as_array()
+                    .is_some_and(|ids| ids.contains(&object["id"])),
+                (DataType::Email, "inThread") => object["threadId"] == *value,
+                (DataType::Email, "sentBefore") => {
+                    compare_dates(&object["sentAt"], value) == Ordering::Less
+                }
+                (DataType::Email, "sentAfter") => {
+                    compare_dates(&object["sentAt"], value) != Ordering::Less
+                }
+                (DataType::Email, "from" | "to" | "cc" | "bcc" | "subject") => {
+                    contains(&object[condition.as_str()], value)
+                }
+                (DataType::Email, "body") => contains(&object["preview"], value),
+                (DataType::Email, "text") => ["from", "to", "cc", "bcc", "subject", "preview"]
+                    .iter()
+                    .any(|property| contains(&object[*property], value)),
+                (DataType::EmailSubmission, "identityIds" | "emailIds" | "threadIds") => {
+                    let property = &condition[..condition.len() - 1];
+                    value
+                        .as_array()
+                        .is_some_and(|ids| ids.contains(&object[property]))
+                }
+                (DataType::EmailSubmission, "undoStatus") => object.get(condition) == Some(value),
+                (DataType::EmailSubmission, "before") => {
+                    compare_dates(&object["sendAt"], value) == Ordering::Less
+                }
+                (DataType::EmailSubmission, "after") => {
+                    compare_dates(&object["sendAt"], value) != Ordering::Less
+                }
+                (DataType::Quota, "name") => contains(&object["name"], value),
+                (DataType::Quota, "scope" | "resourceType") => object.get(condition) == Some(value),
+                (DataType::Quota, "type") => object["types"]
+                    .as_array()
+                    .is_some_and(|types| types.contains(value)),
+                _ => return Err(method_error("unsupportedFilter")),
+            };
+            if !matches {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn sort_key(
+        &self,
+        data_type: &DataType,
+        object: &Value,
+        comparator: &Value,
+    ) -> Result<Value, Value> {
+        let property = comparator
+            .get("property")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        match (data_type, property) {
+            (DataType::Mailbox, "name" | "sortOrder" | "parentId")
+            | (DataType::Email, "receivedAt" | "size" | "subject" | "sentAt")
+            | (DataType::EmailSubmission, "emailId" | "threadId")
+            | (DataType::Quota, "name" | "used") => {
+                Ok(object.get(property).cloned().unwrap_or_default())
+            }
+            (DataType::EmailSubmission, "sentAt") => Ok(object["sendAt"].clone()),
+            (DataType::Email, "from" | "to") => Ok(object[property][0]["email"].clone()),
+            (DataType::Email, "hasKeyword") => Ok(has_keyword(
+                object.get("keywords"),
+                comparator["keyword"].as_str().unwrap_or_default(),
+            )
+            .into()),
+            _ => Err(method_error("unsupportedSort")),
+        }
+    }
+
+    fn validate_mailbox(
+        &self,
+        id: Option<&str>,
+        mailbox: &Map<String, Value>,
+    ) -> Result<(), Value> {
+        if mailbox
+            .get("name")
+            .and_then(|v| v.as_str())
+            .is_none_or(|name| name.is_empty())
+        {
+            return Err(set_error("invalidProperties", &["name"]));
+        }
+
+        // Walk up the tree to make sure the parent exists and no cycle is created
+        let mut parent_id = mailbox.get("parentId").and_then(|v| v.as_str());
+        while let Some(current_id) = parent_id {
+            if Some(current_id) == id {
+                return Err(set_error("invalidProperties", &["parentId"]));
+            }
+            parent_id = self
+                .store
+                .mailboxes
+                .get(current_id)
+                .ok_or_else(|| set_error("invalidProperties", &["parentId"]))?["parentId"]
+                .as_str();
+        }
+        Ok(())
+    }
+
+    fn validate_mailbox_ids(&self, email: &Map<String, Value>) -> Result<(), Value> {
+        if email
+            .get("mailboxIds")
+            .and_then(|v| v.as_object())
+            .is_some_and(|ids| {
+                !ids.is_empty() && ids.keys().all(|id| self.store.mailboxes.contains(id))
+            })
+        {
+            Ok(())
+        } else {
+            Err(set_error("invalidProperties", &["mailboxIds"]))
+        }
+    }
+
+    /// Stores a new email, assigning it to a thread based on its subject.
+    fn add_email(&mut self, mut email: Map<String, Value>) -> String {
+        set_default(&mut email, "keywords", json!({}));
+        set_default(&mut email, "receivedAt", now().into());
+        let subject = thread_subject(&email);
+        let thread_id = self
+            .store
+            .emails
+            .values()
+            .find(|email| thread_subject(email.as_object().unwrap()) == subject)
+            .and_then(|email| email["threadId"].as_str())
+            .map(|thread_id| thread_id.to_string());
+        let thread_id = match thread_id {
+            Some(thread_id) => {
+                self.store.threads.touch(&thread_id);
+                thread_id
+            }
+            None => self.store.threads.insert(Map::new()),
+        };
+        email.insert("threadId".to_string(), thread_id.into());
+        for mailbox_id in mailbox_ids(email.get("mailboxIds")) {
+            self.store.mailboxes.touch(mailbox_id);
+        }
+        self.touch_quotas();
+        self.store.emails.insert(email)
+    }
+
+    fn destroy_email(&mut self, id: &str) {
+        if let Some(email) = self.store.emails.remove(id) {
+            for mailbox_id in mailbox_ids(email.get("mailboxIds")) {
+                self.store.mailboxes.touch(mailbox_id);
+            }
+            let thread_id = email["threadId"].as_str().unwrap_or_default();
+            if self.thread_email_ids(thread_id).is_empty() {
+                self.store.threads.remove(thread_id);
+            } else {
+                self.store.threads.touch(thread_id);
+            }
+            self.touch_quotas();
+        }
+    }
+
+    fn touch_quotas(&mut self) {
+        for id in self.store.quotas.ids() {
+            self.store.quotas.touch(&id);
+        }
+    }
+
+    fn thread_email_ids(&self, thread_id: &str) -> Vec<String> {
+        let mut emails = self
+            .store
+            .emails
+            .ids()
+            .into_iter()
+            .filter_map(|id| {
+                let email = self.store.emails.get(&id)?;
+                (email["threadId"].as_str() == Some(thread_id))
+                    .then(|| (email["receivedAt"].clone(), id))
+            })
+            .collect::<Vec<_>>();
+        emails.sort_by(|(a, _), (b, _)| compare_dates(a, b));
+        emails.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn resolve_references(&self, arguments: &mut Value) -> Result<(), Value> {
+        if let Value::Object(map) = arguments {
+            let references = map
+                .iter()
+                .filter(|(key, value)| key.starts_with('#') && value.get("resultOf").is_some())
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            for key in references {
+                let reference = map.remove(&key).unwrap();
+                let value = (|| {
+                    let call_id = reference.get("resultOf")?.as_str()?;
+                    let name = reference.get("name")?.as_str()?;
+                    let path = reference.get("path")?.as_str()?;
+                    let (_, response, _) = self
+                        .responses
+                        .iter()
+                        .find(|(n, _, id)| id == call_id && *n == name)?;
+                    let path = path
+                        .strip_prefix('/')
+                        .unwrap_or(path)
+                        .split('/')
+                        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+                        .collect::<Vec<_>>();
+                    evaluate_path(response, &path)
+                })()
+                .ok_or_else(|| method_error("invalidResultReference"))?;
+                map.insert(key[1..].to_string(), value);
+            }
+            for value in map.values_mut() {
+                self.resolve_references(value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_creation_ids(&self, value: Value) -> Value {
+        match value {
+            Value::String(string) => Value::String(self.resolve_id(&string)),
+            Value::Array(list) => Value::Array(
+                list.into_iter()
+                    .map(|value| self.resolve_creation_ids(value))
+                    .collect(),
+            ),
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (self.resolve_id(&key), self.resolve_creation_ids(value)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    fn resolve_id(&self, id: &str) -> String {
+        id.strip_prefix('#')
+            .and_then(|create_id| self.created_ids.get(create_id))
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    fn resolve_path(&self, path: &str) -> String {
+        path.split('/')
+            .map(|segment| self.resolve_id(segment))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+fn evaluate_path(value: &Value, path: &[String]) -> Option<Value> {
+    match path.split_first() {
+        None => Some(value.clone()),
+        Some((segment, rest)) if segment == "*" => {
+            let mut result = Vec::new();
+            for item in value.as_array()? {
+                match evaluate_path(item, rest)? {
+                    Value::Array(items) => result.extend(items),
+                    item => result.push(item),
+                }
+            }
+            Some(Value::Array(result))
+        }
+        Some((segment, rest)) => match value {
+            Value::Object(map) => evaluate_path(map.get(segment.as_str())?, rest),
+            Value::Array(list) => evaluate_path(list.get(segment.parse::<usize>().ok()?)?, rest),
+            _ => None,
+        },
+    }
+}
+
+fn mailbox_ids(mailbox_ids: Option<&Value>) -> impl Iterator<Item = &str> {
+    mailbox_ids
+        .and_then(|v| v.as_object())
+        .into_iter()
+        .flat_map(|ids| ids.keys().map(|id| id.as_str()))
+}
+
+fn has_keyword(keywords: Option<&Value>, keyword: &str) -> bool {
+    keywords
+        .and_then(|v| v.get(keyword))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn thread_subject(email: &Map<String, Value>) -> String {
+    let mut subject = email
+        .get("subject")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    while let Some(stripped) = ["re:", "fwd:", "fw:"]
+        .iter()
+        .find_map(|prefix| subject.strip_prefix(prefix))
+    {
+        subject = stripped.trim_start().to_string();
+    }
+    subject
+}
+
+fn contains(value: &Value, text: &Value) -> bool {
+    let text = text.as_str().unwrap_or_default().to_lowercase();
+    match value {
+        Value::String(value) => value.to_lowercase().contains(&text),
+        Value::Array(addresses) => addresses.iter().any(|address| {
+            ["name", "email"]
+                .iter()
+                .any(|property| contains(&address[*property], &Value::String(text.clone())))
+        }),
+        _ => false,
+    }
+}
+
+fn compare_dates(a: &Value, b: &Value) -> Ordering {
+    match (
+        a.as_str()
+            .and_then(|a| DateTime::parse_from_rfc3339(a).ok()),
+        b.as_str()
+            .and_then(|b| DateTime::parse_from_rfc3339(b).ok()),
+    ) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => compare_values(a, b),
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+fn set_default(object: &mut Map<String, Value>, property: &str, value: Value) {
+    object.entry(property).or_insert(value);
+}
+
+fn non_empty(map: Map<String, Value>) -> Option<Map<String, Value>> {
+    (!map.is_empty()).then_some(map)
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn method_error(type_: &str) -> Value {
+    json!({"type": type_})
+}
+
+fn set_error(type_: &str, properties: &[&str]) -> Value {
+    if properties.is_empty() {
+        json!({"type": type_})
+    } else {
+        json!({"type": type_, "properties": properties})
+    }
+}
+
+pub(crate) fn problem(type_: &str, detail: &str) -> Value {
+    json!({"type": type_, "status": 400, "detail": detail})
+}
@@ -0,0 +1,617 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! In-process mock JMAP server for testing code built on top of the [`Client`].
+//!
+//! The [`MockServer`] implements [`Transport`] and keeps an in-memory store of
+//! `Mailbox`, `Email`, `Thread`, `Identity` and `EmailSubmission` objects:
+//!
+//! ```ignore
+//! let server = MockServer::new();
+//! let client = server.connect().await?;
+//! let mailbox = client.mailbox_create("Inbox", None::<String>, Role::Inbox).await?;
+//! ```
+//!
+//! Requests sent to a real server can also be recorded to a fixture file with
+//! [ClientBuilder.record()](crate::client::ClientBuilder::record) and served back
+//! offline with [ClientBuilder.replay()](crate::client::ClientBuilder::replay).
+
+pub(crate) mod fixture;
+pub(crate) mod message;
+pub(crate) mod methods;
+pub(crate) mod store;
+#[cfg(feature = "websockets")]
+pub(crate) mod websocket;
+
+use std::sync::Arc;
+
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE,
+        RANGE,
+    },
+    Method, StatusCode,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    client::Client,
+    transport::{HttpRequest, HttpResponse, Transport},
+    DataType,
+};
+
+pub use self::fixture::{RecordingTransport, ReplayTransport};
+
+use self::{methods::RequestHandler, store::Store};
+
+pub(crate) const ACCOUNT_ID: &str = "a";
+const BASE_URL: &str = "https://jmap.test";
+const USERNAME: &str = "test@example.org";
+const SESSION_STATE: &str = "0";
+
+/// Mock JMAP server that handles requests in-process.
+///
+/// Clones share the same store, so a clone can be handed to
+/// [ClientBuilder.transport()](crate::client::ClientBuilder::transport) while the
+/// original is kept to inspect the server state.
+#[derive(Clone, Default)]
+pub struct MockServer {
+    inner: Arc<parking_lot::Mutex<Inner>>,
+}
+
+struct Inner {
+    store: Store,
+    #[cfg(feature = "async")]
+    subscribers: Vec<Subscriber>,
+    #[cfg(feature = "async")]
+    last_event_id: u64,
+    #[cfg(feature = "websockets")]
+    ws_url: Option<String>,
+    #[cfg(feature = "websockets")]
+    ws_control: tokio::sync::watch::Sender<websocket::WsControl>,
+}
+
+#[cfg(feature = "async")]
+struct Subscriber {
+    types: Option<Vec<DataType>>,
+    close_after_state: bool,
+    websocket: bool,
+    tx: tokio::sync::mpsc::UnboundedSender<String>,
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn url(&self) -> &'static str {
+        BASE_URL
+    }
+
+    pub fn account_id(&self) -> &'static str {
+        ACCOUNT_ID
+    }
+
+    /// Connects a new [`Client`] to this server.
+    #[maybe_async::maybe_async]
+    pub async fn connect(&self) -> crate::Result<Client> {
+        Client::new()
+            .credentials((USERNAME, "secret"))
+            .transport(self.clone())
+            .connect(BASE_URL)
+            .await
+    }
+
+    /// Closes all the open EventSource connections.
+    #[cfg(feature = "async")]
+    pub fn close_event_sources(&self) {
+        self.inner
+            .lock()
+            .subscribers
+            .retain(|subscriber| subscriber.websocket);
+    }
+
+    /// Returns the current state string of a data type.
+    pub fn state(&self, data_type: DataType) -> Option<String> {
+        self.inner
+            .lock()
+            .store
+            .collection(&data_type)
+            .map(|collection| collection.state())
+    }
+
+    /// Returns the stored representation of an object, excluding computed properties.
+    pub fn object(&self, data_type: DataType, id: &str) -> Option<Value> {
+        self.inner
+            .lock()
+            .store
+            .collection(&data_type)?
+            .get(id)
+            .cloned()
+    }
+
+    fn session(&self) -> Value {
+        #[allow(unused_mut)]
+        let mut session = json!({
+            "capabilities": {
+                "urn:ietf:params:jmap:core": {
+                    "maxSizeUpload": 50000000,
+                    "maxConcurrentUpload": 4,
+                    "maxSizeRequest": 10000000,
+                    "maxConcurrentRequests": 4,
+                    "maxCallsInRequest": 16,
+                    "maxObjectsInGet": 500,
+                    "maxObjectsInSet": 500,
+                    "collationAlgorithms": ["i;ascii-casemap"]
+                },
+                "urn:ietf:params:jmap:mail": {},
+                "urn:ietf:params:jmap:submission": {},
+                "urn:ietf:params:jmap:blob": {},
+                "urn:ietf:params:jmap:quota": {},
+                "urn:ietf:params:jmap:mdn": {}
+            },
+            "accounts": {
+                ACCOUNT_ID: {
+                    "name": USERNAME,
+                    "isPersonal": true,
+                    "isReadOnly": false,
+                    "accountCapabilities": {
+                        "urn:ietf:params:jmap:mail": {},
+                        "urn:ietf:params:jmap:submission": {},
+                        "urn:ietf:params:jmap:blob": {
+                            "maxSizeBlobSet": null,
+                            "maxDataSources": 64,
+                            "supportedTypeNames": ["Email", "Mailbox", "Thread"],
+                            "supportedDigestAlgorithms": []
+                        },
+                        "urn:ietf:params:jmap:quota": {},
+                        "urn:ietf:params:jmap:mdn": {}
+                    }
+                }
+            },
+            "primaryAccounts": {
+                "urn:ietf:params:jmap:mail": ACCOUNT_ID,
+                "urn:ietf:params:jmap:submission": ACCOUNT_ID
+            },
+            "username": USERNAME,
+            "apiUrl": format!("{BASE_URL}/api/"),
+            "downloadUrl": format!("{BASE_URL}/download/{{accountId}}/{{blobId}}/{{name}}?accept={{type}}"),
+            "uploadUrl": format!("{BASE_URL}/upload/{{accountId}}/"),
+            "eventSourceUrl": format!("{BASE_URL}/eventsource/?types={{types}}&closeafter={{closeafter}}&ping={{ping}}"),
+            "state": SESSION_STATE
+        });
+
+        #[cfg(feature = "websockets")]
+        if let Some(ws_url) = &self.inner.lock().ws_url {
+            session["capabilities"]["urn:ietf:params:jmap:websocket"] = json!({
+                "url": ws_url,
+                "supportsPush": true
+            });
+        }
+
+        session
+    }
+
+    /// Returns the method responses, or the problem details of a request level error.
+    fn handle_request(&self, body: &[u8]) -> Result<Value, Value> {
+        let mut inner = self.inner.lock();
+        let states = inner.store.states();
+        let result = RequestHandler::new(&mut inner.store).handle(body, SESSION_STATE);
+        let changed = inner
+            .store
+            .states()
+            .into_iter()
+            .filter(|state| !states.contains(state))
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            inner.notify(changed);
+        }
+        result
+    }
+
+    fn api(&self, body: &[u8]) -> HttpResponse {
+        match self.handle_request(body) {
+            Ok(response) => json_response(StatusCode::OK, &response),
+            Err(problem) => json_response(StatusCode::BAD_REQUEST, &problem),
+        }
+    }
+
+    fn upload(&self, account_id: &str, content_type: &str, body: Vec<u8>) -> HttpResponse {
+        if account_id != ACCOUNT_ID {
+            return empty_response(StatusCode::NOT_FOUND);
+        }
+        let size = body.len();
+        let blob_id = self.inner.lock().store.add_blob(content_type, body);
+        json_response(
+            StatusCode::CREATED,
+            &json!({
+                "accountId": ACCOUNT_ID,
+                "blobId": blob_id,
+                "type": content_type,
+                "size": size,
+            }),
+        )
+    }
+
+    fn download(
+        &self,
+        account_id: &str,
+        blob_id: &str,
+        name: &str,
+        query: &str,
+        range: Option<&str>,
+    ) -> HttpResponse {
+        let inner = self.inner.lock();
+        match inner.store.blobs.get(blob_id) {
+            Some(blob) if account_id == ACCOUNT_ID => {
+                let content_type = form_urlencoded::parse(query.as_bytes())
+                    .find(|(name, _)| name == "accept")
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap_or_else(|| blob.content_type.clone());
+                let mut headers = HeaderMap::new();
+                if let Ok(content_type) = HeaderValue::from_str(&content_type) {
+                    headers.insert(CONTENT_TYPE, content_type);
+                }
+                if let Some(Ok(disposition)) =
+                    form_urlencoded::parse(name.as_bytes())
+                        .next()
+                        .map(|(name, _)| {
+                            HeaderValue::from_str(&format!("attachment; filename=\"{name}\""))
+                        })
+                {
+                    headers.insert(CONTENT_DISPOSITION, disposition);
+                }
+                // Only open-ended ranges are supported
+                match range
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| range.strip_suffix('-'))
+                    .and_then(|offset| offset.parse::<usize>().ok())
+                {
+                    Some(offset) if offset < blob.data.len() => {
+                        headers.insert(
+                            CONTENT_RANGE,
+                            HeaderValue::from_str(&format!(
+                                "bytes {}-{}/{}",
+                                offset,
+                                blob.data.len() - 1,
+                                blob.data.len()
+                            ))
+                            .unwrap(),
+                        );
+                        HttpResponse::new(
+                            StatusCode::PARTIAL_CONTENT,
+                            headers,
+                            blob.data[offset..].to_vec(),
+                        )
+                    }
+                    Some(_) => empty_response(StatusCode::RANGE_NOT_SATISFIABLE),
+                    None => HttpResponse::new(StatusCode::OK, headers, blob.data.clone()),
+                }
+            }
+            _ => empty_response(StatusCode::NOT_FOUND),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    fn event_source(&self, query: &str) -> HttpResponse<crate::transport::ByteStream> {
+        let mut types = None;
+        let mut close_after_state = false;
+        let mut ping = 0;
+        for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+            match name.as_ref() {
+                "types" if value != "*" => {
+                    types = Some(
+                        value
+                            .split(',')
+                            .filter_map(|t| serde_json::from_value(Value::from(t)).ok())
+                            .collect(),
+                    );
+                }
+                "closeafter" => close_after_state = value == "state",
+                "ping" => ping = value.parse::<u64>().unwrap_or(0),
+                _ => (),
+            }
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        self.inner.lock().subscribers.push(Subscriber {
+            types,
+            close_after_state,
+            websocket: false,
+            tx,
+        });
+
+        let ping = std::time::Duration::from_secs(ping);
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        HttpResponse::new(
+            StatusCode::OK,
+            headers,
+            Box::pin(async_stream::stream! {
+                loop {
+                    let event = if ping.is_zero() {
+                        rx.recv().await
+                    } else {
+                        match tokio::time::timeout(ping, rx.recv()).await {
+                            Ok(event) => event,
+                            Err(_) => Some(format!(
+                                "event: ping\ndata: {{\"interval\":{}}}\n\n",
+                                ping.as_secs()
+                            )),
+                        }
+                    };
+                    match event {
+                        Some(event) => yield Ok(bytes::Bytes::from(event)),
+                        None => break,
+                    }
+                }
+            }),
+        )
+    }
+}
+
+impl Inner {
+    #[cfg(feature = "async")]
+    fn notify(&mut self, changed: Vec<(DataType, String)>) {
+        let mut subscribers = std::mem::take(&mut self.subscribers);
+        subscribers.retain(|subscriber| {
+            let changed = changed
+                .iter()
+                .filter(|(data_type, _)| {
+                    subscriber
+                        .types
+                        .as_ref()
+                        .is_none_or(|types| types.contains(data_type))
+                })
+                .cloned()
+                .collect::<ahash::AHashMap<_, _>>();
+            if changed.is_empty() {
+                return !subscriber.tx.is_closed();
+            }
+
+            self.last_event_id += 1;
+            let event = if subscriber.websocket {
+                json!({
+                    "@type": "StateChange",
+                    "changed": { ACCOUNT_ID: changed },
+                    "pushState": self.last_event_id.to_string(),
+                })
+                .to_string()
+            } else {
+                format!(
+                    "event: state\nid: {}\ndata: {}\n\n",
+                    self.last_event_id,
+                    json!({
+                        "@type": "StateChange",
+                        "changed": { ACCOUNT_ID: changed },
+                    })
+                )
+            };
+            subscriber.tx.send(event).is_ok() && !subscriber.close_after_state
+        });
+        self.subscribers = subscribers;
+    }
+
+    #[cfg(feature = "blocking")]
+    fn notify(&mut self, _changed: Vec<(DataType, String)>) {}
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Inner {
+            store: Store::new(),
+            #[cfg(feature = "async")]
+            subscribers: Vec::new(),
+            #[cfg(feature = "async")]
+            last_event_id: 0,
+            #[cfg(feature = "websockets")]
+            ws_url: None,
+            #[cfg(feature = "websockets")]
+            ws_control: tokio::sync::watch::Sender::new(websocket::WsControl::Open),
+        }
+    }
+}
+
+#[maybe_async::maybe_async]
+impl Transport for MockServer {
+    async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        let Some(url) = request.url.strip_prefix(BASE_URL) else {
+            return Ok(empty_response(StatusCode::NOT_FOUND));
+        };
+        if !request.headers.contains_key(AUTHORIZATION) {
+            return Ok(empty_response(StatusCode::UNAUTHORIZED));
+        }
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let path = path.split('/').skip(1).collect::<Vec<_>>();
+
+        Ok(match (&request.method, path.as_slice()) {
+            (&Method::GET, [".well-known", "jmap"]) => {
+                json_response(StatusCode::OK, &self.session())
+            }
+            (&Method::POST, ["api", ""]) => self.api(request.body.as_deref().unwrap_or_default()),
+            (&Method::POST, ["upload", account_id, ""]) => self.upload(
+                account_id,
+                request
+                    .headers
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("application/octet-stream"),
+                request.body.unwrap_or_default(),
+            ),
+            (&Method::GET, ["download", account_id, blob_id, name]) => self.download(
+                account_id,
+                blob_id,
+                name,
+                query,
+                request.headers.get(RANGE).and_then(|v| v.to_str().ok()),
+            ),
+            _ => empty_response(StatusCode::NOT_FOUND),
+        })
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_stream(
+        &self,
+        request: HttpRequest,
+    ) -> crate::Result<HttpResponse<crate::transport::ByteStream>> {
+        match request.url.strip_prefix(BASE_URL) {
+            Some(url)
+                if url.starts_with("/eventsource/")
+                    && request.headers.contains_key(AUTHORIZATION) =>
+            {
+                let query = url.split_once('?').map(|(_, query)| query).unwrap_or("");
+                Ok(self.event_source(query))
+            }
+            _ => {
+                let response = self.send(request).await?;
+                let body = bytes::Bytes::from(response.body);
+                Ok(HttpResponse::new(
+                    response.status,
+                    response.headers,
+                    Box::pin(futures_util::stream::once(async move { Ok(body) })),
+                ))
+            }
+        }
+    }
+}
+
+fn json_response(status: StatusCode, body: &Value) -> HttpResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    HttpResponse::new(
+        status,
+        headers,
+        serde_json::to_vec(body).unwrap_or_default(),
+    )
+}
+
+fn empty_response(status: StatusCode) -> HttpResponse {
+    HttpResponse::new(status, HeaderMap::new(), Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::set::SetObject, mailbox, testing::MockServer, DataType};
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn request_references() {
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+
+        // Creation ids and result references are resolved within a request
+        let mut request = client.build();
+        let set_request = request.set_mailbox();
+        let parent_id = set_request.create().name("Drafts").create_id().unwrap();
+        set_request.create().name("Sent").parent_id_ref(&parent_id);
+        let query_request = request.query_mailbox();
+        query_request.filter(mailbox::query::Filter::name("sent"));
+        let reference = query_request.result_reference();
+        request.get_mailbox().ids_ref(reference);
+        let mut response = request.send().await.unwrap();
+        let sent = response
+            .pop_method_response()
+            .unwrap()
+            .unwrap_get_mailbox()
+            .unwrap()
+            .take_list()
+            .pop()
+            .unwrap();
+        let drafts_id = response
+            .method_response_by_pos(0)
+            .unwrap_set_mailbox()
+            .unwrap()
+            .created(&parent_id)
+            .unwrap()
+            .take_id();
+        assert_eq!(sent.parent_id(), Some(drafts_id.as_str()));
+
+        // Successful submissions update the email in an implicit Email/set call
+        let email_id = client
+            .email_import(
+                b"From: john@example.org\r\nTo: jane@example.org\r\n\r\nHi!".to_vec(),
+                [&drafts_id],
+                Some(["$draft"]),
+                None,
+            )
+            .await
+            .unwrap()
+            .take_id();
+        let identity_id = client
+            .identity_create("John Doe", "john@example.org")
+            .await
+            .unwrap()
+            .take_id();
+        let mut request = client.build();
+        let set_request = request.set_email_submission();
+        let create_id = set_request
+            .create()
+            .email_id(&email_id)
+            .identity_id(&identity_id)
+            .create_id()
+            .unwrap();
+        set_request
+            .arguments()
+            .on_success_update_email(&create_id)
+            .keyword("$draft", false)
+            .mailbox_id(&drafts_id, false)
+            .mailbox_id(sent.id().unwrap(), true);
+        let mut response = request.send().await.unwrap();
+        assert_eq!(response.method_responses().len(), 2);
+        response
+            .pop_method_response()
+            .unwrap()
+            .unwrap_set_email()
+            .unwrap()
+            .updated(&email_id)
+            .unwrap();
+        let email = server.object(DataType::Email, &email_id).unwrap();
+        assert_eq!(email["keywords"], serde_json::json!({}));
+        assert_eq!(
+            email["mailboxIds"],
+            serde_json::json!({ sent.id().unwrap(): true })
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn event_source() {
+        use crate::{event_source::PushNotification, mailbox::Role};
+        use futures_util::StreamExt;
+
+        let server = MockServer::new();
+        let client = server.connect().await.unwrap();
+        let mut stream = client
+            .event_source(Some([DataType::Mailbox]), true, None, None)
+            .await
+            .unwrap();
+
+        client
+            .identity_create("John Doe", "john@example.org")
+            .await
+            .unwrap();
+        client
+            .mailbox_create("Inbox", None::<String>, Role::Inbox)
+            .await
+            .unwrap();
+        match stream.next().await.unwrap().unwrap() {
+            PushNotification::StateChange(mut changes) => {
+                let changes = changes.account_changes(server.account_id()).unwrap();
+                assert_eq!(changes.len(), 1);
+                assert_eq!(
+                    changes.get(&DataType::Mailbox),
+                    server.state(DataType::Mailbox).as_ref()
+                );
+            }
+            notification => panic!("Unexpected notification: {notification:?}"),
+        }
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,338 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use ahash::AHashMap;
+use serde_json::{json, Map, Value};
+
+use crate::DataType;
+
+/// Objects of a single data type along with their change log.
+pub(crate) struct Collection {
+    prefix: &'static str,
+    next_id: u64,
+    state: u64,
+    objects: AHashMap<String, (u64, Value)>,
+    changes: Vec<Change>,
+}
+
+struct Change {
+    state: u64,
+    id: String,
+    kind: ChangeKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChangeKind {
+    Created,
+    Updated,
+    Destroyed,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Changes {
+    pub new_state: String,
+    pub has_more_changes: bool,
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub destroyed: Vec<String>,
+}
+
+pub(crate) struct Blob {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+pub(crate) struct Store {
+    pub mailboxes: Collection,
+    pub emails: Collection,
+    pub threads: Collection,
+    pub identities: Collection,
+    pub submissions: Collection,
+    pub quotas: Collection,
+    pub blobs: AHashMap<String, Blob>,
+    next_blob_id: u64,
+}
+
+impl Collection {
+    pub fn new(prefix: &'static str) -> Self {
+        Collection {
+            prefix,
+            next_id: 0,
+            state: 0,
+            objects: AHashMap::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> String {
+        self.state.to_string()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        self.objects.get(id).map(|(_, value)| value)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.objects.contains_key(id)
+    }
+
+    /// Returns all ids in creation order.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids = self
+            .objects
+            .iter()
+            .map(|(id, (seq, _))| (*seq, id))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.into_iter().map(|(_, id)| id.clone()).collect()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.objects.values().map(|(_, value)| value)
+    }
+
+    pub fn next_id(&self) -> String {
+        format!("{}{}", self.prefix, self.next_id)
+    }
+
+    pub fn insert(&mut self, mut object: Map<String, Value>) -> String {
+        let id = self.next_id();
+        object.insert("id".to_string(), Value::String(id.clone()));
+        self.objects
+            .insert(id.clone(), (self.next_id, Value::Object(object)));
+        self.next_id += 1;
+        self.log(id.clone(), ChangeKind::Created);
+        id
+    }
+
+    pub fn replace(&mut self, id: &str, object: Value) {
+        if let Some((_, value)) = self.objects.get_mut(id) {
+            *value = object;
+            self.log(id.to_string(), ChangeKind::Updated);
+        }
+    }
+
+    /// Records an update for an object whose computed properties changed.
+    pub fn touch(&mut self, id: &str) {
+        if self.objects.contains_key(id) {
+            self.log(id.to_string(), ChangeKind::Updated);
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Value> {
+        let (_, value) = self.objects.remove(id)?;
+        self.log(id.to_string(), ChangeKind::Destroyed);
+        Some(value)
+    }
+
+    fn log(&mut self, id: String, kind: ChangeKind) {
+        self.state += 1;
+        self.changes.push(Change {
+            state: self.state,
+            id,
+            kind,
+        });
+    }
+
+    /// Returns the changes made after `since_state`, or `None` if the state is unknown.
+    pub fn changes(&self, since_state: &str, max_changes: Option<usize>) -> Option<Changes> {
+        let since_state = since_state.parse::<u64>().ok()?;
+        if since_state > self.state {
+            return None;
+        }
+
+        let mut changed_ids: Vec<(&str, ChangeKind, ChangeKind)> = Vec::new();
+        let mut result = Changes {
+            new_state: self.state(),
+            ..Default::default()
+        };
+        let mut last_state = since_state;
+        for change in self.changes.iter().filter(|c| c.state > since_state) {
+            if let Some(entry) = changed_ids.iter_mut().find(|(id, _, _)| *id == change.id) {
+                entry.2 = change.kind;
+            } else if max_changes.is_some_and(|max| changed_ids.len() == max) {
+                result.new_state = last_state.to_string();
+                result.has_more_changes = true;
+                break;
+            } else {
+                changed_ids.push((&change.id, change.kind, change.kind));
+            }
+            last_state = change.state;
+        }
+
+        for (id, first, last) in changed_ids {
+            match (first, last) {
+                (ChangeKind::Created, ChangeKind::Destroyed) => (),
+                (ChangeKind::Created, _) => result.created.push(id.to_string()),
+                (_, ChangeKind::Destroyed) => result.destroyed.push(id.to_string()),
+                _ => result.updated.push(id.to_string()),
+            }
+        }
+        Some(result)
+    }
+}
+
+impl Store {
+    pub fn new() -> Self {
+        let mut quotas = Collection::new("q");
+        for quota in [
+            json!({
+                "name": "Mail storage",
+                "resourceType": "octets",
+                "scope": "account",
+                "types": ["Email"],
+                "hardLimit": 50000000,
+                "warnLimit": 45000000,
+            }),
+            json!({
+                "name": "Mail count",
+                "resourceType": "count",
+                "scope": "account",
+                "types": ["Email"],
+                "hardLimit": 10000,
+            }),
+        ] {
+            if let Value::Object(quota) = quota {
+                quotas.insert(quota);
+            }
+        }
+
+        Store {
+            mailboxes: Collection::new("m"),
+            emails: Collection::new("e"),
+            threads: Collection::new("t"),
+            identities: Collection::new("i"),
+            submissions: Collection::new("s"),
+            quotas,
+            blobs: AHashMap::new(),
+            next_blob_id: 0,
+        }
+    }
+
+    pub fn collection(&self, data_type: &DataType) -> Option<&Collection> {
+        match data_type {
+            DataType::Mailbox => Some(&self.mailboxes),
+            DataType::Email => Some(&self.emails),
+            DataType::Thread => Some(&self.threads),
+            DataType::Identity => Some(&self.identities),
+            DataType::EmailSubmission => Some(&self.submissions),
+            DataType::Quota => Some(&self.quotas),
+            _ => None,
+        }
+    }
+
+    pub fn collection_mut(&mut self, data_type: &DataType) -> Option<&mut Collection> {
+        match data_type {
+            DataType::Mailbox => Some(&mut self.mailboxes),
+            DataType::Email => Some(&mut self.emails),
+            DataType::Thread => Some(&mut self.threads),
+            DataType::Identity => Some(&mut self.identities),
+            DataType::EmailSubmission => Some(&mut self.submissions),
+            DataType::Quota => Some(&mut self.quotas),
+            _ => None,
+        }
+    }
+
+    pub fn states(&self) -> Vec<(DataType, String)> {
+        [
+            DataType::Mailbox,
+            DataType::Email,
+            DataType::Thread,
+            DataType::Identity,
+            DataType::EmailSubmission,
+        ]
+        .into_iter()
+        .map(|data_type| {
+            let state = self.collection(&data_type).unwrap().state();
+            (data_type, state)
+        })
+        .collect()
+    }
+
+    pub fn add_blob(&mut self, content_type: impl Into<String>, data: Vec<u8>) -> String {
+        self.next_blob_id += 1;
+        let blob_id = format!("b{}", self.next_blob_id);
+        self.blobs.insert(
+            blob_id.clone(),
+            Blob {
+                content_type: content_type.into(),
+                data,
+            },
+        );
+        blob_id
+    }
+}
+
+/// Applies a JMAP patch to an object. The path is a JSON pointer relative to the
+/// object, setting a nested value to `null` (or `false` in boolean maps) removes it.
+pub(crate) fn apply_patch(object: &mut Map<String, Value>, path: &str, value: Value) -> bool {
+    let mut segments = path
+        .split('/')
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect::<Vec<_>>();
+    let last = segments.pop().unwrap_or_default();
+    if segments.is_empty() {
+        object.insert(last, value);
+        return true;
+    }
+
+    let mut current = object;
+    for segment in segments {
+        let entry = current
+            .entry(segment)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if entry.is_null() {
+            *entry = Value::Object(Map::new());
+        }
+        match entry {
+            Value::Object(map) => current = map,
+            _ => return false,
+        }
+    }
+    if matches!(value, Value::Null | Value::Bool(false)) {
+        current.remove(&last);
+    } else {
+        current.insert(last, value);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Collection;
+
+    #[test]
+    fn changes() {
+        let mut collection = Collection::new("m");
+        let a = collection.insert(Default::default());
+        let b = collection.insert(Default::default());
+        let state = collection.state();
+        collection.touch(&a);
+        let c = collection.insert(Default::default());
+        collection.remove(&b);
+        collection.remove(&c);
+
+        let changes = collection.changes(&state, None).unwrap();
+        assert_eq!(changes.new_state, collection.state());
+        assert_eq!(changes.updated, [a.as_str()]);
+        assert_eq!(changes.destroyed, [b.as_str()]);
+        assert!(changes.created.is_empty());
+
+        let changes = collection.changes("0", Some(1)).unwrap();
+        assert!(changes.has_more_changes);
+        assert_eq!(changes.created, [a]);
+        let changes = collection.changes(&changes.new_state, Some(1)).unwrap();
+        assert_eq!(changes.created, [b]);
+
+        assert!(collection.changes("100", None).is_none());
+        assert!(collection.changes("invalid", None).is_none());
+    }
+}
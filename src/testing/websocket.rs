@@ -0,0 +1,196 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use futures_util::{SinkExt, StreamExt};
+use reqwest::header::{HeaderValue, SEC_WEBSOCKET_PROTOCOL};
+use serde_json::Value;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{Request, Response},
+    Message,
+};
+
+use crate::Error;
+
+use super::{methods::problem, MockServer, Subscriber};
+
+enum Incoming {
+    Message(Message),
+    Push(String),
+    Stalled,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WsControl {
+    Open,
+    Stall,
+    Close,
+}
+
+impl MockServer {
+    /// Accepts JMAP over WebSocket connections on a local port, which is advertised in the
+    /// session fetched by clients connected afterwards.
+    pub async fn listen_ws(&self) -> crate::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|err| Error::Internal(format!("Failed to bind WebSocket listener: {err}")))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|err| Error::Internal(format!("Failed to bind WebSocket listener: {err}")))?;
+        self.inner.lock().ws_url = Some(format!("ws://{addr}"));
+
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(server.clone().handle_ws(stream));
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Closes all open WebSocket connections.
+    pub fn close_ws(&self) {
+        self.inner.lock().ws_control.send_replace(WsControl::Close);
+    }
+
+    /// Stops reading from all open WebSocket connections without closing them, leaving
+    /// requests and pings unanswered.
+    pub fn stall_ws(&self) {
+        self.inner.lock().ws_control.send_replace(WsControl::Stall);
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn handle_ws(self, stream: TcpStream) {
+        let Ok(stream) =
+            tokio_tungstenite::accept_hdr_async(stream, |_: &Request, mut response: Response| {
+                response
+                    .headers_mut()
+                    .insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("jmap"));
+                Ok(response)
+            })
+            .await
+        else {
+            return;
+        };
+        let (mut tx, rx) = stream.split();
+        let (push_tx, mut push_rx) = mpsc::unbounded_channel();
+        let mut ws_control = self.inner.lock().ws_control.subscribe();
+        let mut incoming = Box::pin(futures_util::stream::select(
+            futures_util::stream::select(
+                rx.map(|message| message.map_or(Incoming::Closed, Incoming::Message))
+                    .chain(futures_util::stream::once(async { Incoming::Closed })),
+                async_stream::stream! {
+                    while let Some(push) = push_rx.recv().await {
+                        yield Incoming::Push(push);
+                    }
+                },
+            ),
+            async_stream::stream! {
+                while ws_control.changed().await.is_ok() {
+                    let control = *ws_control.borrow_and_update();
+                    match control {
+                        WsControl::Stall => yield Incoming::Stalled,
+                        WsControl::Close => break,
+                        WsControl::Open => (),
+                    }
+                }
+                yield Incoming::Closed;
+            },
+        ));
+
+        while let Some(incoming) = incoming.next().await {
+            let reply = match incoming {
+                Incoming::Message(Message::Text(text)) => {
+                    self.handle_ws_message(text.as_bytes(), &push_tx)
+                }
+                Incoming::Message(Message::Close(_)) | Incoming::Closed => {
+                    let _ = tx.close().await;
+                    break;
+                }
+                Incoming::Stalled => {
+                    // Stop reading, which also leaves pings unanswered, until closed
+                    let mut ws_control = self.inner.lock().ws_control.subscribe();
+                    while ws_control.changed().await.is_ok()
+                        && *ws_control.borrow() != WsControl::Close
+                    {}
+                    let _ = tx.close().await;
+                    break;
+                }
+                Incoming::Message(_) => None,
+                Incoming::Push(push) => Some(push),
+            };
+            if let Some(reply) = reply {
+                if tx.send(Message::text(reply)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_ws_message(
+        &self,
+        message: &[u8],
+        push_tx: &mpsc::UnboundedSender<String>,
+    ) -> Option<String> {
+        let request = serde_json::from_slice::<Value>(message).unwrap_or_default();
+        match request.get("@type").and_then(Value::as_str) {
+            Some("Request") => {
+                let mut response = match self.handle_request(message) {
+                    Ok(mut response) => {
+                        response["@type"] = "Response".into();
+                        response
+                    }
+                    Err(mut problem) => {
+                        problem["@type"] = "RequestError".into();
+                        problem
+                    }
+                };
+                if let Some(request_id) = request.get("id") {
+                    response["requestId"] = request_id.clone();
+                }
+                Some(response.to_string())
+            }
+            Some("WebSocketPushEnable") => {
+                let mut inner = self.inner.lock();
+                inner
+                    .subscribers
+                    .retain(|subscriber| !subscriber.tx.same_channel(push_tx));
+                inner.subscribers.push(Subscriber {
+                    types: serde_json::from_value(request["dataTypes"].clone()).unwrap_or_default(),
+                    close_after_state: false,
+                    websocket: true,
+                    tx: push_tx.clone(),
+                });
+                None
+            }
+            Some("WebSocketPushDisable") => {
+                self.inner
+                    .lock()
+                    .subscribers
+                    .retain(|subscriber| !subscriber.tx.same_channel(push_tx));
+                None
+            }
+            _ => {
+                let mut problem = problem(
+                    "urn:ietf:params:jmap:error:notRequest",
+                    "Unsupported WebSocket message.",
+                );
+                problem["@type"] = "RequestError".into();
+                Some(problem.to_string())
+            }
+        }
+    }
+}
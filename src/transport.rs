@@ -0,0 +1,479 @@
+/*
+ * Copyright Stalwart Labs LLC See the COPYING
+ * file at the top-level directory of this distribution.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use ahash::AHashSet;
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    redirect, Method, StatusCode,
+};
+
+#[cfg(feature = "blocking")]
+use reqwest::blocking::Client as HttpClient;
+#[cfg(feature = "async")]
+use reqwest::Client as HttpClient;
+
+const POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+const TCP_KEEPALIVE_SECS: u64 = 60;
+
+#[cfg(feature = "async")]
+pub type ByteStream = std::pin::Pin<
+    Box<dyn futures_util::Stream<Item = crate::Result<bytes::Bytes>> + Send + 'static>,
+>;
+
+#[cfg(feature = "blocking")]
+pub type ByteReader = Box<dyn std::io::Read + Send + 'static>;
+
+/// Streaming body of [Transport.send_stream()](Transport::send_stream) responses
+/// and [Transport.send_upload()](Transport::send_upload) requests.
+#[cfg(feature = "async")]
+pub(crate) type StreamBody = ByteStream;
+#[cfg(feature = "blocking")]
+pub(crate) type StreamBody = ByteReader;
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse<B = Vec<u8>> {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: B,
+}
+
+/// HTTP transport used by the [`Client`](crate::client::Client) to talk to the JMAP server.
+///
+/// The default implementation is [`ReqwestTransport`]. A custom transport can be
+/// installed with [ClientBuilder.transport()](crate::client::ClientBuilder::transport),
+/// for example to route requests through an existing connection pool or to an
+/// in-process test server.
+#[maybe_async::maybe_async]
+pub trait Transport: Send + Sync {
+    /// Sends a request and returns the full response body.
+    async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse>;
+
+    /// Sends a request and returns the response body as a stream of chunks.
+    ///
+    /// Streaming requests are long-lived, the request timeout should only bound waiting
+    /// for the response and not reading the body.
+    /// The default implementation buffers the response using [Transport.send()](Transport::send).
+    #[cfg(feature = "async")]
+    async fn send_stream(&self, request: HttpRequest) -> crate::Result<HttpResponse<ByteStream>> {
+        let response = self.send(request).await?;
+        let body = bytes::Bytes::from(response.body);
+        Ok(HttpResponse {
+            status: response.status,
+            headers: response.headers,
+            body: Box::pin(futures_util::stream::once(async move { Ok(body) })),
+        })
+    }
+
+    /// Sends a request and returns a reader over the response body.
+    ///
+    /// The blocking `reqwest` client bounds reading the body with the request timeout as well.
+    /// The default implementation buffers the response using [Transport.send()](Transport::send).
+    #[cfg(feature = "blocking")]
+    async fn send_stream(&self, request: HttpRequest) -> crate::Result<HttpResponse<ByteReader>> {
+        let response = self.send(request).await?;
+        Ok(HttpResponse {
+            status: response.status,
+            headers: response.headers,
+            body: Box::new(std::io::Cursor::new(response.body)),
+        })
+    }
+
+    /// Sends a request with a streaming body, used for uploads that should not be held in memory.
+    ///
+    /// The default implementation buffers the body using [Transport.send()](Transport::send).
+    #[cfg(feature = "async")]
+    async fn send_upload(
+        &self,
+        mut request: HttpRequest,
+        mut body: ByteStream,
+    ) -> crate::Result<HttpResponse> {
+        use futures_util::StreamExt;
+
+        let mut buf = Vec::new();
+        while let Some(chunk) = body.next().await {
+            buf.extend_from_slice(&chunk?);
+        }
+        request.body = Some(buf);
+        self.send(request).await
+    }
+
+    /// Sends a request with a streaming body, used for uploads that should not be held in memory.
+    ///
+    /// The default implementation buffers the body using [Transport.send()](Transport::send).
+    #[cfg(feature = "blocking")]
+    async fn send_upload(
+        &self,
+        mut request: HttpRequest,
+        mut body: ByteReader,
+    ) -> crate::Result<HttpResponse> {
+        use std::io::Read;
+
+        let mut buf = Vec::new();
+        body.read_to_end(&mut buf)
+            .map_err(|err| crate::Error::Internal(format!("Failed to read upload: {}", err)))?;
+        request.body = Some(buf);
+        self.send(request).await
+    }
+}
+
+#[maybe_async::maybe_async]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        self.as_ref().send(request).await
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_stream(&self, request: HttpRequest) -> crate::Result<HttpResponse<ByteStream>> {
+        self.as_ref().send_stream(request).await
+    }
+
+    #[cfg(feature = "blocking")]
+    async fn send_stream(&self, request: HttpRequest) -> crate::Result<HttpResponse<ByteReader>> {
+        self.as_ref().send_stream(request).await
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_upload(
+        &self,
+        request: HttpRequest,
+        body: ByteStream,
+    ) -> crate::Result<HttpResponse> {
+        self.as_ref().send_upload(request, body).await
+    }
+
+    #[cfg(feature = "blocking")]
+    async fn send_upload(
+        &self,
+        request: HttpRequest,
+        body: ByteReader,
+    ) -> crate::Result<HttpResponse> {
+        self.as_ref().send_upload(request, body).await
+    }
+}
+
+/// Default [`Transport`] backed by a single, connection-pooled `reqwest` client.
+pub struct ReqwestTransport {
+    client: HttpClient,
+}
+
+impl ReqwestTransport {
+    pub fn new(
+        trusted_hosts: Arc<AHashSet<String>>,
+        accept_invalid_certs: bool,
+        connect_timeout: Duration,
+    ) -> crate::Result<Self> {
+        Self::with_trusted_domain(trusted_hosts, None, accept_invalid_certs, connect_timeout)
+    }
+
+    /// Also follows redirects to the subdomains of `trusted_domain`.
+    pub(crate) fn with_trusted_domain(
+        trusted_hosts: Arc<AHashSet<String>>,
+        trusted_domain: Option<String>,
+        accept_invalid_certs: bool,
+        connect_timeout: Duration,
+    ) -> crate::Result<Self> {
+        HttpClient::builder()
+            .danger_accept_invalid_certs(accept_invalid_certs)
+            .redirect(redirect_policy(trusted_hosts, trusted_domain))
+            .connect_timeout(connect_timeout)
+            .pool_idle_timeout(Duration::from_secs(POOL_IDLE_TIMEOUT_SECS))
+            .tcp_keepalive(Duration::from_secs(TCP_KEEPALIVE_SECS))
+            .http2_adaptive_window(true)
+            .build()
+            .map(ReqwestTransport::from_client)
+            .map_err(Into::into)
+    }
+
+    /// Wraps an existing `reqwest` client, sharing its connection pool.
+    pub fn from_client(client: HttpClient) -> Self {
+        ReqwestTransport { client }
+    }
+
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+}
+
+#[maybe_async::maybe_async]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        let mut http_request = self
+            .client
+            .request(request.method, &request.url)
+            .headers(request.headers);
+        if let Some(timeout) = request.timeout {
+            http_request = http_request.timeout(timeout);
+        }
+        if let Some(body) = request.body {
+            http_request = http_request.body(body);
+        }
+        let response = http_request.send().await?;
+
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_stream(&self, request: HttpRequest) -> crate::Result<HttpResponse<ByteStream>> {
+        use futures_util::StreamExt;
+
+        let mut http_request = self
+            .client
+            .request(request.method, &request.url)
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            http_request = http_request.body(body);
+        }
+        // The timeout only bounds waiting for the response, the body is read for as long as it lasts
+        let response = match request.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, http_request.send())
+                .await
+                .map_err(|_| {
+                    crate::Error::Internal("Timed out waiting for a response.".to_string())
+                })??,
+            None => http_request.send().await?,
+        };
+
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: Box::pin(
+                response
+                    .bytes_stream()
+                    .map(|result| result.map_err(Into::into)),
+            ),
+        })
+    }
+
+    #[cfg(feature = "blocking")]
+    async fn send_stream(&self, request: HttpRequest) -> crate::Result<HttpResponse<ByteReader>> {
+        let mut http_request = self
+            .client
+            .request(request.method, &request.url)
+            .headers(request.headers);
+        if let Some(timeout) = request.timeout {
+            http_request = http_request.timeout(timeout);
+        }
+        if let Some(body) = request.body {
+            http_request = http_request.body(body);
+        }
+        let response = http_request.send()?;
+
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: Box::new(response),
+        })
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_upload(
+        &self,
+        request: HttpRequest,
+        body: ByteStream,
+    ) -> crate::Result<HttpResponse> {
+        let mut http_request = self
+            .client
+            .request(request.method, &request.url)
+            .headers(request.headers)
+            .body(reqwest::Body::wrap_stream(body));
+        if let Some(timeout) = request.timeout {
+            http_request = http_request.timeout(timeout);
+        }
+        let response = http_request.send().await?;
+
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+
+    #[cfg(feature = "blocking")]
+    async fn send_upload(
+        &self,
+        request: HttpRequest,
+        body: ByteReader,
+    ) -> crate::Result<HttpResponse> {
+        let mut http_request = self
+            .client
+            .request(request.method, &request.url)
+            .headers(request.headers)
+            .body(reqwest::blocking::Body::new(body));
+        if let Some(timeout) = request.timeout {
+            http_request = http_request.timeout(timeout);
+        }
+        let response = http_request.send()?;
+
+        Ok(HttpResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes()?.to_vec(),
+        })
+    }
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        HttpRequest {
+            method,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_header(mut self, name: header::HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<B> HttpResponse<B> {
+    pub fn new(status: StatusCode, headers: HeaderMap, body: B) -> Self {
+        HttpResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+}
+
+impl HttpResponse {
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+#[cfg(feature = "async")]
+impl HttpResponse<ByteStream> {
+    pub async fn collect(self) -> crate::Result<HttpResponse> {
+        use futures_util::StreamExt;
+
+        let mut body = Vec::new();
+        let mut stream = self.body;
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(HttpResponse {
+            status: self.status,
+            headers: self.headers,
+            body,
+        })
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl HttpResponse<ByteReader> {
+    pub fn collect(mut self) -> crate::Result<HttpResponse> {
+        use std::io::Read;
+
+        let mut body = Vec::new();
+        self.body
+            .read_to_end(&mut body)
+            .map_err(|err| crate::Error::Internal(format!("Failed to read response: {}", err)))?;
+        Ok(HttpResponse {
+            status: self.status,
+            headers: self.headers,
+            body,
+        })
+    }
+}
+
+pub(crate) fn redirect_policy(
+    trusted_hosts: Arc<AHashSet<String>>,
+    trusted_domain: Option<String>,
+) -> redirect::Policy {
+    redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > 5 {
+            attempt.error("Too many redirects.")
+        } else if matches!( attempt.url().host_str(), Some(host) if is_trusted_host(&trusted_hosts, trusted_domain.as_deref(), host) )
+        {
+            attempt.follow()
+        } else {
+            let message = format!(
+                "Aborting redirect request to unknown host '{}'.",
+                attempt.url().host_str().unwrap_or("")
+            );
+            attempt.error(message)
+        }
+    })
+}
+
+/// Returns whether a host is in the trusted hosts list or is a subdomain of
+/// the trusted domain.
+pub(crate) fn is_trusted_host(
+    trusted_hosts: &AHashSet<String>,
+    trusted_domain: Option<&str>,
+    host: &str,
+) -> bool {
+    trusted_hosts.contains(host)
+        || trusted_domain.is_some_and(|domain| {
+            host.strip_suffix(domain)
+                .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.'))
+        })
+}
@@ -111,7 +111,7 @@ impl Client {
         let mut request = self.build();
         let get_request = request.get_vacation_response().ids(["singleton"]);
         if let Some(properties) = properties {
-            get_request.properties(properties.into_iter());
+            get_request.properties(properties);
         }
         request
             .send_single::<VacationResponseGetResponse>()